@@ -12,16 +12,28 @@
 //! would misrepresent the protocol *and* make the shared-components equality
 //! test (§4.2) unsatisfiable.
 
-use endpoint_libs::model::{EndpointSchema, SchemaComponents, TypeRegistry, apply_meta};
+use std::collections::BTreeMap;
+
+use endpoint_libs::model::{
+    COMPONENTS_SCHEMAS_PREFIX, EndpointSchema, SchemaComponents, Type, TypeRegistry, apply_meta, relocate_refs,
+};
 use eyre::{Context, Result};
 use serde_json::{Value, json};
 
-use crate::definitions::{ErrorCodeSchema, GenService};
+use crate::definitions::{EndpointSchemaElement, ErrorCodeSchema, GenService, ServerEventElement};
 use crate::docs::Data;
 use crate::spec_common::{
-    ERROR_ENVELOPE, build_registry, collect_components, document_schemas, document_title, error_code_list,
-    visible_services,
+    ERROR_ENVELOPE, build_registry, collect_components, document_schemas, error_code_list, operational_extensions,
+    resolve_stand_ins, visible_services,
 };
+use crate::spec_config::{SESSION_TOKEN_SCHEME, SpecConfig};
+use crate::spec_output::{SpecOutput, write_document};
+
+/// Correlates a request envelope and its response.
+const REQUEST_SEQ: &str = "requestSeq";
+
+/// Correlates a stream item and the request that opened the stream.
+const STREAM_ORIGINAL_SEQ: &str = "streamOriginalSeq";
 
 /// Builds the document. Separated from writing so tests can assert on the value.
 pub fn build_asyncapi(data: &Data, public_only: bool) -> Result<Value> {
@@ -104,7 +116,7 @@ pub fn build_asyncapi(data: &Data, public_only: bool) -> Result<Value> {
         // Per-endpoint message pairs.
         for element in &service.endpoints {
             let schema = &element.schema;
-            let (request, response) = endpoint_messages(
+            let (mut request, response) = endpoint_messages(
                 service,
                 schema,
                 element.frontend_facing,
@@ -113,41 +125,145 @@ pub fn build_asyncapi(data: &Data, public_only: bool) -> Result<Value> {
                 &data.error_codes,
             )
             .with_context(|| format!("endpoint {} ({})", schema.name, schema.code))?;
+            let object = request.as_object_mut().expect("messages are objects");
+            for (key, value) in operational_extensions(&element.operational) {
+                object.entry(key).or_insert(value);
+            }
 
             messages.insert(format!("{}Request", schema.name), request);
             messages.insert(format!("{}Response", schema.name), response);
+
+            if let Some(stream) = &schema.stream_response {
+                let item = format!("{}StreamItem", schema.name);
+                let message = stream_item_message(service, element, stream, &registry)
+                    .with_context(|| format!("endpoint {} ({})", schema.name, schema.code))?;
+                messages.insert(item.clone(), message);
+                channels[&channel_name]["messages"][&item] = json!({ "$ref": format!("#/components/messages/{item}") });
+                operations.insert(
+                    format!("{}_receive{item}", service.name),
+                    json!({
+                        "action": "receive",
+                        "channel": { "$ref": format!("#/channels/{channel_name}") },
+                        "title": format!("Receive {} stream items", schema.name),
+                        "description": stream_description(element),
+                        "messages": [{ "$ref": format!("#/channels/{channel_name}/messages/{item}") }],
+                        "x-request-message": { "$ref": format!("#/components/messages/{}Request", schema.name) },
+                    }),
+                );
+            }
+        }
+    }
+
+    for service in &services {
+        let channel_name = &service.name;
+        for event in &service.events {
+            let name = format!("{}Event", event.name);
+            let message = event_message(service, event, &components, &registry)
+                .with_context(|| format!("event {} ({})", event.name, event.code))?;
+            messages.insert(name.clone(), message);
+            channels[channel_name]["messages"][&name] = json!({ "$ref": format!("#/components/messages/{name}") });
+            operations.insert(
+                format!("{}_receive{name}", service.name),
+                json!({
+                    "action": "receive",
+                    "channel": { "$ref": format!("#/channels/{channel_name}") },
+                    "title": format!("Receive {} events", event.name),
+                    "description": "Pushed by the server without a request.",
+                    "messages": [{ "$ref": format!("#/channels/{channel_name}/messages/{name}") }],
+                }),
+            );
+        }
+    }
+
+    // The cancelling side of each pairing, now that every message exists.
+    for service in &services {
+        for element in &service.endpoints {
+            if let Some(cancel) = &element.cancelled_by
+                && let Some(request) = messages.get_mut(&format!("{cancel}Request"))
+            {
+                request["x-cancels-stream"] =
+                    json!({ "$ref": format!("#/components/messages/{}StreamItem", element.schema.name) });
+            }
         }
     }
 
     channels.insert("framedJson".into(), framed_json_channel());
 
-    Ok(json!({
+    let mut document = json!({
         "asyncapi": "3.0.0",
-        "info": {
-            "title": document_title(data),
-            "version": "1.0.0",
-            "description": INFO_DESCRIPTION,
-        },
+        "info": data.spec.info_with_license_url(&data.project_name, INFO_DESCRIPTION),
         "channels": Value::Object(channels),
         "operations": Value::Object(operations),
         "components": {
             "schemas": document_schemas(&components),
             "messages": Value::Object(messages),
+            "correlationIds": {
+                REQUEST_SEQ: {
+                    "description": "The client-chosen `seq` of a request, echoed by its response.",
+                    "location": "$message.payload#/seq",
+                },
+                STREAM_ORIGINAL_SEQ: {
+                    "description": "The `seq` of the request that opened a stream, carried by each of its items.",
+                    "location": "$message.payload#/original_seq",
+                },
+            },
         },
-    }))
+    });
+    if let Some(external_docs) = data.spec.external_docs() {
+        document["info"]["externalDocs"] = external_docs;
+    }
+    if !data.spec.servers.is_empty() {
+        add_servers(&mut document, &data.spec);
+    }
+    resolve_stand_ins(&mut document, data);
+    Ok(document)
 }
 
-/// Writes `docs/asyncapi.json`.
-pub fn gen_asyncapi(data: &Data, public_only: bool) -> Result<()> {
-    let docs_dir = data.project_root.join("docs");
-    std::fs::create_dir_all(&docs_dir)?;
+/// Adds the servers of `spec.ron`, each accepting the session token or any of
+/// its security schemes. Without servers the schemes would be referenced from
+/// nowhere, so they are only emitted alongside them.
+fn add_servers(document: &mut Value, spec: &SpecConfig) {
+    let mut schemes = serde_json::Map::new();
+    schemes.insert(
+        SESSION_TOKEN_SCHEME.into(),
+        json!({
+            "type": "httpApiKey",
+            "in": "header",
+            "name": "Sec-WebSocket-Protocol",
+            "description": "Auth token passed as a WebSocket subprotocol; see AuthController.",
+        }),
+    );
+    for (name, scheme) in &spec.security_schemes {
+        schemes.insert(name.clone(), scheme.to_asyncapi());
+    }
+    let security: Vec<Value> = schemes
+        .keys()
+        .map(|name| json!({ "$ref": format!("#/components/securitySchemes/{name}") }))
+        .collect();
+
+    let mut servers = serde_json::Map::new();
+    for (name, server) in &spec.servers {
+        let mut entry = json!({
+            "host": server.host,
+            "protocol": server.protocol.as_str(),
+            "security": security,
+        });
+        if let Some(pathname) = &server.pathname {
+            entry["pathname"] = json!(pathname);
+        }
+        if let Some(description) = &server.description {
+            entry["description"] = json!(description);
+        }
+        servers.insert(name.clone(), entry);
+    }
+    document["servers"] = Value::Object(servers);
+    document["components"]["securitySchemes"] = Value::Object(schemes);
+}
 
+/// Writes `docs/asyncapi.json` or `.yaml`.
+pub fn gen_asyncapi(data: &Data, public_only: bool, output: SpecOutput) -> Result<()> {
     let document = build_asyncapi(data, public_only)?;
-    let filename = docs_dir.join("asyncapi.json");
-    let file = std::fs::File::create(&filename)
-        .with_context(|| format!("Failed to create AsyncAPI file: {}", filename.display()))?;
-    serde_json::to_writer_pretty(file, &document)?;
-    Ok(())
+    write_document(&data.project_root.join("docs"), "asyncapi", document, output)
 }
 
 const INFO_DESCRIPTION: &str = "\
@@ -193,6 +309,7 @@ fn request_envelope_message(services: &[GenService]) -> Value {
         "title": "Request envelope",
         "summary": "A client-to-server call. `method` selects the endpoint; `seq` correlates the response.",
         "contentType": "application/json",
+        "correlationId": { "$ref": format!("#/components/correlationIds/{REQUEST_SEQ}") },
         "payload": {
             "type": "object",
             "properties": {
@@ -215,6 +332,7 @@ fn response_envelope_message() -> Value {
         "title": "Response envelope",
         "summary": "A successful server-to-client reply, correlated by `seq`.",
         "contentType": "application/json",
+        "correlationId": { "$ref": format!("#/components/correlationIds/{REQUEST_SEQ}") },
         "payload": {
             "type": "object",
             "properties": {
@@ -297,7 +415,14 @@ fn endpoint_messages(
         response.insert("x-stream-response".into(), json!(true));
         response.insert(
             "summary".into(),
-            json!("Streaming: the server may send multiple response frames for one request."),
+            json!(format!(
+                "Streaming: after this response the server pushes {}StreamItem messages.",
+                schema.name
+            )),
+        );
+        response.insert(
+            "x-stream-item".into(),
+            json!({ "$ref": format!("#/components/messages/{}StreamItem", schema.name) }),
         );
     }
     response.insert("payload".into(), components.response_schema(schema, registry)?);
@@ -305,6 +430,102 @@ fn endpoint_messages(
     Ok((request, Value::Object(response)))
 }
 
+/// The `Stream` frame the server pushes for each item of a `stream_response`
+/// endpoint, with the item under `data`.
+fn stream_item_message(
+    service: &GenService,
+    element: &EndpointSchemaElement,
+    stream: &Type,
+    registry: &TypeRegistry,
+) -> Result<Value> {
+    let schema = &element.schema;
+    // The definitions the item reaches are already components; only the
+    // item's own schema is needed here.
+    let mut item = stream
+        .to_json_schema(registry, &mut BTreeMap::new())
+        .wrap_err("stream_response")?;
+    relocate_refs(&mut item, COMPONENTS_SCHEMAS_PREFIX);
+
+    let mut message = json!({
+        "name": format!("{}StreamItem", schema.name),
+        "title": format!("{} stream item", schema.name),
+        "summary": format!("One item pushed on a stream opened by {}Request.", schema.name),
+        "description": stream_description(element),
+        "contentType": "application/json",
+        "correlationId": { "$ref": format!("#/components/correlationIds/{STREAM_ORIGINAL_SEQ}") },
+        "payload": {
+            "type": "object",
+            "properties": {
+                "type": { "const": "Stream" },
+                "original_seq": {
+                    "type": "integer",
+                    "description": "The `seq` of the request that opened the stream.",
+                },
+                "method": { "const": schema.code, "description": "The endpoint code of that request." },
+                "stream_seq": { "type": "integer", "description": "The position of this item in the stream." },
+                "stream_code": { "type": "integer", "description": "The server's identifier of the stream." },
+                "data": item,
+            },
+            "required": ["type", "original_seq", "method", "stream_seq", "stream_code", "data"],
+        },
+        "x-endpoint-code": schema.code,
+        "x-service": service.name,
+    });
+    if let Some(cancel) = &element.cancelled_by {
+        message["x-cancelled-by"] = json!({ "$ref": format!("#/components/messages/{cancel}Request") });
+    }
+    Ok(message)
+}
+
+/// The `Immediate` frame of a server event: no request precedes it, so `seq`
+/// is 0 and `method` is the event code.
+fn event_message(
+    service: &GenService,
+    event: &ServerEventElement,
+    components: &SchemaComponents,
+    registry: &TypeRegistry,
+) -> Result<Value> {
+    let schema = event.endpoint_schema();
+    let mut message = json!({
+        "name": format!("{}Event", event.name),
+        "title": format!("{} event", event.name),
+        "contentType": "application/json",
+        "payload": {
+            "type": "object",
+            "properties": {
+                "type": { "const": "Immediate" },
+                "method": { "const": event.code, "description": "The event code." },
+                "seq": { "const": 0, "description": "Always 0: no request is answered." },
+                "params": components.response_schema(&schema, registry)?,
+            },
+            "required": ["type", "method", "seq", "params"],
+        },
+        "x-event-code": event.code,
+        "x-service": service.name,
+    });
+    if !event.description.is_empty() {
+        message["summary"] = json!(first_line(&event.description));
+        message["description"] = json!(event.description);
+    }
+    if !event.roles.is_empty() {
+        message["x-roles"] = json!(event.roles);
+    }
+    Ok(message)
+}
+
+/// When a stream's items arrive, and what ends them.
+fn stream_description(element: &EndpointSchemaElement) -> String {
+    let name = &element.schema.name;
+    let end = match &element.cancelled_by {
+        Some(cancel) => format!("until a {cancel}Request cancels it or the connection closes"),
+        None => "until the connection closes".to_string(),
+    };
+    format!(
+        "Pushed after a successful {name}Response, {end}. Each item's `original_seq` is the \
+         `seq` of the {name}Request that opened the stream."
+    )
+}
+
 fn first_line(text: &str) -> String {
     text.lines().next().unwrap_or_default().trim().to_string()
 }
@@ -364,6 +585,7 @@ mod tests {
             frontend_facing,
             config: RustGenConfig::default(),
             schema,
+            ..Default::default()
         }
     }
 
@@ -378,11 +600,14 @@ mod tests {
 
         Data {
             project_name: "api.example.com".into(),
+            spec: Default::default(),
             project_root: PathBuf::from("/tmp/api.example.com"),
             output_dir: PathBuf::from("/tmp/api.example.com/generated"),
             services: vec![GenService::new("userApi".into(), 1, vec![element(login, true)])],
             enums: vec![],
             structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
             error_codes: vec![],
         }
     }
@@ -405,6 +630,52 @@ mod tests {
         assert_eq!(ops["userApi_receiveResponse"]["action"], "receive");
     }
 
+    #[test]
+    fn spec_config_servers_accept_the_session_token_or_extra_schemes() {
+        let mut data = sample_data();
+        assert!(build_asyncapi(&data, false).unwrap().get("servers").is_none());
+
+        data.spec = ron::from_str(
+            r#"(
+                license: Some((name: "MIT", identifier: Some("MIT"))),
+                external_docs: Some((url: "https://docs.example.com")),
+                servers: {
+                    "production": (host: "api.example.com", protocol: Wss, pathname: Some("/ws")),
+                    "local": (host: "localhost:8443", protocol: Ws),
+                },
+                security_schemes: {"partnerJwt": Bearer(format: Some("JWT"))},
+            )"#,
+        )
+        .unwrap();
+        let doc = build_asyncapi(&data, false).unwrap();
+
+        assert_eq!(
+            doc["info"]["license"],
+            json!({ "name": "MIT", "url": "https://spdx.org/licenses/MIT.html" })
+        );
+        assert_eq!(doc["info"]["externalDocs"]["url"], "https://docs.example.com");
+        let production = &doc["servers"]["production"];
+        assert_eq!(production["host"], "api.example.com");
+        assert_eq!(production["protocol"], "wss");
+        assert_eq!(production["pathname"], "/ws");
+        assert_eq!(doc["servers"]["local"]["protocol"], "ws");
+        assert_eq!(
+            production["security"],
+            json!([
+                { "$ref": "#/components/securitySchemes/partnerJwt" },
+                { "$ref": "#/components/securitySchemes/sessionToken" },
+            ])
+        );
+        assert_eq!(
+            doc["components"]["securitySchemes"]["sessionToken"]["type"],
+            "httpApiKey"
+        );
+        assert_eq!(
+            doc["components"]["securitySchemes"]["partnerJwt"],
+            json!({ "type": "http", "scheme": "bearer", "bearerFormat": "JWT" })
+        );
+    }
+
     #[test]
     fn request_envelope_carries_the_method_map() {
         let doc = build_asyncapi(&sample_data(), false).unwrap();
@@ -425,6 +696,94 @@ mod tests {
         assert_eq!(messages["UserLoginResponse"]["x-endpoint-code"], 10000);
     }
 
+    #[test]
+    fn stream_items_get_a_message_a_receive_operation_and_their_cancellation() {
+        let mut data = sample_data();
+        let subscribe = EndpointSchema::new("SubscribePrices", 10001, vec![], vec![])
+            .with_description("Streams prices.")
+            .with_stream_response_type(Type::struct_("Price", vec![Field::new("bid", Type::Float64)]));
+        let unsubscribe = EndpointSchema::new("UnsubscribePrices", 10002, vec![], vec![]);
+        let mut streaming = element(subscribe, true);
+        streaming.cancelled_by = Some("UnsubscribePrices".into());
+        data.services[0]
+            .endpoints
+            .extend([streaming, element(unsubscribe, true)]);
+        let doc = build_asyncapi(&data, false).unwrap();
+        let messages = &doc["components"]["messages"];
+
+        let item = &messages["SubscribePricesStreamItem"];
+        assert_eq!(item["payload"]["properties"]["method"]["const"], 10001);
+        assert_eq!(
+            item["payload"]["properties"]["data"]["$ref"],
+            "#/components/schemas/Price"
+        );
+        assert_eq!(
+            item["correlationId"]["$ref"],
+            "#/components/correlationIds/streamOriginalSeq"
+        );
+        assert_eq!(
+            doc["components"]["correlationIds"]["streamOriginalSeq"]["location"],
+            "$message.payload#/original_seq"
+        );
+        assert_eq!(
+            item["x-cancelled-by"]["$ref"],
+            "#/components/messages/UnsubscribePricesRequest"
+        );
+        assert_eq!(
+            messages["UnsubscribePricesRequest"]["x-cancels-stream"]["$ref"],
+            "#/components/messages/SubscribePricesStreamItem"
+        );
+        assert_eq!(
+            messages["SubscribePricesResponse"]["x-stream-item"]["$ref"],
+            "#/components/messages/SubscribePricesStreamItem"
+        );
+
+        let receive = &doc["operations"]["userApi_receiveSubscribePricesStreamItem"];
+        assert_eq!(receive["action"], "receive");
+        assert_eq!(
+            receive["messages"][0]["$ref"],
+            "#/channels/userApi/messages/SubscribePricesStreamItem"
+        );
+        assert!(doc["channels"]["userApi"]["messages"]["SubscribePricesStreamItem"].is_object());
+        assert!(messages.get("UserLoginStreamItem").is_none());
+    }
+
+    #[test]
+    fn server_events_get_a_message_and_a_receive_operation() {
+        let mut data = sample_data();
+        data.services[0].events = vec![ServerEventElement {
+            service_name: "userApi".into(),
+            service_id: 1,
+            config: RustGenConfig::default(),
+            name: "PriceTick".into(),
+            code: 10900,
+            fields: vec![Field::new(
+                "price",
+                Type::struct_("Price", vec![Field::new("bid", Type::Float64)]),
+            )],
+            roles: vec![],
+            description: "A new best price.".into(),
+        }];
+        let doc = build_asyncapi(&data, true).unwrap();
+
+        let event = &doc["components"]["messages"]["PriceTickEvent"];
+        assert_eq!(event["payload"]["properties"]["method"]["const"], 10900);
+        assert_eq!(event["payload"]["properties"]["seq"]["const"], 0);
+        assert_eq!(
+            event["payload"]["properties"]["params"]["properties"]["price"]["$ref"],
+            "#/components/schemas/Price"
+        );
+        assert_eq!(event["summary"], "A new best price.");
+        assert!(doc["components"]["schemas"]["Price"].is_object());
+
+        let receive = &doc["operations"]["userApi_receivePriceTickEvent"];
+        assert_eq!(receive["action"], "receive");
+        assert_eq!(
+            receive["messages"][0]["$ref"],
+            "#/channels/userApi/messages/PriceTickEvent"
+        );
+    }
+
     #[test]
     fn framing_is_reconstructible_from_x_framing_alone() {
         let doc = build_asyncapi(&sample_data(), false).unwrap();
@@ -460,6 +819,7 @@ mod tests {
         for target in &refs {
             let resolves = target.starts_with("#/components/schemas/")
                 || target.starts_with("#/components/messages/")
+                || target.starts_with("#/components/correlationIds/")
                 || target.starts_with("#/channels/");
             assert!(resolves, "unexpected ref target: {target}");
         }
@@ -0,0 +1,243 @@
+//! Specification documents cut down to one audience or one service.
+//!
+//! Beside the merged documents, `--per-audience` writes
+//! `docs/audiences/{audience}/` and `--per-service` writes
+//! `docs/services/{serviceName}/`. Each directory holds the OpenAPI and
+//! AsyncAPI documents that are enabled and the MCP tool, resource and prompt
+//! lists, restricted to its endpoints. The documents are built from a [`Data`] holding only those
+//! endpoints, so their components are exactly the ones the endpoints reach.
+//!
+//! An endpoint joins an audience by listing it in `audiences`. Endpoints that
+//! list none are in no audience's documents: handing a partner an endpoint
+//! must be a decision, not a default.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use eyre::{Context, Result, bail};
+
+use crate::docs::{self, Data};
+use crate::spec_output::{SpecOutput, write_document};
+use crate::{asyncapi, mcp, openapi};
+
+/// Where `--per-audience` writes, relative to `docs/`.
+pub const AUDIENCES_DIR: &str = "audiences";
+
+/// Where `--per-service` writes, relative to `docs/`.
+pub const SERVICES_DIR: &str = "services";
+
+/// Which cuts to write, and what goes into each.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CutOptions {
+    pub per_audience: bool,
+    pub per_service: bool,
+    pub openapi: bool,
+    pub asyncapi: bool,
+    pub public_only: bool,
+    pub output: SpecOutput,
+}
+
+/// Every audience some endpoint lists, sorted.
+pub fn audience_names(data: &Data) -> BTreeSet<String> {
+    data.services
+        .iter()
+        .flat_map(|service| &service.endpoints)
+        .flat_map(|endpoint| endpoint.audiences.iter().cloned())
+        .collect()
+}
+
+/// Audience names become directory names, so they are restricted to
+/// `[A-Za-z0-9_-]`.
+pub fn validate_audiences(data: &Data) -> Result<()> {
+    let mut problems = vec![];
+    for service in &data.services {
+        for endpoint in &service.endpoints {
+            for audience in &endpoint.audiences {
+                let valid = !audience.is_empty()
+                    && audience
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+                if !valid {
+                    problems.push(format!(
+                        "{}.{}: audience {audience:?} may only use letters, digits, '_' and '-'",
+                        service.name, endpoint.schema.name
+                    ));
+                }
+            }
+        }
+    }
+    if !problems.is_empty() {
+        bail!("Invalid audiences:\n  {}", problems.join("\n  "));
+    }
+    Ok(())
+}
+
+/// `data` with only the endpoints in `audience`, and the MCP resources and
+/// prompts that need no other endpoint. Services left empty are dropped.
+/// Server events cannot join an audience, so none are kept.
+pub fn for_audience(data: &Data, audience: &str) -> Data {
+    let mut cut = data.clone();
+    for service in &mut cut.services {
+        service
+            .endpoints
+            .retain(|endpoint| endpoint.audiences.iter().any(|a| a == audience));
+        service.events.clear();
+        let endpoints = &service.endpoints;
+        let kept = |name: &String| endpoints.iter().any(|endpoint| &endpoint.schema.name == name);
+        service.resources.retain(|resource| kept(&resource.endpoint));
+        service.prompts.retain(|prompt| prompt.endpoints.iter().all(kept));
+    }
+    cut.services.retain(|service| !service.endpoints.is_empty());
+    cut
+}
+
+/// `data` with only the service named `service`.
+pub fn for_service(data: &Data, service: &str) -> Data {
+    let mut cut = data.clone();
+    cut.services.retain(|s| s.name == service);
+    cut
+}
+
+/// Writes every cut `options` asks for, replacing the cut directories of an
+/// earlier run so that a dropped audience or service does not linger.
+pub fn gen_cuts(data: &Data, options: CutOptions) -> Result<()> {
+    let docs_dir = data.project_root.join("docs");
+    if options.per_audience {
+        clear(&docs_dir.join(AUDIENCES_DIR))?;
+        for audience in audience_names(data) {
+            write_cut(
+                &for_audience(data, &audience),
+                &docs_dir.join(AUDIENCES_DIR).join(&audience),
+                options,
+            )
+            .with_context(|| format!("audience {audience}"))?;
+        }
+    }
+    if options.per_service {
+        clear(&docs_dir.join(SERVICES_DIR))?;
+        for service in &data.services {
+            write_cut(
+                &for_service(data, &service.name),
+                &docs_dir.join(SERVICES_DIR).join(&service.name),
+                options,
+            )
+            .with_context(|| format!("service {}", service.name))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_cut(cut: &Data, dir: &Path, options: CutOptions) -> Result<()> {
+    if options.openapi {
+        write_document(
+            dir,
+            "openapi",
+            openapi::build_openapi(cut, options.public_only)?,
+            options.output,
+        )?;
+    }
+    if options.asyncapi {
+        write_document(
+            dir,
+            "asyncapi",
+            asyncapi::build_asyncapi(cut, options.public_only)?,
+            options.output,
+        )?;
+    }
+    docs::write_mcp_tools_json(cut, dir)?;
+    mcp::write_mcp_catalog_json(cut, dir)
+}
+
+fn clear(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir).with_context(|| format!("Failed to clear {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{EndpointSchemaElement, GenService, RustGenConfig, StructElement};
+    use endpoint_libs::model::{EndpointSchema, Field, Type};
+    use std::path::PathBuf;
+
+    fn endpoint(name: &str, code: u32, returns: &str, audiences: &[&str]) -> EndpointSchemaElement {
+        EndpointSchemaElement {
+            audiences: audiences.iter().map(|a| a.to_string()).collect(),
+            schema: EndpointSchema::new(
+                name,
+                code,
+                vec![],
+                vec![Field::new("result", Type::struct_ref(returns))],
+            )
+            .with_description("Test."),
+            ..Default::default()
+        }
+    }
+
+    fn shared(name: &str) -> StructElement {
+        StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec![],
+            inner: Type::struct_(name, vec![Field::new("id", Type::Int64)]),
+        }
+    }
+
+    fn data() -> Data {
+        Data {
+            project_name: "api".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![
+                GenService::new(
+                    "userApi".into(),
+                    1,
+                    vec![
+                        endpoint("GetOrder", 10000, "Order", &["partner_a", "internal"]),
+                        endpoint("GetInvoice", 10001, "Invoice", &["internal"]),
+                    ],
+                ),
+                GenService::new("adminApi".into(), 2, vec![endpoint("GetAudit", 20000, "Audit", &[])]),
+            ],
+            enums: vec![],
+            structs: vec![shared("Order"), shared("Invoice"), shared("Audit")],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    #[test]
+    fn an_audience_document_holds_only_what_its_endpoints_reach() {
+        let data = data();
+        assert_eq!(
+            audience_names(&data).into_iter().collect::<Vec<_>>(),
+            ["internal", "partner_a"]
+        );
+
+        let doc = openapi::build_openapi(&for_audience(&data, "partner_a"), false).unwrap();
+        let paths: Vec<_> = doc["paths"].as_object().unwrap().keys().collect();
+        assert_eq!(paths, ["/userApi/get_order"]);
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        assert!(schemas.contains_key("Order"));
+        assert!(
+            !schemas.contains_key("Invoice"),
+            "pruned: no partner_a endpoint reaches it"
+        );
+        assert!(!schemas.contains_key("Audit"));
+
+        let doc = openapi::build_openapi(&for_service(&data, "adminApi"), false).unwrap();
+        let paths: Vec<_> = doc["paths"].as_object().unwrap().keys().collect();
+        assert_eq!(paths, ["/adminApi/get_audit"]);
+    }
+
+    #[test]
+    fn audience_names_must_be_directory_safe() {
+        let mut data = data();
+        data.services[0].endpoints[0].audiences.push("../partner".into());
+        let err = validate_audiences(&data).unwrap_err().to_string();
+        assert!(err.contains("userApi.GetOrder: audience \"../partner\""), "{err}");
+    }
+}
@@ -1,11 +1,15 @@
 use crate::rust::ToRust;
+use crate::rust_item::{
+    RustField, RustItem, RustItemKind, RustVariant, STRUCT_DERIVES, WORKTABLE_ENUM_DERIVES, WORKTABLE_RKYV_ATTR,
+    enum_name, struct_name, to_strings, worktable_struct_derives,
+};
 use convert_case::{Case, Casing};
 use endpoint_gen_macros::DefinitionVariant;
-use endpoint_libs::model::{EndpointSchema, Type};
-use itertools::Itertools;
+use endpoint_libs::model::{EndpointSchema, Field, Type};
 use serde::{Deserialize, Serialize};
 use smart_default::SmartDefault;
 use smart_serde_default::smart_serde_default;
+use std::collections::BTreeMap;
 
 /// Marker trait for types that can be used as Definition variants
 /// All types used in Definition must implement this to ensure they are properly validatable
@@ -20,6 +24,11 @@ pub enum Definition {
     ErrorCodeList(ErrorCodeListDefinition),
     Struct(StructElement),
     StructList(StructListDefinition),
+    TaggedUnion(TaggedUnionElement),
+    Newtype(NewtypeElement),
+    ServerEvent(ServerEventElement),
+    McpResource(McpResourceElement),
+    McpPrompt(McpPromptElement),
 }
 
 impl Definition {
@@ -30,8 +39,13 @@ impl Definition {
             Definition::ErrorCodeList(list) => list.validate_element(),
             Definition::Struct(s) => s.validate_element(),
             Definition::StructList(list) => list.validate_element(),
+            Definition::TaggedUnion(union) => union.validate_element(),
+            Definition::Newtype(newtype) => newtype.validate_element(),
             Definition::EndpointSchema(schema) => schema.validate_element(),
             Definition::EndpointSchemaList(schemas) => schemas.validate_element(),
+            Definition::ServerEvent(event) => event.validate_element(),
+            Definition::McpResource(resource) => resource.validate_element(),
+            Definition::McpPrompt(prompt) => prompt.validate_element(),
         }
     }
 }
@@ -106,140 +120,40 @@ impl GenElement<EnumListDefinition> for EnumListDefinition {
     }
 }
 
+impl EnumElement {
+    /// The item emitted into `model.rs`; see [`crate::rust_item`].
+    pub fn rust_item(&self) -> RustItem {
+        let Type::Enum { name, variants } = &self.inner else {
+            unreachable!("EnumElement is validated to wrap an enum");
+        };
+        let mut item = RustItem::unit_enum(name, self.config.prefix_enum, variants);
+        if self.config.worktable_support {
+            item.derives = to_strings(WORKTABLE_ENUM_DERIVES);
+            item.attrs = vec![WORKTABLE_RKYV_ATTR.to_string(), "#[repr(u8)]".to_string()];
+        }
+        item.with_config(&self.config)
+    }
+}
+
 impl ToRust for EnumElement {
     fn to_rust_ref(&self, _serde_with: bool) -> String {
         self.validate_element()
             .unwrap_or_else(|_| panic!("EnumElement is invalid: {self:?}"));
 
-        let name = match &self.inner {
-            Type::Enum { name, .. } => name.to_case(Case::Pascal),
+        match &self.inner {
+            Type::Enum { name, .. } => enum_name(name, self.config.prefix_enum),
             _ => unreachable!("The previous validation ensured that this type is a valid Enum"),
-        };
-
-        if self.config.prefix_enum {
-            format!("Enum{name}")
-        } else {
-            name
         }
     }
 
-    fn to_rust_decl(&self, serde_with: bool, add_derives: bool) -> String {
+    fn to_rust_decl(&self, _serde_with: bool, add_derives: bool) -> String {
         self.validate_element()
             .unwrap_or_else(|_| panic!("EnumElement is invalid: {self:?}"));
 
-        let code_regex = regex::Regex::new(r"=\s*(\d+)").expect("Error building regex to extract endpoint code");
-
-        match &self.inner {
-            Type::Enum {
-                name: _,
-                variants: fields,
-            } => {
-                let mut fields = fields
-                    .iter()
-                    .map(|x| {
-                        let variant_name = if x.name.chars().last().unwrap().is_lowercase() {
-                            x.name.to_case(Case::Pascal)
-                        } else {
-                            x.name.clone()
-                        };
-                        // A blank description must omit the `///` line entirely: an
-                        // empty doc comment trips clippy::empty_docs downstream.
-                        if x.description.trim().is_empty() {
-                            format!(
-                                r#"
-    {} = {}
-"#,
-                                variant_name, x.value
-                            )
-                        } else {
-                            format!(
-                                r#"
-    /// {}
-    {} = {}
-"#,
-                                x.description, variant_name, x.value
-                            )
-                        }
-                    })
-                    .sorted_by(|a, b| {
-                        // Sort by the endpoint code
-                        let code_a = {
-                            match code_regex.captures(a) {
-                                Some(code) => code[1].parse::<u64>().unwrap_or_else(|err| {
-                                    eprintln!("Sorting error: {err}: Rust output may not be sorted correctly");
-                                    0
-                                }),
-                                None => {
-                                    eprintln!("Sorting error: Rust output may not be sorted correctly");
-                                    0
-                                }
-                            }
-                        };
-
-                        let code_b = {
-                            match code_regex.captures(b) {
-                                Some(code) => code[1].parse::<u64>().unwrap_or_else(|err| {
-                                    eprintln!("Sorting error: {err}: Rust output may not be sorted correctly");
-                                    0
-                                }),
-                                None => {
-                                    eprintln!("Sorting error: Rust output may not be sorted correctly");
-                                    0
-                                }
-                            }
-                        };
-
-                        code_a.cmp(&code_b)
-                    });
-                let enum_content = format!(r#"pub enum {} {{{}}}"#, self.to_rust_ref(serde_with), fields.join(","));
-
-                if add_derives {
-                    self.add_derives(enum_content)
-                } else {
-                    enum_content
-                }
-            }
-            _ => unreachable!(),
-        }
-    }
-
-    fn add_derives(&self, input: String) -> String {
-        if self.config.worktable_support {
-            format!(
-                r#"#[derive(
-                    MemStat,
-                    Archive,
-                    Clone,
-                    Copy,
-                    Debug,
-                    Display,
-                    PartialEq,
-                    PartialOrd,
-                    Eq,
-                    Hash,
-                    Ord,
-                    EnumString,
-                    rkyv::Deserialize,
-                    rkyv::Serialize,
-                    serde::Serialize,
-                    serde::Deserialize,
-                    {}
-                )]
-                #[rkyv(compare(PartialEq), derive(Debug))]
-                #[repr(u8)]
-                {input}
-            "#,
-                if self.config.json_schema_gen {
-                    "JsonSchema,"
-                } else {
-                    Default::default()
-                }
-            )
-        } else {
-            Type::add_default_enum_derives(input)
-        }
+        self.rust_item().render(add_derives)
     }
 }
+
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, PartialOrd, Eq, Ord, DefinitionVariant)]
 pub struct StructListDefinition {
     #[serde(default)]
@@ -254,7 +168,7 @@ impl GenElement<StructListDefinition> for StructListDefinition {
             .iter()
             .all(|s| matches!(s.inner, Type::Struct { .. }))
         {
-            Ok(())
+            self.struct_elements.iter().try_for_each(|s| s.validate_element())
         } else {
             eyre::bail!("Not all elements of the StructListDefinition are Struct types")
         }
@@ -266,15 +180,57 @@ impl GenElement<StructListDefinition> for StructListDefinition {
 pub struct StructElement {
     #[serde(default)]
     pub config: RustGenConfig,
+    /// Type parameters, e.g. `["T"]` for `Paged<T>`. Fields refer to them as
+    /// `StructRef("T")`; see [`crate::generics`].
+    #[serde(default)]
+    pub type_params: Vec<String>,
     pub inner: Type,
 }
 
+impl StructElement {
+    /// The generics list as written after the struct name: `<T, U>`, or empty.
+    pub fn generics(&self) -> String {
+        if self.type_params.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", self.type_params.join(", "))
+        }
+    }
+
+    /// The item emitted into `model.rs`; see [`crate::rust_item`].
+    pub fn rust_item(&self, serde_with: bool) -> RustItem {
+        let Type::Struct { name, fields } = &self.inner else {
+            unreachable!("StructElement is validated to wrap a struct");
+        };
+        let mut item = RustItem::struct_(name, fields, serde_with);
+        item.generics = self.type_params.clone();
+        if self.config.worktable_support {
+            // Field types are checked up front; see
+            // crate::type_check::check_worktable_structs.
+            item.derives = worktable_struct_derives(fields);
+            item.attrs.insert(0, WORKTABLE_RKYV_ATTR.to_string());
+        }
+        item.with_config(&self.config)
+    }
+}
+
 impl GenElement<StructElement> for StructElement {
     fn validate_element(&self) -> eyre::Result<()> {
         match &self.inner {
-            Type::Struct { .. } => Ok(()),
+            Type::Struct { .. } => {}
             _ => eyre::bail!("Expected struct type"),
         }
+        for (i, param) in self.type_params.iter().enumerate() {
+            let is_ident = param.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                && param.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_ident {
+                eyre::bail!("Invalid type parameter '{param}': expected a Rust identifier");
+            }
+            if self.type_params[..i].contains(param) {
+                eyre::bail!("Duplicate type parameter '{param}'");
+            }
+        }
+        Ok(())
     }
 }
 
@@ -284,7 +240,7 @@ impl ToRust for StructElement {
             .unwrap_or_else(|_| panic!("StructElement is invalid: {self:?}"));
 
         match &self.inner {
-            Type::Struct { name, .. } => name.clone(),
+            Type::Struct { name, .. } => struct_name(name),
             _ => unreachable!("The previous validation ensured that this type is a valid Struct"),
         }
     }
@@ -293,95 +249,228 @@ impl ToRust for StructElement {
         self.validate_element()
             .unwrap_or_else(|_| panic!("StructElement is invalid: {self:?}"));
 
-        let (name, fields) = match &self.inner {
-            Type::Struct { name, fields } => (name.to_case(Case::Pascal), fields),
-            _ => unreachable!("The previous validation ensured that this type is a valid Struct"),
-        };
+        self.rust_item(serde_with).render(add_derives)
+    }
+}
 
-        let mut fields = fields.iter().map(|x| {
-            let opt = matches!(&x.ty, Type::Optional(_));
-            let serde_with_opt = match &x.ty {
-                Type::BlockchainDecimal => "rust_decimal::serde::str",
-                Type::BlockchainAddress if serde_with => "WithBlockchainAddress",
-                Type::BlockchainTransactionHash if serde_with => "WithBlockchainTransactionHash",
-                // TODO: handle optional decimals
-                // Type::Optional(t) if matches!(**t, Type::BlockchainDecimal) => {
-                //     "WithBlockchainDecimal"
-                // }
-                // Type::Optional(t) if matches!(**t, Type::BlockchainAddress) => {
-                //     "WithBlockchainAddress"
-                // }
-                // Type::Optional(t) if matches!(**t, Type::BlockchainTransactionHash) => {
-                //     "WithBlockchainTransactionHash"
-                // }
-                _ => "",
-            };
-            format!(
-                "{} {} pub {}: {}",
-                if opt { "#[serde(default)]" } else { "" },
-                if serde_with_opt.is_empty() {
-                    "".to_string()
-                } else {
-                    format!("#[serde(with = \"{serde_with_opt}\")]")
-                },
-                x.name,
-                x.ty.to_rust_ref(serde_with)
-            )
-        });
-        let input = format!("pub struct {} {{{}}}", name, fields.join(","));
+/// A sum type whose variants carry fields, serialized with an internal tag:
+/// `{"type": "Filled", "qty": 1.0}`.
+///
+/// `Type` has no variant for this, so other definitions reference a union as
+/// `StructRef("OrderEvent")`; see [`TaggedUnionElement::placeholder`].
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, PartialOrd, Eq, Ord, DefinitionVariant)]
+pub struct TaggedUnionElement {
+    #[serde(default)]
+    pub config: RustGenConfig,
+    pub name: String,
+    /// Name of the discriminator property, on the wire and in every document
+    /// exactly as written: unlike the variant fields, it is not camelCased.
+    #[serde(default = "default_union_tag")]
+    pub tag: String,
+    #[serde(default)]
+    pub description: String,
+    pub variants: Vec<TaggedVariant>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, PartialOrd, Eq, Ord)]
+pub struct TaggedVariant {
+    /// Rust variant name, also the tag value on the wire.
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+fn default_union_tag() -> String {
+    "type".to_string()
+}
 
-        if add_derives { self.add_derives(input) } else { input }
+impl TaggedUnionElement {
+    /// Name of the struct each variant's fields are lowered to for schemas,
+    /// e.g. `OrderEventFilled`.
+    pub fn variant_struct_name(&self, variant: &TaggedVariant) -> String {
+        format!("{}{}", self.name.to_case(Case::Pascal), variant.name)
     }
 
-    fn add_derives(&self, input: String) -> String {
-        if self.config.worktable_support {
-            // format!(
-            //     r#"#[derive(
-            //             Clone,
-            //             Copy,
-            //             Debug,
-            //             Default,
-            //             Eq,
-            //             Hash,
-            //             Ord,
-            //             PartialEq,
-            //             PartialOrd,
-            //             derive_more::Display,
-            //             derive_more::From,
-            //             derive_more::FromStr,
-            //             derive_more::Into,
-            //             MemStat,
-            //             rkyv::Archive,
-            //             SizeMeasure,
-            //             rkyv::Deserialize,
-            //             rkyv::Serialize,
-            //             serde::Serialize,
-            //             serde::Deserialize,
-            //             derive_more::Display,
-            //         )]
-            //         #[rkyv(compare(PartialEq), derive(Debug, PartialOrd, PartialEq, Eq, Ord))]
-            //         {input}
-            //     "#
-
-            // TODO: Fix worktable support for structs
-            format!(
-                r#" #[derive(Serialize, Deserialize, Debug, Clone, {})]
-                #[serde(rename_all = "camelCase")]
-                {input}
-            "#,
-                if self.config.json_schema_gen {
-                    "JsonSchema,"
-                } else {
-                    Default::default()
-                }
-            )
-        } else {
-            Type::add_default_struct_derives(input)
+    /// The struct registered under the union's name, so `StructRef`s to it
+    /// resolve: one optional field per variant, each typed as that variant's
+    /// struct. The spec emitters replace its schema with a `oneOf`; see
+    /// [`crate::spec_common::apply_tagged_unions`].
+    pub fn placeholder(&self) -> Type {
+        Type::struct_(
+            self.name.clone(),
+            self.variants
+                .iter()
+                .map(|v| {
+                    Field::new(
+                        v.name.clone(),
+                        Type::optional(Type::struct_(self.variant_struct_name(v), v.fields.clone())),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    /// The item emitted into `model.rs`; see [`crate::rust_item`].
+    pub fn rust_item(&self, serde_with: bool) -> RustItem {
+        let variants = self
+            .variants
+            .iter()
+            .map(|v| RustVariant {
+                doc: v.description.clone(),
+                name: v.name.clone(),
+                discriminant: None,
+                fields: v.fields.iter().map(|f| RustField::from_field(f, serde_with)).collect(),
+            })
+            .collect();
+        RustItem {
+            doc: self.description.clone(),
+            derives: to_strings(STRUCT_DERIVES),
+            // Variant fields follow the camelCase convention of generated structs.
+            attrs: vec![format!(
+                r#"#[serde(tag = "{}", rename_all_fields = "camelCase")]"#,
+                self.tag
+            )],
+            name: struct_name(&self.name),
+            generics: vec![],
+            kind: RustItemKind::Enum(variants),
+        }
+        .with_config(&self.config)
+    }
+}
+
+impl GenElement<TaggedUnionElement> for TaggedUnionElement {
+    fn validate_element(&self) -> eyre::Result<()> {
+        if self.variants.is_empty() {
+            eyre::bail!("Tagged union '{}' has no variants", self.name);
+        }
+        for (i, variant) in self.variants.iter().enumerate() {
+            let is_ident = variant.name.chars().next().is_some_and(|c| c.is_ascii_uppercase())
+                && variant.name.chars().all(|c| c.is_ascii_alphanumeric());
+            if !is_ident {
+                eyre::bail!(
+                    "Tagged union '{}': variant '{}' must be a PascalCase identifier",
+                    self.name,
+                    variant.name
+                );
+            }
+            if self.variants[..i].iter().any(|v| v.name == variant.name) {
+                eyre::bail!("Tagged union '{}': duplicate variant '{}'", self.name, variant.name);
+            }
+            // Fields go on the wire camelCased; the tag as written.
+            if let Some(field) = variant.fields.iter().find(|f| f.name.to_case(Case::Camel) == self.tag) {
+                eyre::bail!(
+                    "Tagged union '{}': variant '{}' field '{}' collides with the tag '{}'",
+                    self.name,
+                    variant.name,
+                    field.name,
+                    self.tag
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ToRust for TaggedUnionElement {
+    fn to_rust_ref(&self, _serde_with: bool) -> String {
+        struct_name(&self.name)
+    }
+
+    fn to_rust_decl(&self, serde_with: bool, add_derives: bool) -> String {
+        self.rust_item(serde_with).render(add_derives)
+    }
+}
+
+/// A distinct named type over another, e.g. `UserId` over `Int64`, emitted as
+/// a transparent tuple struct so handlers cannot mix up ids of different kinds.
+///
+/// Referenced as `StructRef("UserId")`; see [`NewtypeElement::placeholder`].
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, PartialOrd, Eq, Ord, DefinitionVariant)]
+pub struct NewtypeElement {
+    #[serde(default)]
+    pub config: RustGenConfig,
+    pub name: String,
+    pub ty: Type,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl NewtypeElement {
+    /// The struct registered under the newtype's name, so `StructRef`s to it
+    /// resolve: a single `value` field of the underlying type. The spec
+    /// emitters replace its schema with the underlying one; see
+    /// [`crate::spec_common::apply_newtypes`].
+    pub fn placeholder(&self) -> Type {
+        Type::struct_(self.name.clone(), vec![Field::new("value", self.ty.clone())])
+    }
+
+    /// Whether the underlying type is a plain value with total equality, so the
+    /// newtype can derive `Eq`, `Hash` and `Ord` (and `Copy` if `copy`).
+    fn is_key_like(&self, copy: bool) -> bool {
+        match &self.ty {
+            Type::Int32 | Type::Int64 | Type::UInt32 | Type::TimeStampMs | Type::Boolean | Type::UUID => true,
+            Type::String => !copy,
+            _ => false,
+        }
+    }
+
+    /// The item emitted into `model.rs`; see [`crate::rust_item`].
+    pub fn rust_item(&self, serde_with: bool) -> RustItem {
+        let with = crate::serde_adapters::with_attribute(&self.ty, serde_with);
+        let mut derives = to_strings(&["Serialize", "Deserialize", "Debug", "Clone", "PartialEq"]);
+        if self.is_key_like(false) {
+            derives.extend(to_strings(&["Eq", "Hash", "PartialOrd", "Ord"]));
+        }
+        if self.is_key_like(true) {
+            derives.push("Copy".to_string());
+        }
+        RustItem {
+            doc: self.description.clone(),
+            derives,
+            attrs: vec!["#[serde(transparent)]".to_string()],
+            name: struct_name(&self.name),
+            generics: vec![],
+            kind: RustItemKind::Tuple(vec![RustField {
+                attrs: if with.is_empty() { vec![] } else { vec![with] },
+                name: "0".to_string(),
+                ty: self.ty.to_rust_ref(serde_with),
+            }]),
+        }
+        .with_config(&self.config)
+    }
+}
+
+impl GenElement<NewtypeElement> for NewtypeElement {
+    fn validate_element(&self) -> eyre::Result<()> {
+        match &self.ty {
+            // A named declaration here would be a second definition in disguise.
+            Type::Struct { .. } | Type::Enum { .. } => {
+                eyre::bail!(
+                    "Newtype '{}' must wrap a primitive or a reference, not an inline declaration",
+                    self.name
+                )
+            }
+            _ => Ok(()),
         }
     }
 }
 
+impl ToRust for NewtypeElement {
+    fn to_rust_ref(&self, _serde_with: bool) -> String {
+        struct_name(&self.name)
+    }
+
+    fn to_rust_decl(&self, serde_with: bool, add_derives: bool) -> String {
+        self.rust_item(serde_with).render(add_derives)
+    }
+}
+
+/// Read through [`RawRustGenConfig`], which resolves the project defaults set
+/// by [`with_project_defaults`].
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, PartialOrd, Eq, Ord, Default)]
+#[serde(from = "RawRustGenConfig")]
 pub struct RustGenConfig {
     #[serde(default)]
     pub prefix_enum: bool,
@@ -393,6 +482,115 @@ pub struct RustGenConfig {
     pub snake_case_fields: bool,
     #[serde(default)]
     pub override_parent: bool,
+    /// Emit a Postgres table for the struct; see [`crate::sql`].
+    #[serde(default)]
+    pub sql_table: bool,
+    /// Store enum columns of the table as `text` with a `CHECK` constraint
+    /// instead of as a Postgres enum type.
+    #[serde(default)]
+    pub sql_enums_as_text: bool,
+    /// Derives added to the generated item, e.g. `["PartialEq", "utoipa::ToSchema"]`.
+    #[serde(default)]
+    pub derives: Vec<String>,
+    /// Attributes added to the generated item, written without `#[...]`:
+    /// `["serde(deny_unknown_fields)"]`.
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    /// Attributes added to fields, by field name as written in the RON.
+    #[serde(default)]
+    pub field_attributes: BTreeMap<String, Vec<String>>,
+}
+
+/// A [`RustGenConfig`] as written in the RON, where a flag left out is `None`
+/// rather than `false`, so it can take the project default instead.
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct RawRustGenConfig {
+    #[serde(default, deserialize_with = "flag")]
+    pub prefix_enum: Option<bool>,
+    #[serde(default, deserialize_with = "flag")]
+    pub worktable_support: Option<bool>,
+    #[serde(default, deserialize_with = "flag")]
+    pub json_schema_gen: Option<bool>,
+    #[serde(default, deserialize_with = "flag")]
+    pub snake_case_fields: Option<bool>,
+    #[serde(default)]
+    pub override_parent: bool,
+    #[serde(default, deserialize_with = "flag")]
+    pub sql_table: Option<bool>,
+    #[serde(default, deserialize_with = "flag")]
+    pub sql_enums_as_text: Option<bool>,
+    #[serde(default)]
+    pub derives: Vec<String>,
+    #[serde(default)]
+    pub attributes: Vec<String>,
+    #[serde(default)]
+    pub field_attributes: BTreeMap<String, Vec<String>>,
+}
+
+/// Reads a flag written as a plain `true`/`false`, not `Some(..)`.
+fn flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    bool::deserialize(deserializer).map(Some)
+}
+
+impl RawRustGenConfig {
+    /// This config on top of the project-wide `defaults`: each flag as set
+    /// here, else as in the defaults; derives and attributes from both. A
+    /// config with `override_parent` opts out of the defaults as it does out
+    /// of its parent list's config.
+    pub fn resolve(mut self, defaults: &RawRustGenConfig) -> RustGenConfig {
+        if !self.override_parent {
+            self.derives = merged(&defaults.derives, &self.derives);
+            self.attributes = merged(&defaults.attributes, &self.attributes);
+            for (field, attributes) in &defaults.field_attributes {
+                let own = self.field_attributes.remove(field).unwrap_or_default();
+                self.field_attributes.insert(field.clone(), merged(attributes, &own));
+            }
+        }
+        let flag = |own: Option<bool>, default: Option<bool>| {
+            own.or(default.filter(|_| !self.override_parent)).unwrap_or_default()
+        };
+        RustGenConfig {
+            prefix_enum: flag(self.prefix_enum, defaults.prefix_enum),
+            worktable_support: flag(self.worktable_support, defaults.worktable_support),
+            json_schema_gen: flag(self.json_schema_gen, defaults.json_schema_gen),
+            snake_case_fields: flag(self.snake_case_fields, defaults.snake_case_fields),
+            override_parent: self.override_parent,
+            sql_table: flag(self.sql_table, defaults.sql_table),
+            sql_enums_as_text: flag(self.sql_enums_as_text, defaults.sql_enums_as_text),
+            derives: self.derives,
+            attributes: self.attributes,
+            field_attributes: self.field_attributes,
+        }
+    }
+}
+
+thread_local! {
+    static PROJECT_DEFAULTS: std::cell::RefCell<RawRustGenConfig> = Default::default();
+}
+
+impl From<RawRustGenConfig> for RustGenConfig {
+    fn from(raw: RawRustGenConfig) -> Self {
+        PROJECT_DEFAULTS.with(|defaults| raw.resolve(&defaults.borrow()))
+    }
+}
+
+/// Runs `load` with `defaults` (the `rust` section of `defaults.ron`) under
+/// every [`RustGenConfig`] it deserializes.
+///
+/// The defaults have to be applied while the config is still raw: once read
+/// into a `RustGenConfig`, a flag set to `false` and one left out look the same.
+pub fn with_project_defaults<T>(defaults: &RawRustGenConfig, load: impl FnOnce() -> T) -> T {
+    let previous = PROJECT_DEFAULTS.with(|current| current.replace(defaults.clone()));
+    let result = load();
+    PROJECT_DEFAULTS.with(|current| *current.borrow_mut() = previous);
+    result
+}
+
+/// `first` then whatever of `second` it does not already contain.
+fn merged(first: &[String], second: &[String]) -> Vec<String> {
+    let mut out = first.to_vec();
+    out.extend(second.iter().filter(|s| !first.contains(s)).cloned());
+    out
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -400,11 +598,204 @@ pub struct GenService {
     pub name: String,
     pub id: u16,
     pub endpoints: Vec<EndpointSchemaElement>,
+    /// The messages the service pushes without a request, by code.
+    #[serde(default)]
+    pub events: Vec<ServerEventElement>,
+    /// Read-only endpoints the service also serves as MCP resources.
+    #[serde(default)]
+    pub resources: Vec<McpResourceElement>,
+    /// MCP prompts built on the service's endpoints.
+    #[serde(default)]
+    pub prompts: Vec<McpPromptElement>,
 }
 
 impl GenService {
     pub fn new(name: String, id: u16, endpoints: Vec<EndpointSchemaElement>) -> Self {
-        Self { name, id, endpoints }
+        Self {
+            name,
+            id,
+            endpoints,
+            events: vec![],
+            resources: vec![],
+            prompts: vec![],
+        }
+    }
+
+    /// The endpoint of this service called `name`.
+    pub fn endpoint(&self, name: &str) -> Option<&EndpointSchemaElement> {
+        self.endpoints.iter().find(|endpoint| endpoint.schema.name == name)
+    }
+}
+
+/// A message the server sends without a request, such as a maintenance notice
+/// or a price tick. `code` is sent as the frame's `method`, so it shares the
+/// code space of the endpoints. Emitted into `model.rs` as `{name}Event` with
+/// a `WsPush` impl.
+#[derive(Clone, Debug, Serialize, Deserialize, DefinitionVariant)]
+pub struct ServerEventElement {
+    pub service_name: String,
+    pub service_id: u16,
+    #[serde(default)]
+    pub config: RustGenConfig,
+    pub name: String,
+    pub code: u32,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+    /// Who receives the event, in the `Enum::Variant` form of endpoint roles.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl ServerEventElement {
+    /// The event as an endpoint with no parameters and its payload as
+    /// `returns`, so that the registry and schema components built for
+    /// endpoints describe it too.
+    pub fn endpoint_schema(&self) -> EndpointSchema {
+        EndpointSchema::new(self.name.clone(), self.code, vec![], self.fields.clone())
+            .with_description(self.description.clone())
+            .with_roles(self.roles.clone())
+    }
+
+    /// The payload struct, `{name}Event`.
+    pub fn payload(&self) -> Type {
+        Type::struct_(format!("{}Event", self.name), self.fields.clone())
+    }
+}
+
+impl GenElement<ServerEventElement> for ServerEventElement {
+    fn validate_element(&self) -> eyre::Result<()> {
+        if self.name.is_empty() {
+            eyre::bail!("Server event with code {} has no name", self.code);
+        }
+        Ok(())
+    }
+}
+
+/// A read-only endpoint served as an MCP resource. An endpoint without
+/// parameters is a concrete resource; one with parameters is a resource
+/// template whose variables are the parameters.
+#[derive(Clone, Debug, Serialize, Deserialize, DefinitionVariant)]
+pub struct McpResourceElement {
+    pub service_name: String,
+    pub service_id: u16,
+    pub name: String,
+    /// The endpoint of the service that reads the resource.
+    pub endpoint: String,
+    /// An RFC 6570 URI template, instead of the one built by
+    /// [`McpResourceElement::uri_template`].
+    #[serde(default)]
+    pub uri_template: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Falls back to the endpoint's description.
+    #[serde(default)]
+    pub description: String,
+    /// Falls back to `application/json`.
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+impl McpResourceElement {
+    /// The declared URI template, else `{service}://{tool name}` followed by
+    /// the required parameters as path segments and the optional ones as a
+    /// query, in wire (camelCase) names: `userapi://get_order/{orderId}{?verbose}`.
+    /// The scheme is the service name in lowercase, with `_` as `-`.
+    pub fn uri_template(&self, service_name: &str, endpoint: &EndpointSchemaElement) -> String {
+        if let Some(template) = &self.uri_template {
+            return template.clone();
+        }
+        let mut template = format!(
+            "{}://{}",
+            service_name.to_lowercase().replace('_', "-"),
+            endpoint.tool_name()
+        );
+        let (optional, required): (Vec<_>, Vec<_>) = endpoint
+            .schema
+            .parameters
+            .iter()
+            .partition(|field| matches!(field.ty, Type::Optional(_)));
+        for field in required {
+            template.push_str(&format!("/{{{}}}", field.name.to_case(Case::Camel)));
+        }
+        if !optional.is_empty() {
+            let names = optional
+                .iter()
+                .map(|field| field.name.to_case(Case::Camel))
+                .collect::<Vec<_>>()
+                .join(",");
+            template.push_str(&format!("{{?{names}}}"));
+        }
+        template
+    }
+
+    pub fn mime_type(&self) -> &str {
+        self.mime_type.as_deref().unwrap_or("application/json")
+    }
+}
+
+impl GenElement<McpResourceElement> for McpResourceElement {
+    fn validate_element(&self) -> eyre::Result<()> {
+        if self.name.is_empty() {
+            eyre::bail!("MCP resource for endpoint '{}' has no name", self.endpoint);
+        }
+        Ok(())
+    }
+}
+
+/// A reusable MCP prompt: a user message with `{argument}` placeholders that
+/// has the agent call some of the service's endpoints.
+#[derive(Clone, Debug, Serialize, Deserialize, DefinitionVariant)]
+pub struct McpPromptElement {
+    pub service_name: String,
+    pub service_id: u16,
+    pub name: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub arguments: Vec<McpPromptArgument>,
+    /// The endpoints of the service the prompt relies on, by endpoint name.
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    pub template: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct McpPromptArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl McpPromptElement {
+    /// The `{name}` placeholders of the template, in order of appearance.
+    pub fn placeholders(&self) -> Vec<&str> {
+        let mut placeholders = vec![];
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find('{') {
+            rest = &rest[start + 1..];
+            let Some(end) = rest.find('}') else { break };
+            let name = &rest[..end];
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                placeholders.push(name);
+                rest = &rest[end + 1..];
+            }
+        }
+        placeholders
+    }
+}
+
+impl GenElement<McpPromptElement> for McpPromptElement {
+    fn validate_element(&self) -> eyre::Result<()> {
+        if self.name.is_empty() {
+            eyre::bail!("MCP prompt has no name");
+        }
+        Ok(())
     }
 }
 
@@ -422,9 +813,128 @@ pub struct EndpointSchemaElement {
     pub frontend_facing: bool,
     #[serde(default)]
     pub config: RustGenConfig,
+    /// The GraphQL root type of the endpoint's field. Ignored for
+    /// `stream_response` endpoints, which are always subscriptions.
+    #[serde(default)]
+    pub graphql: GraphqlRoot,
+    /// The partner audiences whose documents include the endpoint; see
+    /// [`crate::audiences`].
+    #[serde(default)]
+    pub audiences: Vec<String>,
+    /// For a `stream_response` endpoint, the endpoint of the same service
+    /// that ends its stream, such as an unsubscribe.
+    #[serde(default)]
+    pub cancelled_by: Option<String>,
+    /// What clients may rely on when calling the endpoint.
+    #[serde(default)]
+    pub operational: Operational,
+    /// How the endpoint appears to MCP clients.
+    #[serde(default)]
+    pub mcp: McpConfig,
     pub schema: EndpointSchema,
 }
 
+impl EndpointSchemaElement {
+    /// The MCP tool name: `mcp.name`, else the endpoint name in snake_case.
+    pub fn tool_name(&self) -> String {
+        self.mcp.name.clone().unwrap_or_else(|| self.schema.tool_name())
+    }
+
+    /// Whether the endpoint changes nothing: `mcp.read_only`, else `operational.safe`.
+    pub fn is_read_only(&self) -> bool {
+        self.mcp.read_only.unwrap_or(self.operational.safe)
+    }
+}
+
+/// The MCP section of an endpoint. The annotations left unset are inferred
+/// from [`Operational`] where it says anything.
+#[smart_serde_default]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, SmartDefault)]
+pub struct McpConfig {
+    /// Whether the endpoint is an MCP tool at all.
+    #[smart_default(true)]
+    pub expose: bool,
+    /// The tool name, instead of the endpoint name in snake_case.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// A human-readable name for the tool.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The tool changes nothing (`readOnlyHint`); `operational.safe` implies it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    /// The tool may delete or overwrite (`destructiveHint`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destructive: Option<bool>,
+    /// Repeating a call has no further effect (`idempotentHint`); implied by `operational`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotent: Option<bool>,
+    /// The tool reaches beyond this service, e.g. to an exchange (`openWorldHint`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_world: Option<bool>,
+    /// Guidance for agents, appended to the tool description.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<String>,
+}
+
+impl McpConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Rate limits, timeout, retry safety, payload size and cost of an endpoint.
+/// Emitted as consts on the request type, as `x-` extensions in the
+/// specification documents and as MCP tool annotations.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Operational {
+    /// Requests allowed per window, by role in the `Enum::Variant` form of
+    /// the endpoint's `roles`.
+    #[serde(default)]
+    pub rate_limits: BTreeMap<String, RateLimit>,
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Sending the request twice has the effect of sending it once.
+    #[serde(default)]
+    pub idempotent: bool,
+    /// The request changes nothing. Implies `idempotent`.
+    #[serde(default)]
+    pub safe: bool,
+    #[serde(default)]
+    pub max_payload_bytes: Option<u64>,
+    /// The weight of one call against a budget shared by the endpoints.
+    #[serde(default)]
+    pub cost: Option<u32>,
+}
+
+impl Operational {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether a retry is harmless: declared idempotent, or safe.
+    pub fn is_idempotent(&self) -> bool {
+        self.idempotent || self.safe
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per_seconds: u32,
+}
+
+/// Which GraphQL root type an endpoint's field belongs to; see [`crate::graphql`].
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GraphqlRoot {
+    /// `Query` if a word of the endpoint name reads as a lookup, such as `Get`
+    /// or `List`, otherwise `Mutation`.
+    #[default]
+    Inferred,
+    Query,
+    Mutation,
+}
+
 impl From<EndpointSchemaElement> for EndpointSchema {
     fn from(val: EndpointSchemaElement) -> Self {
         // Was a field-by-field copy, which `#[non_exhaustive]` (endpoint-libs 2.0)
@@ -489,4 +999,154 @@ mod tests {
         assert!(decl.contains("Bare = 1"));
         assert!(decl.contains("Blank = 2"));
     }
+
+    fn order_event() -> TaggedUnionElement {
+        TaggedUnionElement {
+            config: RustGenConfig::default(),
+            name: "OrderEvent".into(),
+            tag: default_union_tag(),
+            description: "Something that happened to an order.".into(),
+            variants: vec![
+                TaggedVariant {
+                    name: "Filled".into(),
+                    description: "The order was filled.".into(),
+                    fields: vec![
+                        Field::new("filled_qty", Type::Float64),
+                        Field::new("price", Type::Float64),
+                    ],
+                },
+                TaggedVariant {
+                    name: "Cancelled".into(),
+                    description: "The order was cancelled.".into(),
+                    fields: vec![Field::new("reason", Type::optional(Type::String))],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn tagged_union_decl_is_an_internally_tagged_enum() {
+        let decl = order_event().to_rust_decl(false, true);
+        assert!(
+            decl.contains(r#"#[serde(tag = "type", rename_all_fields = "camelCase")]"#),
+            "{decl}"
+        );
+        assert!(decl.contains("pub enum OrderEvent"), "{decl}");
+        assert!(decl.contains("/// The order was filled.\n    Filled {"), "{decl}");
+        assert!(decl.contains("filled_qty: f64"), "{decl}");
+        assert!(decl.contains("#[serde(default)] reason: Option<String>"), "{decl}");
+    }
+
+    #[test]
+    fn a_snake_case_tag_is_spelled_as_written_everywhere() {
+        let mut union = order_event();
+        union.tag = "event_kind".into();
+        // The field goes on the wire as `eventKind`, which is not the tag.
+        union.variants[1].fields.push(Field::new("eventKind", Type::String));
+        union.validate_element().unwrap();
+
+        let decl = union.to_rust_decl(false, true);
+        assert!(decl.contains(r#"#[serde(tag = "event_kind""#), "{decl}");
+
+        let mut doc = serde_json::json!({ "components": { "schemas": {
+            "OrderEvent": {},
+            "OrderEventFilled": { "properties": {}, "required": [] },
+        } } });
+        crate::spec_common::apply_tagged_unions(&mut doc, &[union]);
+        let schemas = &doc["components"]["schemas"];
+        assert_eq!(schemas["OrderEvent"]["discriminator"]["propertyName"], "event_kind");
+        assert_eq!(
+            schemas["OrderEventFilled"]["required"],
+            serde_json::json!(["event_kind"])
+        );
+        assert_eq!(
+            schemas["OrderEventFilled"]["properties"]["event_kind"]["const"],
+            "Filled"
+        );
+    }
+
+    #[test]
+    fn tagged_union_rejects_a_field_colliding_with_the_tag() {
+        let mut union = order_event();
+        assert!(union.validate_element().is_ok());
+
+        union.variants[1].fields.push(Field::new("type", Type::String));
+        let err = union.validate_element().unwrap_err().to_string();
+        assert!(err.contains("field 'type' collides with the tag 'type'"), "{err}");
+
+        let mut union = order_event();
+        union.variants[1].name = "Filled".into();
+        assert!(
+            union
+                .validate_element()
+                .unwrap_err()
+                .to_string()
+                .contains("duplicate variant")
+        );
+    }
+
+    #[test]
+    fn newtype_decl_is_a_transparent_tuple_struct() {
+        let user_id = NewtypeElement {
+            config: RustGenConfig::default(),
+            name: "UserId".into(),
+            ty: Type::Int64,
+            description: "Identifies a user.".into(),
+        };
+        let decl = user_id.to_rust_decl(false, true);
+        assert!(decl.contains("#[serde(transparent)]"), "{decl}");
+        assert!(
+            decl.contains("/// Identifies a user.\n#[derive(") && decl.contains("pub struct UserId(pub i64);"),
+            "{decl}"
+        );
+        assert!(decl.contains("Copy") && decl.contains("Hash"), "{decl}");
+
+        let price = NewtypeElement {
+            name: "Price".into(),
+            ty: Type::Float64,
+            ..user_id
+        };
+        let decl = price.to_rust_decl(false, true);
+        assert!(!decl.contains("Eq,") && !decl.contains("Copy"), "{decl}");
+    }
+
+    #[test]
+    fn defaults_merge_under_a_config_unless_it_overrides() {
+        let defaults: RawRustGenConfig = ron::from_str(
+            r#"(json_schema_gen: true, prefix_enum: true, derives: ["PartialEq"], field_attributes: {"id": ["serde(alias = \"ID\")"]})"#,
+        )
+        .unwrap();
+        let own: RawRustGenConfig = ron::from_str(
+            r#"(json_schema_gen: false, derives: ["Hash", "PartialEq"], field_attributes: {"id": ["serde(default)"]})"#,
+        )
+        .unwrap();
+
+        let merged = own.clone().resolve(&defaults);
+        assert!(!merged.json_schema_gen, "an element can switch a default flag off");
+        assert!(merged.prefix_enum);
+        assert!(!merged.worktable_support);
+        assert_eq!(merged.derives, ["PartialEq", "Hash"]);
+        assert_eq!(
+            merged.field_attributes["id"],
+            ["serde(alias = \"ID\")", "serde(default)"]
+        );
+
+        let overriding = RawRustGenConfig {
+            override_parent: true,
+            ..own
+        };
+        let resolved = overriding.resolve(&defaults);
+        assert!(!resolved.prefix_enum);
+        assert_eq!(resolved.derives, ["Hash", "PartialEq"]);
+    }
+
+    #[test]
+    fn project_defaults_apply_while_deserializing() {
+        let defaults: RawRustGenConfig = ron::from_str("(prefix_enum: true, sql_table: true)").unwrap();
+        let config: RustGenConfig = with_project_defaults(&defaults, || ron::from_str("(sql_table: false)")).unwrap();
+        assert!(config.prefix_enum && !config.sql_table);
+
+        let config: RustGenConfig = ron::from_str("()").unwrap();
+        assert!(!config.prefix_enum, "the defaults are gone once loading is done");
+    }
 }
@@ -1,15 +1,21 @@
-use crate::definitions::{EnumElement, ErrorCodeSchema, GenService, StructElement};
+use crate::definitions::{
+    EndpointSchemaElement, EnumElement, ErrorCodeSchema, GenService, NewtypeElement, StructElement, TaggedUnionElement,
+};
 use crate::rust::ToRust;
 use crate::service::get_systemd_service;
+use crate::spec_config::SpecConfig;
+use crate::spec_output::{SPEC_DOCUMENTS, SPLIT_SCHEMAS_DIR, SpecFormat, SpecOutput};
 use convert_case::{Case, Casing};
 use endpoint_libs::model::{EndpointSchema, Service, Type};
 use eyre::Context;
 use itertools::Itertools;
 use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{File, create_dir_all};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 
+#[derive(Clone)]
 pub struct Data {
     /// Human-facing name of the project, used as the title of the emitted
     /// specification documents.
@@ -19,11 +25,16 @@ pub struct Data {
     /// make every document differ from its committed copy on every check.
     /// Document identity is data, not an artefact of where it was written.
     pub project_name: String,
+    /// The `info`, servers and security schemes of the specification
+    /// documents, from `spec.ron`.
+    pub spec: SpecConfig,
     pub project_root: PathBuf,
     pub output_dir: PathBuf,
     pub services: Vec<GenService>,
     pub enums: Vec<EnumElement>,
     pub structs: Vec<StructElement>,
+    pub unions: Vec<TaggedUnionElement>,
+    pub newtypes: Vec<NewtypeElement>,
     pub error_codes: Vec<ErrorCodeSchema>,
 }
 
@@ -38,7 +49,8 @@ pub fn gen_services_docs(docs: &Data) -> eyre::Result<()> {
     let mut docs_file = File::create(&docs_filename)
         .with_context(|| format!("Failed to create docs file: {}", docs_filename.display()))?;
 
-    // Only write FE facing endpoints to the services.json file
+    // Only write FE facing endpoints to the services.json file. Events are
+    // pushed to the frontend, so they all go in.
     let services = docs
         .services
         .clone()
@@ -50,10 +62,28 @@ pub fn gen_services_docs(docs: &Data) -> eyre::Result<()> {
                 .filter(|endpoint| endpoint.frontend_facing)
                 .collect();
 
-            Service::new(service.name, service.id, fe_endpoints)
+            (Service::new(service.name, service.id, fe_endpoints), service.events)
         })
-        .filter(|service| !service.endpoints.is_empty())
-        .collect::<Vec<Service>>();
+        .filter(|(service, events)| !service.endpoints.is_empty() || !events.is_empty())
+        .map(|(service, events)| {
+            let mut value = serde_json::to_value(service)?;
+            if !events.is_empty() {
+                value["events"] = events
+                    .iter()
+                    .map(|event| {
+                        json!({
+                            "name": event.name,
+                            "code": event.code,
+                            "description": event.description,
+                            "fields": event.fields,
+                            "roles": event.roles,
+                        })
+                    })
+                    .collect();
+            }
+            Ok(value)
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
 
     let enums = doc_enums(docs);
 
@@ -155,9 +185,7 @@ fn format_type(field_name: &str, ty: &Type, datamodels: bool) -> String {
             format!(
                 "{}: {}",
                 field_name.to_case(Case::Camel),
-                prefixed_name
-                    .then(|| format!("Enum{}", name.to_case(Case::Pascal)))
-                    .unwrap_or(name.to_case(Case::Pascal))
+                crate::rust_item::enum_name(name, *prefixed_name)
             )
         }
         // Type::DataTable { name, fields } => {
@@ -178,6 +206,11 @@ fn format_type(field_name: &str, ty: &Type, datamodels: bool) -> String {
         //         )
         //     )
         // }
+        Type::StructRef(name) if crate::maps::parse_map_ref(name).is_some() => format!(
+            "{}: {}",
+            field_name.to_case(Case::Camel),
+            crate::maps::display_name(name).unwrap_or_else(|| name.clone())
+        ),
         _ => format!("{}: {}", field_name.to_case(Case::Camel), ty.to_rust_ref(false)),
     }
 }
@@ -228,10 +261,20 @@ pub fn gen_md_docs(data: &Data) -> eyre::Result<()> {
         "#,
         data.structs
             .iter()
-            .map(|s| format!(
-                "struct {:#}\n",
-                format_type(&s.inner.to_rust_ref(false), &s.inner, true)
-            ))
+            .map(|s| {
+                let name = s.inner.to_rust_ref(false);
+                let decl = format_type(&name, &s.inner, true);
+                // Type parameters go straight after the name: `struct Paged<T>{ .. }`.
+                format!(
+                    "struct {:#}\n",
+                    decl.replacen(&name, &format!("{name}{}", s.generics()), 1)
+                )
+            })
+            .chain(
+                data.newtypes
+                    .iter()
+                    .map(|n| format!("struct {}({})\n", n.name, n.ty.to_rust_ref(false)))
+            )
             .join("\n\n"),
         data.enums
             .iter()
@@ -240,17 +283,48 @@ pub fn gen_md_docs(data: &Data) -> eyre::Result<()> {
             .map(|e| format!("enum {:#}\n", format_type(&e.to_rust_ref(false), &e, true)))
             .join("\n\n")
     )?;
+    if !data.unions.is_empty() {
+        writeln!(
+            &mut docs_file,
+            r#"
+## Tagged Unions
+
+|Union|Variant|Fields|Description|
+|-----------|-----------|----------|-----------|"#
+        )?;
+        for union in &data.unions {
+            for variant in &union.variants {
+                writeln!(
+                    &mut docs_file,
+                    "|{}|{}|{}|{}|",
+                    union.name,
+                    wrap_code_md(format!("{}: \"{}\"", union.tag, variant.name)),
+                    variant
+                        .fields
+                        .iter()
+                        .map(|x| wrap_code_md(format_type(&x.name, &x.ty, false)))
+                        .join(", "),
+                    variant.description
+                )?;
+            }
+        }
+    }
     for s in &data.services {
         writeln!(
             &mut docs_file,
             r#"
 ## {} Server
-ID: {}
-### Endpoints
-|Code|Name|Parameters|Response|Description|FE Facing|Errors|
-|-----------|-----------|----------|--------|-----------|-----------|-----------|"#,
+ID: {}"#,
             s.name, s.id
         )?;
+        if !s.endpoints.is_empty() {
+            writeln!(
+                &mut docs_file,
+                r#"### Endpoints
+|Code|Name|Parameters|Response|Description|FE Facing|Errors|
+|-----------|-----------|----------|--------|-----------|-----------|-----------|"#
+            )?;
+        }
         for e in &s.endpoints {
             writeln!(
                 &mut docs_file,
@@ -272,61 +346,249 @@ ID: {}
                 format_errors(&e.schema.errors),
             )?;
         }
+        if !s.events.is_empty() {
+            writeln!(
+                &mut docs_file,
+                r#"### Events
+|Code|Name|Fields|Description|Roles|
+|-----------|-----------|----------|-----------|-----------|"#
+            )?;
+        }
+        for e in &s.events {
+            writeln!(
+                &mut docs_file,
+                "|{}|{}|{}|{}|{}|",
+                e.code,
+                e.name,
+                e.fields
+                    .iter()
+                    .map(|x| wrap_code_md(format_type(&x.name, &x.ty, false)))
+                    .join(", "),
+                e.description,
+                e.roles.join(", "),
+            )?;
+        }
     }
     Ok(())
 }
 
 /// Writes `docs/<service>_mcp_tools.json` for each service: the MCP tool list
-/// (name, description, inputSchema, outputSchema) exactly as a server built
-/// from these schemas will report it via `tools/list`. Intended for review —
-/// schema changes show up as diffs in these files.
+/// (name, description, inputSchema, outputSchema) as a server built from these
+/// schemas reports it via `tools/list`. Intended for review — schema changes
+/// show up as diffs in these files. The `mcp` section's exposure, name, title
+/// and annotations apply here only: endpoint-libs 2.1 lists every endpoint
+/// under `schema.tool_name()`, with description and schemas alone.
 pub fn gen_mcp_tools_json(data: &Data) -> eyre::Result<()> {
-    let docs_dir = data.project_root.join("docs");
-    create_dir_all(&docs_dir)?;
+    write_mcp_tools_json(data, &data.project_root.join("docs"))
+}
 
-    let mut registry = endpoint_libs::model::TypeRegistry::new();
-    registry.add_all(crate::rust::shared_type_definitions(data).iter());
-    for service in &data.services {
-        for endpoint in &service.endpoints {
-            registry.add_endpoint(&endpoint.schema);
+/// [`gen_mcp_tools_json`] into `docs_dir`.
+pub fn write_mcp_tools_json(data: &Data, docs_dir: &Path) -> eyre::Result<()> {
+    create_dir_all(docs_dir)?;
+
+    let registry = crate::spec_common::build_registry(data);
+    // Stand-in structs (unions, newtypes, generic instances) are resolved below
+    // the same way as in the spec documents, so these files show the intended
+    // schemas; the runtime registry only has the stand-ins.
+
+    for service in data.services.iter().filter(|s| !s.endpoints.is_empty()) {
+        let tools = service
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.mcp.expose)
+            .map(|endpoint| mcp_tool(endpoint, data, &registry))
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        let filename = docs_dir.join(format!("{}_mcp_tools.json", service.name));
+        if tools.is_empty() {
+            // Every endpoint is hidden from agents; drop the file of an earlier run.
+            if filename.exists() {
+                std::fs::remove_file(&filename).with_context(|| format!("Failed to remove {}", filename.display()))?;
+            }
+            continue;
         }
+        write_tools_file(&filename, tools)?;
     }
+    Ok(())
+}
 
+/// Where the per-role tool lists go, relative to `docs/`.
+pub const MCP_ROLES_DIR: &str = "mcp";
+
+/// Writes `docs/mcp/<role>/<service>_mcp_tools.json` for every variant of the
+/// enums that endpoint roles are drawn from: the tools `tools/list` offers a
+/// caller holding only that role. Like the runtime, this compares role ids, so
+/// an endpoint without roles is offered to no one. A role offered none of a
+/// service's tools gets no file for it. `<role>` is the variant in snake_case.
+///
+/// The tool lists of an earlier run are removed first, so that a dropped role
+/// does not linger; nothing else under `docs/mcp/` is touched.
+pub fn gen_mcp_tools_by_role(data: &Data) -> eyre::Result<()> {
+    let roles_dir = data.project_root.join("docs").join(MCP_ROLES_DIR);
+    let earlier: Vec<PathBuf> = walkdir::WalkDir::new(&roles_dir)
+        .min_depth(2)
+        .max_depth(2)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|path| path.strip_prefix(&roles_dir).is_ok_and(is_role_tools_file))
+        .collect();
+    for path in earlier {
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        // Left empty, the role directory was ours too; otherwise it stays.
+        let _ = std::fs::remove_dir(path.parent().expect("role files are two levels down"));
+    }
+
+    let registry = crate::spec_common::build_registry(data);
+    // Each endpoint's tool and role ids, resolved once rather than per role.
+    let mut services = vec![];
     for service in &data.services {
         let tools = service
             .endpoints
             .iter()
+            .filter(|endpoint| endpoint.mcp.expose)
             .map(|endpoint| {
-                let schema = &endpoint.schema;
-                let mut tool = json!({
-                    "name": schema.tool_name(),
-                    "code": schema.code,
-                    "description": schema.description,
-                    "frontendFacing": endpoint.frontend_facing,
-                    "inputSchema": schema.to_mcp_input_schema(&registry).with_context(|| {
-                        format!("endpoint {} ({})", schema.name, schema.code)
-                    })?,
-                });
-                if !schema.returns.is_empty() {
-                    tool["outputSchema"] = schema
-                        .to_mcp_output_schema(&registry)
-                        .with_context(|| format!("endpoint {} ({})", schema.name, schema.code))?;
-                }
-                if schema.stream_response.is_some() {
-                    tool["streaming"] = json!(true);
-                }
-                Ok(tool)
+                let role_ids = crate::rust::resolve_roles_ids(&endpoint.schema.roles, &data.enums);
+                Ok((mcp_tool(endpoint, data, &registry)?, role_ids))
             })
             .collect::<eyre::Result<Vec<_>>>()?;
+        services.push((&service.name, tools));
+    }
 
-        let filename = docs_dir.join(format!("{}_mcp_tools.json", service.name));
-        let file = File::create(&filename)
-            .with_context(|| format!("Failed to create MCP tools file: {}", filename.display()))?;
-        serde_json::to_writer_pretty(file, &json!({ "tools": tools }))?;
+    let mut dirs: BTreeMap<String, String> = BTreeMap::new();
+    for (role, id) in role_variants(data) {
+        let dir = role
+            .split_once("::")
+            .map_or(role.as_str(), |(_, variant)| variant)
+            .to_case(Case::Snake);
+        if let Some(other) = dirs.insert(dir.clone(), role.clone()) {
+            eyre::bail!("Roles {other} and {role} would share docs/{MCP_ROLES_DIR}/{dir}");
+        }
+        for (service, tools) in &services {
+            let offered: Vec<_> = tools
+                .iter()
+                .filter(|(_, role_ids)| role_ids.contains(&id))
+                .map(|(tool, _)| tool.clone())
+                .collect();
+            if offered.is_empty() {
+                continue;
+            }
+            create_dir_all(roles_dir.join(&dir))?;
+            write_tools_file(&roles_dir.join(&dir).join(format!("{service}_mcp_tools.json")), offered)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `rel`, relative to `docs/mcp/`, is a tool list
+/// [`gen_mcp_tools_by_role`] writes: `<role>/<service>_mcp_tools.json`.
+pub fn is_role_tools_file(rel: &Path) -> bool {
+    rel.components().count() == 2 && rel.to_string_lossy().ends_with("_mcp_tools.json")
+}
+
+/// Every variant of the enums named by endpoint roles, as `Enum::Variant` and
+/// the id the runtime compares.
+fn role_variants(data: &Data) -> Vec<(String, i64)> {
+    let role_enums: BTreeSet<&str> = data
+        .services
+        .iter()
+        .flat_map(|service| &service.endpoints)
+        .flat_map(|endpoint| &endpoint.schema.roles)
+        .filter_map(|role| role.split_once("::").map(|(enum_name, _)| enum_name))
+        .collect();
+    let mut variants = vec![];
+    for element in &data.enums {
+        let enum_name = element.to_rust_ref(false);
+        if !role_enums.contains(enum_name.as_str()) {
+            continue;
+        }
+        if let Type::Enum { variants: members, .. } = &element.inner {
+            variants.extend(
+                members
+                    .iter()
+                    .map(|variant| (format!("{enum_name}::{}", variant.name), variant.value)),
+            );
+        }
+    }
+    variants
+}
+
+/// The `tools/list` entry of an exposed endpoint.
+fn mcp_tool(
+    endpoint: &EndpointSchemaElement,
+    data: &Data,
+    registry: &endpoint_libs::model::TypeRegistry,
+) -> eyre::Result<serde_json::Value> {
+    let schema = &endpoint.schema;
+    let mut tool = json!({
+        "name": endpoint.tool_name(),
+        "code": schema.code,
+        "description": tool_description(endpoint),
+        "frontendFacing": endpoint.frontend_facing,
+        "inputSchema": schema.to_mcp_input_schema(registry).with_context(|| {
+            format!("endpoint {} ({})", schema.name, schema.code)
+        })?,
+    });
+    if !schema.returns.is_empty() {
+        tool["outputSchema"] = schema
+            .to_mcp_output_schema(registry)
+            .with_context(|| format!("endpoint {} ({})", schema.name, schema.code))?;
+    }
+    if schema.stream_response.is_some() {
+        tool["streaming"] = json!(true);
+    }
+    if let Some(title) = &endpoint.mcp.title {
+        tool["title"] = json!(title);
+    }
+    if let Some(annotations) = tool_annotations(endpoint) {
+        tool["annotations"] = annotations;
     }
+    crate::spec_common::resolve_stand_ins(&mut tool, data);
+    Ok(tool)
+}
+
+fn write_tools_file(filename: &Path, tools: Vec<serde_json::Value>) -> eyre::Result<()> {
+    let file =
+        File::create(filename).with_context(|| format!("Failed to create MCP tools file: {}", filename.display()))?;
+    serde_json::to_writer_pretty(file, &json!({ "tools": tools }))?;
     Ok(())
 }
 
+/// The endpoint description, followed by the `mcp.usage` notes for agents.
+pub(crate) fn tool_description(endpoint: &EndpointSchemaElement) -> String {
+    match &endpoint.mcp.usage {
+        Some(usage) => format!("{}\n\n{usage}", endpoint.schema.description),
+        None => endpoint.schema.description.clone(),
+    }
+}
+
+/// MCP tool `annotations`: the hints the `mcp` section sets, over those its
+/// `operational` metadata implies. `None` if there are none.
+fn tool_annotations(endpoint: &EndpointSchemaElement) -> Option<serde_json::Value> {
+    let mcp = &endpoint.mcp;
+    let operational = &endpoint.operational;
+    let mut annotations = serde_json::Map::new();
+    if let Some(title) = &mcp.title {
+        annotations.insert("title".into(), json!(title));
+    }
+    let hints = [
+        ("readOnlyHint", mcp.read_only.or(operational.safe.then_some(true))),
+        ("destructiveHint", mcp.destructive),
+        (
+            "idempotentHint",
+            mcp.idempotent.or(operational.is_idempotent().then_some(true)),
+        ),
+        ("openWorldHint", mcp.open_world),
+    ];
+    for (key, hint) in hints {
+        if let Some(hint) = hint {
+            annotations.insert(key.into(), json!(hint));
+        }
+    }
+    (!annotations.is_empty()).then_some(serde_json::Value::Object(annotations))
+}
+
 pub fn gen_systemd_services(data: &Data, app_name: &str, user: &str) -> eyre::Result<()> {
     create_dir_all(data.project_root.join("etc").join("systemd"))?;
 
@@ -364,46 +626,76 @@ pub fn gen_error_message_md(root: &Path, codes: &[ErrorCodeSchema]) -> eyre::Res
     Ok(())
 }
 
-/// Writes `docs/openapi-README.md`: what the two specification documents are,
+/// Writes `docs/openapi-README.md`: what the specification documents are,
 /// the synthetic-path warning, and how to consume them.
 ///
 /// Generated rather than hand-written so it cannot drift out of sync with the
 /// emitters, and so every consumer repo gets it without copying a file around.
-pub fn gen_spec_readme(project_root: &Path, openapi: bool, asyncapi: bool) -> eyre::Result<()> {
+pub fn gen_spec_readme(
+    project_root: &Path,
+    openapi: bool,
+    asyncapi: bool,
+    openrpc: bool,
+    json_schema: bool,
+    gateway: bool,
+    output: SpecOutput,
+) -> eyre::Result<()> {
     let docs_dir = project_root.join("docs");
     create_dir_all(&docs_dir)?;
     let filename = docs_dir.join("openapi-README.md");
-    let mut file =
-        File::create(&filename).with_context(|| format!("Failed to create spec README: {}", filename.display()))?;
 
     // The table lists only what was actually emitted — the specs are opt-in, so
     // a README promising an openapi.json that does not exist would be a lie.
-    write!(&mut file, "{SPEC_README_HEAD}")?;
+    let mut readme = SPEC_README_HEAD.to_string();
     if asyncapi {
-        writeln!(
-            &mut file,
-            "| `asyncapi.json` | AsyncAPI 3.0. **The authoritative description of the wire protocol.** |"
-        )?;
+        readme
+            .push_str("| `asyncapi.json` | AsyncAPI 3.0. **The authoritative description of the wire protocol.** |\n");
     }
     if openapi {
-        writeln!(
-            &mut file,
-            "| `openapi.json` | OpenAPI 3.1. A projection for HTTP tooling. Not servable — see below. |"
-        )?;
+        readme.push_str("| `openapi.json` | OpenAPI 3.1. A projection for HTTP tooling. Not servable — see below. |\n");
     }
-    writeln!(
-        &mut file,
-        "| `<service>_mcp_tools.json` | The MCP tool list a server reports via `tools/list`. |"
-    )?;
-    writeln!(
-        &mut file,
-        "| `services.json`, `README.md` | Human-facing dumps of the same model. |"
-    )?;
+    if openrpc {
+        readme.push_str(
+            "| `openrpc.json` | OpenRPC 1.3. One method per endpoint; the wire method is its `x-endpoint-code`. |\n",
+        );
+    }
+    if json_schema {
+        readme.push_str(
+            "| `schemas/` | JSON Schema 2020-12: one file per type, a request and a response file per endpoint, and `bundle.json`. |\n",
+        );
+    }
+    if gateway {
+        readme.push_str(
+            "| `openapi.gateway.json` | OpenAPI 3.1 for the generated HTTP gateway. Servable through the gateway. |\n",
+        );
+    }
+    if output.split && (openapi || asyncapi || openrpc || gateway) {
+        readme.push_str(&format!(
+            "| `{SPLIT_SCHEMAS_DIR}/` | The documents' schemas, one file each, shared by every document above. |\n"
+        ));
+    }
+    readme.push_str("| `<service>_mcp_tools.json` | The MCP tool list a server reports via `tools/list`. |\n");
+    readme.push_str("| `services.json`, `README.md` | Human-facing dumps of the same model. |\n");
 
     if openapi {
-        write!(&mut file, "{SPEC_README_OPENAPI}")?;
+        readme.push_str(SPEC_README_OPENAPI);
     }
-    write!(&mut file, "{SPEC_README_TAIL}")?;
+    if gateway {
+        readme.push_str(SPEC_README_GATEWAY);
+    }
+    readme.push_str(SPEC_README_TAIL);
+
+    // The constants name the JSON files; point them at what was written.
+    if output.format != SpecFormat::Json {
+        for stem in SPEC_DOCUMENTS {
+            readme = readme.replace(
+                &format!("{stem}.json"),
+                &format!("{stem}.{}", output.format.extension()),
+            );
+        }
+    }
+    std::fs::write(&filename, readme)
+        .with_context(|| format!("Failed to create spec README: {}", filename.display()))?;
     Ok(())
 }
 
@@ -413,7 +705,7 @@ Generated by `endpoint-gen` from the RON endpoint definitions in `config/`.
 **Do not edit these by hand** — regenerate with `endpoint-gen`, and prove they
 are current with `endpoint-gen --check` (which exits non-zero on drift).
 
-The specification documents are opt-in: pass `--openapi` and/or `--asyncapi`.
+The specification documents are opt-in: pass any of `--openapi`, `--asyncapi`, `--openrpc` and `--json-schema`.
 Upgrading the generator does not add them on its own.
 
 | File | What it is |
@@ -458,9 +750,28 @@ endpoint-gen --public-only
 A mismatch means the hand-rolled MCP metadata and the emitted spec disagree, and
 one of them is lying to an agent.
 
-**Spec-driven fuzzing.** Schemathesis wants a real HTTP surface, which this
-system does not have. It stays future work behind a REST adapter that does not
-exist yet.
+**Spec-driven fuzzing.** Schemathesis wants a real HTTP surface. Generate one
+with `--gateway` and point it at `openapi.gateway.json` instead.
+"#;
+
+const SPEC_README_GATEWAY: &str = r#"
+## The gateway document
+
+`openapi.gateway.json` describes `generated/gateway.rs`, an HTTP server that
+forwards each `POST /{serviceName}/{endpoint_snake_name}` to the backend over a
+WebSocket. Unlike `openapi.json`, it is servable:
+
+- `servers` come from `config/gateway.ron`.
+- Authentication is a bearer token, which the gateway passes to the backend as
+  the WebSocket subprotocol.
+- Each error code an endpoint declares is listed under the HTTP status the
+  gateway answers it with.
+
+Streaming endpoints answer with their initial response only.
+
+```bash
+schemathesis run docs/openapi.gateway.json --url http://localhost:8080
+```
 "#;
 
 const SPEC_README_TAIL: &str = r#"
@@ -497,6 +808,7 @@ mod tests {
 
         let data = Data {
             project_name: "test".into(),
+            spec: Default::default(),
             project_root: dir.clone(),
             output_dir: dir.clone(),
             services: vec![GenService::new(
@@ -512,10 +824,17 @@ mod tests {
                         vec![Field::new("ok", Type::Boolean)],
                     )
                     .with_description("Fetches a user profile."),
+                    operational: crate::definitions::Operational {
+                        safe: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
                 }],
             )],
             enums: vec![],
             structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
             error_codes: vec![],
         };
 
@@ -528,7 +847,157 @@ mod tests {
         assert_eq!(tool["code"], json!(10010));
         assert_eq!(tool["inputSchema"]["required"], json!(["userId"]));
         assert_eq!(tool["outputSchema"]["properties"]["ok"]["type"], json!("boolean"));
+        assert_eq!(
+            tool["annotations"],
+            json!({ "readOnlyHint": true, "idempotentHint": true })
+        );
 
         std::fs::remove_dir_all(&dir).ok();
     }
+
+    #[test]
+    fn the_mcp_section_curates_the_tool() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = |name: &str, code: u32, mcp: crate::definitions::McpConfig| EndpointSchemaElement {
+            schema: EndpointSchema::new(name, code, vec![], vec![]).with_description("Deletes an app."),
+            mcp,
+            ..Default::default()
+        };
+        let data = Data {
+            project_name: "test".into(),
+            spec: Default::default(),
+            project_root: dir.path().to_path_buf(),
+            output_dir: dir.path().to_path_buf(),
+            services: vec![
+                GenService::new(
+                    "admin".to_string(),
+                    1,
+                    vec![
+                        endpoint(
+                            "AdminDeleteApp",
+                            20000,
+                            crate::definitions::McpConfig {
+                                name: Some("delete_app".into()),
+                                title: Some("Delete app".into()),
+                                destructive: Some(true),
+                                usage: Some("Ask the user to confirm first.".into()),
+                                ..Default::default()
+                            },
+                        ),
+                        endpoint(
+                            "AdminRotateKeys",
+                            20001,
+                            crate::definitions::McpConfig {
+                                expose: false,
+                                ..Default::default()
+                            },
+                        ),
+                    ],
+                ),
+                GenService::new(
+                    "internal".to_string(),
+                    2,
+                    vec![endpoint(
+                        "InternalFlush",
+                        30000,
+                        crate::definitions::McpConfig {
+                            expose: false,
+                            ..Default::default()
+                        },
+                    )],
+                ),
+            ],
+            enums: vec![],
+            structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        };
+
+        write_mcp_tools_json(&data, dir.path()).unwrap();
+
+        let out = std::fs::read_to_string(dir.path().join("admin_mcp_tools.json")).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&out).unwrap();
+        let tools = parsed["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 1, "AdminRotateKeys is not exposed");
+        let tool = &tools[0];
+        assert_eq!(tool["name"], json!("delete_app"));
+        assert_eq!(tool["title"], json!("Delete app"));
+        assert_eq!(
+            tool["description"],
+            json!("Deletes an app.\n\nAsk the user to confirm first.")
+        );
+        assert_eq!(
+            tool["annotations"],
+            json!({ "title": "Delete app", "destructiveHint": true })
+        );
+        assert!(!dir.path().join("internal_mcp_tools.json").exists());
+    }
+
+    #[test]
+    fn each_role_sees_only_the_tools_it_is_offered() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = |name: &str, code: u32, roles: &[&str]| EndpointSchemaElement {
+            schema: EndpointSchema::new(name, code, vec![], vec![])
+                .with_description("Test.")
+                .with_roles(roles.iter().map(|role| role.to_string()).collect()),
+            ..Default::default()
+        };
+        let mut hidden = endpoint("UserResetAll", 10003, &["UserRole::Support"]);
+        hidden.mcp.expose = false;
+        let data = Data {
+            project_name: "test".into(),
+            spec: Default::default(),
+            project_root: dir.path().to_path_buf(),
+            output_dir: dir.path().to_path_buf(),
+            services: vec![GenService::new(
+                "user".to_string(),
+                1,
+                vec![
+                    endpoint("UserGetProfile", 10000, &["UserRole::Support", "UserRole::Admin"]),
+                    endpoint("UserBan", 10001, &["UserRole::Admin"]),
+                    endpoint("UserPing", 10002, &[]),
+                    hidden,
+                ],
+            )],
+            enums: vec![EnumElement {
+                config: RustGenConfig::default(),
+                inner: Type::enum_(
+                    "UserRole",
+                    vec![
+                        endpoint_libs::model::EnumVariant::new("Admin", 1),
+                        endpoint_libs::model::EnumVariant::new("Support", 2),
+                        endpoint_libs::model::EnumVariant::new("Guest", 3),
+                    ],
+                ),
+            }],
+            structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        };
+
+        // A list from an earlier run, and a file of the user's own.
+        let roles_dir = dir.path().join("docs").join(MCP_ROLES_DIR);
+        std::fs::create_dir_all(roles_dir.join("guest")).unwrap();
+        std::fs::write(roles_dir.join("guest").join("user_mcp_tools.json"), "{}").unwrap();
+        std::fs::write(roles_dir.join("NOTES.md"), "kept").unwrap();
+
+        gen_mcp_tools_by_role(&data).unwrap();
+
+        let names = |role: &str| -> Vec<String> {
+            let out = std::fs::read_to_string(roles_dir.join(role).join("user_mcp_tools.json")).unwrap();
+            let parsed: serde_json::Value = serde_json::from_str(&out).unwrap();
+            parsed["tools"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|tool| tool["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(names("admin"), ["user_get_profile", "user_ban"]);
+        assert_eq!(names("support"), ["user_get_profile"]);
+        assert!(!roles_dir.join("guest").exists(), "offered nothing");
+        assert!(roles_dir.join("NOTES.md").exists(), "not ours to remove");
+    }
 }
@@ -0,0 +1,276 @@
+//! HTTP-to-WebSocket gateway generation.
+//!
+//! `generated/gateway.rs` is an axum router that serves exactly the paths of
+//! the OpenAPI projection, `POST /{serviceName}/{endpoint_snake_name}`. Each
+//! request opens a WebSocket to the backend with the caller's bearer token as
+//! its subprotocol, sends the body as the `params` of one
+//! `{method, seq, params}` frame and answers with the reply. Error replies get
+//! an HTTP status from a table: [`GatewayConfig::statuses`] by error code name,
+//! then the HTTP status built-in codes are named after, then
+//! [`GatewayConfig::default_status`].
+//!
+//! `docs/openapi.gateway.json` describes that deployment: real servers, a
+//! bearer scheme, and one response per status an endpoint's declared errors
+//! map to. See [`crate::openapi::build_gateway_openapi`].
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use eyre::{Context, Result, bail};
+use serde::Deserialize;
+use smart_default::SmartDefault;
+use smart_serde_default::smart_serde_default;
+
+use crate::definitions::ErrorCodeSchema;
+use crate::docs::Data;
+use crate::openapi::operation_path;
+use crate::spec_common::visible_services;
+
+/// The project-level gateway config, next to `version.toml`.
+pub const GATEWAY_FILE: &str = "gateway.ron";
+
+/// The contents of [`GATEWAY_FILE`].
+#[smart_serde_default]
+#[derive(Debug, Clone, PartialEq, Deserialize, SmartDefault)]
+pub struct GatewayConfig {
+    /// Where the gateway is deployed, for the `servers` of the gateway document.
+    #[serde(default)]
+    pub servers: Vec<GatewayServer>,
+    /// HTTP status by error code name.
+    #[serde(default)]
+    pub statuses: BTreeMap<String, u16>,
+    /// The status of an error code with none of its own.
+    #[smart_default(500)]
+    pub default_status: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GatewayServer {
+    pub url: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl GatewayConfig {
+    /// The HTTP status an error reply with `code` gets.
+    pub fn status(&self, code: &ErrorCodeSchema) -> u16 {
+        if let Some(status) = self.statuses.get(&code.name) {
+            return *status;
+        }
+        // The built-in codes are 100000 plus the HTTP status they are named after.
+        match code.code - 100_000 {
+            status @ 400..=599 => status as u16,
+            _ => self.default_status,
+        }
+    }
+
+    /// Rejects statuses that are not errors and names missing from the catalog.
+    pub fn validate(&self, error_codes: &[ErrorCodeSchema]) -> Result<()> {
+        let mut problems = vec![];
+        for (name, status) in self
+            .statuses
+            .iter()
+            .chain([(&"default_status".to_string(), &self.default_status)])
+        {
+            if !(400..=599).contains(status) {
+                problems.push(format!("{name}: {status} is not an HTTP error status"));
+            }
+        }
+        for name in self.statuses.keys() {
+            if !error_codes.iter().any(|code| &code.name == name) {
+                problems.push(format!("{name}: no error code has this name"));
+            }
+        }
+        if !problems.is_empty() {
+            bail!("Invalid {GATEWAY_FILE}:\n  {}", problems.join("\n  "));
+        }
+        Ok(())
+    }
+}
+
+pub fn read_gateway_file(path: &Path) -> Result<GatewayConfig> {
+    if !path.exists() {
+        return Ok(GatewayConfig::default());
+    }
+    let content = std::fs::read_to_string(path)?;
+    ron::from_str(&content).wrap_err_with(|| format!("Error parsing {}", path.display()))
+}
+
+/// The source of `generated/gateway.rs`.
+pub fn render_gateway(data: &Data, public_only: bool, config: &GatewayConfig) -> String {
+    let mut routes = String::new();
+    for service in visible_services(data, public_only) {
+        for endpoint in &service.endpoints {
+            let schema = &endpoint.schema;
+            writeln!(
+                routes,
+                "    ({:?}, {}),",
+                operation_path(&service.name, &schema.name),
+                schema.code
+            )
+            .unwrap();
+        }
+    }
+
+    let mut statuses = String::new();
+    let mut messages = String::new();
+    for code in &data.error_codes {
+        let status = config.status(code);
+        if status != config.default_status {
+            writeln!(statuses, "        {} => {status},", code.code).unwrap();
+        }
+        writeln!(messages, "        {} => {:?},", code.code, code.description).unwrap();
+    }
+
+    format!(
+        r#"// Generated by endpoint-gen from the RON definitions. Do not edit.
+//
+// HTTP-to-WebSocket gateway serving `POST /{{serviceName}}/{{endpoint_snake_name}}`.
+// Needs `axum` 0.8, `serde_json` and endpoint-libs' `ws-client` feature.
+
+use axum::Json;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::AUTHORIZATION;
+use axum::http::{{HeaderMap, StatusCode}};
+use axum::response::{{IntoResponse, Response}};
+use axum::routing::post;
+use endpoint_libs::libs::ws::WsClientBuilder;
+use serde_json::{{Value, json}};
+
+/// Every served path and the endpoint code it forwards to.
+pub const ROUTES: &[(&str, u32)] = &[
+{routes}];
+
+/// Where the gateway forwards requests.
+#[derive(Clone, Debug)]
+pub struct Gateway {{
+    /// The backend's `ws://` or `wss://` URL.
+    pub backend_url: String,
+}}
+
+/// A router serving [`ROUTES`].
+pub fn router(gateway: Gateway) -> Router {{
+    let mut router = Router::<Gateway>::new();
+    for &(path, method) in ROUTES {{
+        router = router.route(
+            path,
+            post(move |State(gateway): State<Gateway>, headers: HeaderMap, body: Bytes| {{
+                forward(gateway, method, headers, body)
+            }}),
+        );
+    }}
+    router.with_state(gateway)
+}}
+
+/// The HTTP status of an error reply.
+pub fn error_status(code: u32) -> u16 {{
+    match code {{
+{statuses}        _ => {default_status},
+    }}
+}}
+
+/// The catalog description of an error code.
+pub fn error_message(code: u32) -> &'static str {{
+    match code {{
+{messages}        _ => "",
+    }}
+}}
+
+async fn forward(gateway: Gateway, method: u32, headers: HeaderMap, body: Bytes) -> Response {{
+    let params: Value = if body.is_empty() {{
+        json!({{}})
+    }} else {{
+        match serde_json::from_slice(&body) {{
+            Ok(params) => params,
+            Err(err) => return envelope(StatusCode::BAD_REQUEST, 100400, &err.to_string(), Value::Null),
+        }}
+    }};
+    // The backend authenticates the caller, not the gateway.
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let frame = match call(&gateway.backend_url, token, method, params).await {{
+        Ok(frame) => frame,
+        Err(err) => return envelope(StatusCode::BAD_GATEWAY, 100502, &err, Value::Null),
+    }};
+    if frame["type"] == "Error" {{
+        let code = frame["code"].as_u64().unwrap_or_default() as u32;
+        let status = StatusCode::from_u16(error_status(code)).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        return envelope(status, code, error_message(code), frame["params"].clone());
+    }}
+    Json(frame["params"].clone()).into_response()
+}}
+
+/// Sends one frame on a fresh connection and waits for its reply. Stream
+/// pushes and logs are skipped: HTTP gets the initial response only.
+async fn call(backend_url: &str, token: &str, method: u32, params: Value) -> Result<Value, String> {{
+    let (mut client, _) = WsClientBuilder::new()
+        .protocol_header(token)
+        .build(backend_url)
+        .await
+        .map_err(|err| err.to_string())?;
+    client.send_req(method, params).await.map_err(|err| err.to_string())?;
+    loop {{
+        let frame = client.recv_raw().await.map_err(|err| err.to_string())?;
+        if frame["type"] == "Immediate" || frame["type"] == "Error" {{
+            let _ = client.close().await;
+            return Ok(frame);
+        }}
+    }}
+}}
+
+fn envelope(status: StatusCode, code: u32, message: &str, params: Value) -> Response {{
+    (status, Json(json!({{ "code": code, "message": message, "params": params }}))).into_response()
+}}
+"#,
+        default_status = config.default_status,
+    )
+}
+
+/// Writes `generated/gateway.rs`.
+pub fn gen_gateway(data: &Data, public_only: bool, config: &GatewayConfig) -> Result<()> {
+    std::fs::create_dir_all(&data.output_dir)?;
+    let filename = data.output_dir.join("gateway.rs");
+    std::fs::write(&filename, render_gateway(data, public_only, config))
+        .with_context(|| format!("Failed to write gateway: {}", filename.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> Vec<ErrorCodeSchema> {
+        vec![
+            ErrorCodeSchema::new("NotFound", 100404, "Resource was not found"),
+            ErrorCodeSchema::new("TooManyLoginAttempts", 200001, "Too many attempts."),
+            ErrorCodeSchema::new("InsufficientFunds", 200002, "Not enough funds."),
+        ]
+    }
+
+    #[test]
+    fn statuses_come_from_the_table_then_builtin_codes_then_the_default() {
+        let config = GatewayConfig {
+            statuses: BTreeMap::from([("TooManyLoginAttempts".to_string(), 429)]),
+            ..Default::default()
+        };
+        let codes = catalog();
+        assert_eq!(config.status(&codes[0]), 404);
+        assert_eq!(config.status(&codes[1]), 429);
+        assert_eq!(config.status(&codes[2]), 500);
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        let config: GatewayConfig = ron::from_str(r#"(statuses: {"Typo": 429, "NotFound": 200})"#).unwrap();
+        let err = config.validate(&catalog()).unwrap_err().to_string();
+        assert!(err.contains("NotFound: 200 is not an HTTP error status"), "{err}");
+        assert!(err.contains("Typo: no error code has this name"), "{err}");
+        assert_eq!(config.default_status, 500);
+    }
+}
@@ -0,0 +1,457 @@
+//! Generic struct definitions.
+//!
+//! A [`StructElement`] may declare `type_params`. Its fields use a parameter as
+//! `StructRef("T")`, and anything else instantiates it as
+//! `StructRef("Paged<Position>")` — which is already the Rust spelling, so
+//! `model.rs` keeps real generics for free.
+//!
+//! Nothing built on a [`TypeRegistry`] understands type parameters: MCP
+//! schemas, the specification documents and the runtime `type_registry()` all
+//! resolve a `StructRef` by exact name. For those, every instantiation found in
+//! the model is monomorphised into a concrete `Type::Struct` registered under
+//! the name as written, so `StructRef("Paged<Position>")` resolves unchanged.
+//!
+//! Type arguments are Rust type names: a struct, another instantiation, or an
+//! enum by its generated name (`EnumSide` when `prefix_enum` is set).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use endpoint_libs::model::{Field, Type, TypeRegistry};
+use itertools::Itertools;
+
+use crate::definitions::{EnumElement, StructElement};
+use crate::docs::Data;
+use crate::rust::ToRust;
+
+/// Splits `Paged<Position, Wrapper<Side>>` into `("Paged", ["Position", "Wrapper<Side>"])`.
+///
+/// Returns `None` for anything that is not a well-formed instantiation,
+/// including plain names. Callers treat a malformed one as an ordinary name,
+/// which then fails reference resolution with the text as written.
+pub fn parse_generic_ref(name: &str) -> Option<(String, Vec<String>)> {
+    let open = name.find('<')?;
+    let inner = name.trim_end().strip_suffix('>')?.get(open + 1..)?;
+    let base = name[..open].trim();
+
+    let mut args = vec![];
+    let mut depth = 0i32;
+    let mut current = String::new();
+    for c in inner.chars() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        if depth < 0 {
+            return None;
+        }
+        current.push(c);
+    }
+    args.push(current.trim().to_string());
+
+    if base.is_empty() || depth != 0 || args.iter().any(|a| a.is_empty()) {
+        return None;
+    }
+    Some((base.to_string(), args))
+}
+
+fn format_generic_ref(base: &str, args: &[String]) -> String {
+    format!("{base}<{}>", args.join(", "))
+}
+
+/// The generic shared struct called `base`, if there is one.
+pub fn find_generic<'a>(structs: &'a [StructElement], base: &str) -> Option<&'a StructElement> {
+    structs
+        .iter()
+        .find(|s| !s.type_params.is_empty() && matches!(&s.inner, Type::Struct { name, .. } if name == base))
+}
+
+/// The shared enum whose generated Rust name is `name`.
+pub(crate) fn find_enum_by_rust_name<'a>(enums: &'a [EnumElement], name: &str) -> Option<&'a EnumElement> {
+    enums.iter().find(|e| e.to_rust_ref(false) == name)
+}
+
+/// Rewrites a type name under a parameter substitution, including inside
+/// nested instantiations: `Wrapper<T>` with `T = Position` is `Wrapper<Position>`.
+fn substitute_name(name: &str, substitution: &BTreeMap<&str, &str>) -> String {
+    if let Some(arg) = substitution.get(name) {
+        return arg.to_string();
+    }
+    match parse_generic_ref(name) {
+        Some((base, args)) => format_generic_ref(
+            &base,
+            &args
+                .iter()
+                .map(|a| substitute_name(a, substitution))
+                .collect::<Vec<_>>(),
+        ),
+        None => name.to_string(),
+    }
+}
+
+fn substitute(ty: &Type, substitution: &BTreeMap<&str, &str>, enums: &[EnumElement]) -> Type {
+    match ty {
+        Type::StructRef(name) => {
+            let name = substitute_name(name, substitution);
+            // An enum argument lands where the template wrote StructRef("T").
+            match find_enum_by_rust_name(enums, &name) {
+                Some(element) => match &element.inner {
+                    Type::Enum { name, .. } => Type::enum_ref(name.clone(), element.config.prefix_enum),
+                    _ => unreachable!("EnumElement is validated to wrap an enum"),
+                },
+                None => Type::StructRef(name),
+            }
+        }
+        Type::StructTable { struct_ref } => Type::struct_table(substitute_name(struct_ref, substitution)),
+        Type::Struct { name, fields } => Type::struct_(
+            name.clone(),
+            fields
+                .iter()
+                .map(|f| {
+                    let mut field = f.clone();
+                    field.ty = substitute(&f.ty, substitution, enums);
+                    field
+                })
+                .collect(),
+        ),
+        Type::Vec(inner) => Type::vec(substitute(inner, substitution, enums)),
+        Type::Optional(inner) => Type::optional(substitute(inner, substitution, enums)),
+        other => other.clone(),
+    }
+}
+
+fn collect_generic_refs(ty: &Type, out: &mut Vec<String>) {
+    match ty {
+        Type::StructRef(name) | Type::StructTable { struct_ref: name } => {
+            if let Some((_, args)) = parse_generic_ref(name) {
+                out.push(name.clone());
+                // Arguments may themselves be instantiations.
+                for arg in args {
+                    collect_generic_refs(&Type::StructRef(arg), out);
+                }
+            }
+        }
+        Type::Struct { fields, .. } => {
+            for field in fields {
+                collect_generic_refs(&field.ty, out);
+            }
+        }
+        Type::Vec(inner) | Type::Optional(inner) => collect_generic_refs(inner, out),
+        _ => {}
+    }
+}
+
+/// Monomorphises one instantiation, or `None` if it does not name a generic
+/// struct with the right number of parameters. Reference checking reports
+/// those; this only builds what is buildable.
+fn instantiate(name: &str, structs: &[StructElement], enums: &[EnumElement]) -> Option<Type> {
+    let (base, args) = parse_generic_ref(name)?;
+    let generic = find_generic(structs, &base)?;
+    if generic.type_params.len() != args.len() {
+        return None;
+    }
+    let substitution: BTreeMap<&str, &str> = generic
+        .type_params
+        .iter()
+        .map(String::as_str)
+        .zip(args.iter().map(String::as_str))
+        .collect();
+
+    let Type::Struct { fields, .. } = &generic.inner else {
+        unreachable!("StructElement is validated to wrap a struct");
+    };
+    let fields: Vec<Field> = fields
+        .iter()
+        .map(|f| {
+            let mut field = f.clone();
+            field.ty = substitute(&f.ty, &substitution, enums);
+            field
+        })
+        .collect();
+    Some(Type::struct_(name, fields))
+}
+
+/// Every type in `data` that may contain references to resolve: non-generic
+/// shared structs, the placeholders of unions and newtypes, and everything
+/// endpoints carry. Generic templates are left out; their references only mean
+/// something once instantiated.
+pub(crate) fn referencing_types(data: &Data) -> Vec<Type> {
+    let mut types: Vec<Type> = data
+        .structs
+        .iter()
+        .filter(|s| s.type_params.is_empty())
+        .map(|s| s.inner.clone())
+        .collect();
+    types.extend(data.unions.iter().map(|u| u.placeholder()));
+    types.extend(data.newtypes.iter().map(|n| n.placeholder()));
+    for service in &data.services {
+        for endpoint in &service.endpoints {
+            let schema = &endpoint.schema;
+            types.extend(schema.parameters.iter().chain(&schema.returns).map(|f| f.ty.clone()));
+            types.extend(schema.stream_response.clone());
+            for error in &schema.errors {
+                types.extend(error.fields.iter().map(|f| f.ty.clone()));
+            }
+        }
+    }
+    types
+}
+
+/// Every instantiation referenced anywhere in `data` — endpoints, non-generic
+/// shared structs, unions and newtypes, and transitively the instantiations
+/// those need — as concrete structs named exactly as written.
+pub fn instantiations(data: &Data) -> Vec<Type> {
+    let mut pending = vec![];
+    for ty in referencing_types(data) {
+        collect_generic_refs(&ty, &mut pending);
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut instances = vec![];
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        if let Some(instance) = instantiate(&name, &data.structs, &data.enums) {
+            collect_generic_refs(&instance, &mut pending);
+            instances.push(instance);
+        }
+    }
+    instances.sort();
+    instances
+}
+
+/// Checks that `name` — a type argument, a whole instantiation or a map (see
+/// [`crate::maps`]) — resolves, recording every shared struct and enum it uses.
+///
+/// `scope` holds the type parameters in force, i.e. those of the generic
+/// struct the reference was written inside.
+pub fn check_type_name(
+    name: &str,
+    data: &Data,
+    registry: &TypeRegistry,
+    scope: &[String],
+    used_structs: &mut BTreeSet<String>,
+    used_enums: &mut BTreeSet<String>,
+) -> Result<(), String> {
+    if scope.iter().any(|p| p == name) {
+        return Ok(());
+    }
+
+    if let Some((key, value)) = crate::maps::parse_map_ref(name) {
+        if key != "String" {
+            match find_enum_by_rust_name(&data.enums, &key).map(|e| &e.inner) {
+                Some(Type::Enum { name: raw, .. }) => {
+                    used_enums.insert(raw.clone());
+                }
+                _ => {
+                    return Err(format!(
+                        "map key '{key}' in '{name}' must be String or a shared enum's generated name"
+                    ));
+                }
+            }
+        }
+        let mut names = vec![];
+        crate::maps::value_names(&value, &mut names);
+        for value_name in &names {
+            check_type_name(value_name, data, registry, scope, used_structs, used_enums)?;
+        }
+        return Ok(());
+    }
+
+    if let Some((base, args)) = parse_generic_ref(name) {
+        let Some(generic) = find_generic(&data.structs, &base) else {
+            return Err(format!("'{base}' in '{name}' is not a generic struct"));
+        };
+        if generic.type_params.len() != args.len() {
+            return Err(format!(
+                "'{name}' passes {} type argument(s), but '{base}' is declared as {base}{}",
+                args.len(),
+                generic.generics()
+            ));
+        }
+        used_structs.insert(base);
+        for arg in &args {
+            check_type_name(arg, data, registry, scope, used_structs, used_enums)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(generic) = find_generic(&data.structs, name) {
+        return Err(format!(
+            "struct '{name}' is generic and must be instantiated, e.g. \"{name}{}\"",
+            generic.generics()
+        ));
+    }
+    if registry.get_struct(name).is_some() {
+        used_structs.insert(name.to_string());
+        return Ok(());
+    }
+    if let Some(element) = find_enum_by_rust_name(&data.enums, name)
+        && let Type::Enum { name: raw, .. } = &element.inner
+    {
+        used_enums.insert(raw.clone());
+        return Ok(());
+    }
+
+    let known_enum = data
+        .enums
+        .iter()
+        .find(|e| matches!(&e.inner, Type::Enum { name: raw, .. } if raw == name));
+    Err(match known_enum {
+        Some(element) => format!(
+            "type argument '{name}' must use the enum's generated name `{}`",
+            element.to_rust_ref(false)
+        ),
+        None => format!(
+            "'{name}' is not defined (type arguments must be structs, enums or instantiations{})",
+            if scope.is_empty() {
+                String::new()
+            } else {
+                format!(", or one of the parameters {}", scope.iter().join(", "))
+            }
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{EndpointSchemaElement, GenService, RustGenConfig};
+    use endpoint_libs::model::{EndpointSchema, EnumVariant};
+    use std::path::PathBuf;
+
+    fn paged() -> StructElement {
+        StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec!["T".into()],
+            inner: Type::struct_(
+                "Paged",
+                vec![
+                    Field::new("items", Type::vec(Type::struct_ref("T"))),
+                    Field::new("total", Type::Int64),
+                    Field::new("cursor", Type::optional(Type::String)),
+                ],
+            ),
+        }
+    }
+
+    fn plain(name: &str) -> StructElement {
+        StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec![],
+            inner: Type::struct_(name, vec![Field::new("id", Type::Int64)]),
+        }
+    }
+
+    fn data_returning(ty: Type) -> Data {
+        Data {
+            project_name: "test".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![GenService::new(
+                "s1".into(),
+                1,
+                vec![EndpointSchemaElement {
+                    frontend_facing: true,
+                    config: RustGenConfig::default(),
+                    schema: EndpointSchema::new("List", 1, vec![], vec![Field::new("page", ty)]),
+                    ..Default::default()
+                }],
+            )],
+            enums: vec![crate::definitions::EnumElement {
+                config: RustGenConfig {
+                    prefix_enum: true,
+                    ..Default::default()
+                },
+                inner: Type::enum_("side", vec![EnumVariant::new("Buy", 1)]),
+            }],
+            structs: vec![paged(), plain("Position")],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    #[test]
+    fn parses_nested_and_multiple_arguments() {
+        assert_eq!(
+            parse_generic_ref("Pair<A, Paged<B>>"),
+            Some(("Pair".into(), vec!["A".into(), "Paged<B>".into()]))
+        );
+        assert_eq!(parse_generic_ref("Position"), None);
+        assert_eq!(parse_generic_ref("Paged<Position"), None);
+        assert_eq!(parse_generic_ref("Paged<>"), None);
+    }
+
+    #[test]
+    fn instantiation_substitutes_struct_and_enum_arguments() {
+        let data = data_returning(Type::struct_ref("Paged<Position>"));
+        let instances = instantiations(&data);
+        assert_eq!(instances.len(), 1);
+        let Type::Struct { name, fields } = &instances[0] else {
+            panic!("expected a struct");
+        };
+        assert_eq!(name, "Paged<Position>");
+        assert_eq!(fields[0].ty, Type::vec(Type::struct_ref("Position")));
+
+        let data = data_returning(Type::struct_ref("Paged<EnumSide>"));
+        let Type::Struct { fields, .. } = &instantiations(&data)[0] else {
+            panic!("expected a struct");
+        };
+        assert_eq!(fields[0].ty, Type::vec(Type::enum_ref("side", true)));
+    }
+
+    #[test]
+    fn nested_instantiations_are_monomorphised_transitively() {
+        let data = data_returning(Type::struct_ref("Paged<Paged<Position>>"));
+        let names: Vec<_> = instantiations(&data)
+            .into_iter()
+            .map(|t| match t {
+                Type::Struct { name, .. } => name,
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(names, vec!["Paged<Paged<Position>>", "Paged<Position>"]);
+    }
+
+    #[test]
+    fn check_type_name_explains_what_is_wrong() {
+        let data = data_returning(Type::Unit);
+        let registry = crate::spec_common::build_registry(&data);
+        let mut structs = BTreeSet::new();
+        let mut enums = BTreeSet::new();
+        let mut check =
+            |name: &str, scope: &[String]| check_type_name(name, &data, &registry, scope, &mut structs, &mut enums);
+
+        assert!(check("Paged<Position>", &[]).is_ok());
+        assert!(check("Paged<T>", &["T".into()]).is_ok());
+        assert!(check("Paged<EnumSide>", &[]).is_ok());
+        assert!(check("Paged", &[]).unwrap_err().contains("must be instantiated"));
+        assert!(check("Paged<A, B>", &[]).unwrap_err().contains("declared as Paged<T>"));
+        assert!(check("Paged<side>", &[]).unwrap_err().contains("`EnumSide`"));
+        assert!(
+            check("Paged<Missing>", &[])
+                .unwrap_err()
+                .contains("'Missing' is not defined")
+        );
+        assert!(check("Position<A>", &[]).unwrap_err().contains("not a generic struct"));
+        assert!(check("BTreeMap<EnumSide, Vec<Paged<Position>>>", &[]).is_ok());
+        assert!(check("HashMap<String, Option<T>>", &["T".into()]).is_ok());
+        assert!(check("HashMap<i64, f64>", &[]).unwrap_err().contains("map key 'i64'"));
+        assert!(
+            check("HashMap<String, Missing>", &[])
+                .unwrap_err()
+                .contains("'Missing' is not defined")
+        );
+
+        assert!(structs.contains("Paged") && structs.contains("Position"));
+        assert!(enums.contains("side"));
+    }
+}
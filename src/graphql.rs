@@ -0,0 +1,597 @@
+//! GraphQL SDL emission, for gateways that proxy GraphQL onto the WebSocket API.
+//!
+//! Each endpoint becomes a field named after it in camel case, with one
+//! argument per parameter. Endpoints with a `stream_response` are
+//! `Subscription` fields typed by the stream item; the rest go to `Query` or
+//! `Mutation` as [`GraphqlRoot`] says. A field returns `{Endpoint}Response`, or
+//! `Boolean!` when the endpoint returns nothing, as GraphQL object types need
+//! at least one field.
+//!
+//! Structs become object types, and `{Name}Input` input types where they are
+//! used in parameters. Enums become GraphQL enums, tagged unions become unions
+//! over one object type per variant, and newtypes are replaced by their
+//! underlying type. Maps and `Object` are the `JSON` scalar. Only types an
+//! endpoint reaches are emitted.
+//!
+//! The wire format is not GraphQL's: enums are numbers and unions carry a tag
+//! property. The resolver map written next to the schema is what a gateway
+//! needs to translate, along with each field's endpoint code.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use convert_case::{Case, Casing};
+use endpoint_libs::model::{EnumVariant, Field, Type};
+use eyre::{Context, Result, bail};
+use serde::Serialize;
+
+use crate::definitions::{GraphqlRoot, TaggedUnionElement};
+use crate::docs::Data;
+use crate::maps::parse_map_ref;
+use crate::rust::ToRust;
+use crate::rust_item::{enum_variant_name, struct_name};
+use crate::spec_common::{monomorphised_name, visible_services};
+
+/// Words of an endpoint name that make an inferred field a `Query`.
+const QUERY_WORDS: &[&str] = &["get", "list", "search", "find", "fetch", "query", "count"];
+
+/// Everything a gateway needs beyond the SDL: the endpoint code behind each
+/// root field, the wire value of each enum value, and how to tell union
+/// members apart. The format of `docs/graphql/resolvers.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ResolverMap {
+    #[serde(rename = "Query", skip_serializing_if = "BTreeMap::is_empty")]
+    pub query: BTreeMap<String, u32>,
+    #[serde(rename = "Mutation", skip_serializing_if = "BTreeMap::is_empty")]
+    pub mutation: BTreeMap<String, u32>,
+    #[serde(rename = "Subscription", skip_serializing_if = "BTreeMap::is_empty")]
+    pub subscription: BTreeMap<String, u32>,
+    /// GraphQL enum → value name → number on the wire.
+    pub enums: BTreeMap<String, BTreeMap<String, i64>>,
+    pub unions: BTreeMap<String, UnionResolver>,
+}
+
+/// How to resolve a union member: the wire tag property, and the object type
+/// for each of its values.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UnionResolver {
+    pub tag: String,
+    pub types: BTreeMap<String, String>,
+}
+
+/// The root type of an endpoint's field.
+pub fn root_type(graphql: GraphqlRoot, name: &str, streaming: bool) -> &'static str {
+    if streaming {
+        return "Subscription";
+    }
+    match graphql {
+        GraphqlRoot::Query => "Query",
+        GraphqlRoot::Mutation => "Mutation",
+        GraphqlRoot::Inferred => {
+            let snake = name.to_case(Case::Snake);
+            if snake.split('_').any(|word| QUERY_WORDS.contains(&word)) {
+                "Query"
+            } else {
+                "Mutation"
+            }
+        }
+    }
+}
+
+struct Builder<'a> {
+    data: &'a Data,
+    instances: Vec<Type>,
+    /// Rendered object types and unions by name; `None` while one is being
+    /// built, so recursive types terminate.
+    outputs: BTreeMap<String, Option<String>>,
+    inputs: BTreeMap<String, Option<String>>,
+    enums: BTreeMap<String, String>,
+    scalars: BTreeSet<&'static str>,
+    resolvers: ResolverMap,
+    errors: Vec<String>,
+}
+
+impl Builder<'_> {
+    /// A type with its nullability: `Order!`, `[Fill!]!`, `String`.
+    fn type_ref(&mut self, ty: &Type, input: bool, site: &str) -> Option<String> {
+        match self.resolve_newtype(ty) {
+            Type::Optional(inner) => {
+                let inner = self.type_ref(&inner, input, site)?;
+                Some(inner.strip_suffix('!').unwrap_or(&inner).to_string())
+            }
+            Type::Vec(inner) => Some(format!("[{}]!", self.type_ref(&inner, input, site)?)),
+            Type::StructTable { struct_ref } => Some(format!(
+                "[{}!]!",
+                self.named_type(&Type::StructRef(struct_ref), input, site)?
+            )),
+            other => Some(format!("{}!", self.named_type(&other, input, site)?)),
+        }
+    }
+
+    /// The type a newtype reference stands for, or `ty` itself.
+    fn resolve_newtype(&self, ty: &Type) -> Type {
+        match ty {
+            Type::StructRef(name) => match self.data.newtypes.iter().find(|n| &n.name == name) {
+                Some(newtype) => self.resolve_newtype(&newtype.ty),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    fn named_type(&mut self, ty: &Type, input: bool, site: &str) -> Option<String> {
+        let scalar = match ty {
+            Type::Int32 => return Some("Int".into()),
+            Type::Float64 => return Some("Float".into()),
+            Type::Boolean => return Some("Boolean".into()),
+            Type::String
+            | Type::NanoId { .. }
+            | Type::IpAddr
+            | Type::BlockchainAddress
+            | Type::BlockchainTransactionHash => return Some("String".into()),
+            // GraphQL's Int is 32-bit signed, which a u32 overflows.
+            Type::Int64 | Type::UInt32 => "Int64",
+            Type::TimeStampMs => "TimestampMs",
+            Type::UUID => "UUID",
+            Type::BlockchainDecimal => "Decimal",
+            Type::Bytea => "Bytes",
+            Type::Object => "JSON",
+            Type::StructRef(name) if parse_map_ref(name).is_some() => "JSON",
+            Type::Struct { name, fields } => {
+                return self.object(struct_name(name), fields, input, &format!("struct '{name}'"));
+            }
+            Type::Enum { name, variants } => return Some(self.enum_(name, variants)),
+            Type::EnumRef { name, .. } => {
+                let Some(Type::Enum { variants, .. }) = self
+                    .data
+                    .enums
+                    .iter()
+                    .map(|e| &e.inner)
+                    .find(|e| matches!(e, Type::Enum { name: n, .. } if n == name))
+                else {
+                    self.errors.push(format!("{site}: unknown enum `{name}`"));
+                    return None;
+                };
+                let variants = variants.clone();
+                return Some(self.enum_(name, &variants));
+            }
+            Type::StructRef(name) => return self.struct_ref(name, input, site),
+            other => {
+                self.errors
+                    .push(format!("{site}: `{}` has no GraphQL mapping", other.to_rust_ref(false)));
+                return None;
+            }
+        };
+        self.scalars.insert(scalar);
+        Some(scalar.into())
+    }
+
+    fn struct_ref(&mut self, name: &str, input: bool, site: &str) -> Option<String> {
+        if let Some(union) = self.data.unions.iter().find(|u| u.name == name) {
+            if input {
+                self.errors.push(format!(
+                    "{site}: tagged union `{name}` has no GraphQL input mapping, as input unions do not exist"
+                ));
+                return None;
+            }
+            return Some(self.union(union));
+        }
+        let shared = self
+            .data
+            .structs
+            .iter()
+            .filter(|s| s.type_params.is_empty())
+            .map(|s| &s.inner)
+            .find(|s| matches!(s, Type::Struct { name: n, .. } if n == name))
+            .map(|s| (struct_name(name), s.clone()));
+        // Generic instances are named as in the specification documents.
+        let instance = || {
+            self.instances
+                .iter()
+                .find(|s| matches!(s, Type::Struct { name: n, .. } if n == name))
+                .map(|s| (monomorphised_name(name), s.clone()))
+        };
+        match shared.or_else(instance) {
+            Some((type_name, Type::Struct { fields, .. })) => {
+                self.object(type_name, &fields, input, &format!("struct '{name}'"))
+            }
+            _ => {
+                self.errors.push(format!("{site}: unknown struct `{name}`"));
+                None
+            }
+        }
+    }
+
+    /// An object type, or for `input` its `{name}Input` input type.
+    fn object(&mut self, name: String, fields: &[Field], input: bool, site: &str) -> Option<String> {
+        let (name, keyword) = if input {
+            (format!("{name}Input"), "input")
+        } else {
+            (name, "type")
+        };
+        let types = if input { &self.inputs } else { &self.outputs };
+        if types.contains_key(&name) {
+            return Some(name);
+        }
+        if fields.is_empty() {
+            self.errors.push(format!(
+                "{site}: a struct without fields has no GraphQL mapping, as object types need at least one"
+            ));
+            return None;
+        }
+        self.types(input).insert(name.clone(), None);
+        let body = self.fields(fields, input, site);
+        self.types(input)
+            .insert(name.clone(), Some(format!("{keyword} {name} {{\n{body}}}\n")));
+        Some(name)
+    }
+
+    fn types(&mut self, input: bool) -> &mut BTreeMap<String, Option<String>> {
+        if input { &mut self.inputs } else { &mut self.outputs }
+    }
+
+    fn fields(&mut self, fields: &[Field], input: bool, site: &str) -> String {
+        let mut body = String::new();
+        for field in fields {
+            let field_site = format!("{site} field '{}'", field.name);
+            if let Some(ty) = self.type_ref(&field.ty, input, &field_site) {
+                body.push_str(&description(&field.description, "  "));
+                writeln!(body, "  {}: {ty}", field.name.to_case(Case::Camel)).unwrap();
+            }
+        }
+        body
+    }
+
+    fn union(&mut self, union: &TaggedUnionElement) -> String {
+        let name = struct_name(&union.name);
+        if self.outputs.contains_key(&name) {
+            return name;
+        }
+        self.outputs.insert(name.clone(), None);
+        let mut resolver = UnionResolver {
+            tag: union.tag.clone(),
+            types: BTreeMap::new(),
+        };
+        let mut members = vec![];
+        for variant in &union.variants {
+            let member = format!("{name}{}", struct_name(&variant.name));
+            let site = format!("union '{}' variant '{}'", union.name, variant.name);
+            // The tag keeps variants without fields valid, and is what the
+            // gateway resolves `__typename` from.
+            let mut body = format!("  {}: String!\n", union.tag);
+            body.push_str(&self.fields(&variant.fields, false, &site));
+            let mut rendered = description(&variant.description, "");
+            write!(rendered, "type {member} {{\n{body}}}\n").unwrap();
+            self.outputs.insert(member.clone(), Some(rendered));
+            resolver.types.insert(variant.name.clone(), member.clone());
+            members.push(member);
+        }
+        let mut rendered = description(&union.description, "");
+        writeln!(rendered, "union {name} = {}", members.join(" | ")).unwrap();
+        self.outputs.insert(name.clone(), Some(rendered));
+        self.resolvers.unions.insert(name.clone(), resolver);
+        name
+    }
+
+    fn enum_(&mut self, name: &str, variants: &[EnumVariant]) -> String {
+        let name = struct_name(name);
+        if self.enums.contains_key(&name) {
+            return name;
+        }
+        let mut body = String::new();
+        let mut values = BTreeMap::new();
+        for variant in variants {
+            let value = enum_variant_name(&variant.name).to_case(Case::UpperSnake);
+            body.push_str(&description(&variant.description, "  "));
+            writeln!(body, "  {value}").unwrap();
+            values.insert(value, variant.value);
+        }
+        self.enums.insert(name.clone(), format!("enum {name} {{\n{body}}}\n"));
+        self.resolvers.enums.insert(name.clone(), values);
+        name
+    }
+}
+
+/// A description in SDL, on its own line before what it describes.
+fn description(text: &str, indent: &str) -> String {
+    let text = text.trim();
+    if text.is_empty() {
+        return String::new();
+    }
+    if !text.contains(['\n', '"', '\\']) {
+        return format!("{indent}\"{text}\"\n");
+    }
+    let mut block = format!("{indent}\"\"\"\n");
+    for line in text.replace("\"\"\"", "\\\"\"\"").lines() {
+        writeln!(block, "{indent}{line}").unwrap();
+    }
+    writeln!(block, "{indent}\"\"\"").unwrap();
+    block
+}
+
+fn scalar_description(scalar: &str) -> &'static str {
+    match scalar {
+        "Int64" => "A 64-bit integer, sent as a JSON number.",
+        "TimestampMs" => "Unix timestamp in milliseconds, sent as a JSON number.",
+        "UUID" => "A UUID string.",
+        "Decimal" => "A decimal number, sent as a string.",
+        "Bytes" => "Base64-encoded bytes.",
+        _ => "Any JSON value: an object, or a map keyed by strings.",
+    }
+}
+
+/// The SDL for `data` and its resolver map.
+pub fn build_graphql(data: &Data, public_only: bool) -> Result<(String, ResolverMap)> {
+    let mut builder = Builder {
+        data,
+        instances: crate::generics::instantiations(data),
+        outputs: BTreeMap::new(),
+        inputs: BTreeMap::new(),
+        enums: BTreeMap::new(),
+        scalars: BTreeSet::new(),
+        resolvers: ResolverMap::default(),
+        errors: vec![],
+    };
+
+    let mut roots: BTreeMap<&str, String> = BTreeMap::new();
+    for service in visible_services(data, public_only) {
+        for endpoint in &service.endpoints {
+            let schema = &endpoint.schema;
+            let site = format!("service '{}' endpoint '{}'", service.name, schema.name);
+            let root = root_type(endpoint.graphql, &schema.name, schema.stream_response.is_some());
+            if root == "Subscription" && endpoint.graphql != GraphqlRoot::Inferred {
+                builder.errors.push(format!(
+                    "{site}: `graphql: {:?}` cannot apply, as stream_response endpoints are subscriptions",
+                    endpoint.graphql
+                ));
+                continue;
+            }
+
+            let field_name = schema.name.to_case(Case::Camel);
+            let codes = match root {
+                "Query" => &mut builder.resolvers.query,
+                "Mutation" => &mut builder.resolvers.mutation,
+                _ => &mut builder.resolvers.subscription,
+            };
+            if codes.insert(field_name.clone(), schema.code).is_some() {
+                builder.errors.push(format!(
+                    "{site}: another endpoint is already the {root} field '{field_name}'"
+                ));
+                continue;
+            }
+
+            let mut args = vec![];
+            for param in &schema.parameters {
+                let param_site = format!("{site} parameter '{}'", param.name);
+                if let Some(ty) = builder.type_ref(&param.ty, true, &param_site) {
+                    let mut arg = description(&param.description, "    ");
+                    write!(arg, "    {}: {ty}", param.name.to_case(Case::Camel)).unwrap();
+                    args.push(arg);
+                }
+            }
+            let returns = match &schema.stream_response {
+                Some(stream) => builder.type_ref(stream, false, &format!("{site} stream_response")),
+                None if schema.returns.is_empty() => Some("Boolean!".into()),
+                None => builder
+                    .object(
+                        format!("{}Response", struct_name(&schema.name)),
+                        &schema.returns,
+                        false,
+                        &site,
+                    )
+                    .map(|name| format!("{name}!")),
+            };
+            let Some(returns) = returns else {
+                continue;
+            };
+
+            let fields = roots.entry(root).or_default();
+            fields.push_str(&description(&schema.description, "  "));
+            if args.is_empty() {
+                writeln!(fields, "  {field_name}: {returns}").unwrap();
+            } else {
+                writeln!(fields, "  {field_name}(\n{}\n  ): {returns}", args.join("\n")).unwrap();
+            }
+        }
+    }
+
+    if !builder.errors.is_empty() {
+        bail!(
+            "GraphQL generation failed for {} field(s):\n  {}",
+            builder.errors.len(),
+            builder.errors.join("\n  ")
+        );
+    }
+    if !roots.contains_key("Query") {
+        bail!(
+            "GraphQL generation failed: a schema needs at least one Query field, and no endpoint is one. \
+             Set `graphql: Query` on an endpoint that only reads."
+        );
+    }
+
+    let mut out = String::from("# Generated by endpoint-gen from the RON definitions. Do not edit.\n\n");
+    for scalar in &builder.scalars {
+        out.push_str(&description(scalar_description(scalar), ""));
+        writeln!(out, "scalar {scalar}\n").unwrap();
+    }
+    for root in ["Query", "Mutation", "Subscription"] {
+        if let Some(fields) = roots.get(root) {
+            writeln!(out, "type {root} {{\n{fields}}}\n").unwrap();
+        }
+    }
+    for rendered in builder
+        .outputs
+        .into_values()
+        .chain(builder.inputs.into_values())
+        .flatten()
+        .chain(builder.enums.into_values())
+    {
+        out.push_str(&rendered);
+        out.push('\n');
+    }
+    Ok((out.trim_end().to_string() + "\n", builder.resolvers))
+}
+
+/// Writes `docs/graphql/schema.graphql` and `docs/graphql/resolvers.json`.
+pub fn gen_graphql(data: &Data, public_only: bool) -> Result<()> {
+    let graphql_dir = data.project_root.join("docs").join("graphql");
+    std::fs::create_dir_all(&graphql_dir)?;
+
+    let (schema, resolvers) = build_graphql(data, public_only)?;
+    std::fs::write(graphql_dir.join("schema.graphql"), schema)?;
+    let filename = graphql_dir.join("resolvers.json");
+    let file = std::fs::File::create(&filename)
+        .with_context(|| format!("Failed to create GraphQL resolver map: {}", filename.display()))?;
+    serde_json::to_writer_pretty(file, &resolvers)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{EndpointSchemaElement, EnumElement, GenService, StructElement, TaggedVariant};
+    use endpoint_libs::model::EndpointSchema;
+    use std::path::PathBuf;
+
+    fn endpoint(schema: EndpointSchema) -> EndpointSchemaElement {
+        EndpointSchemaElement {
+            schema,
+            ..Default::default()
+        }
+    }
+
+    fn data(endpoints: Vec<EndpointSchemaElement>) -> Data {
+        Data {
+            project_name: "api".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![GenService::new("orders".into(), 1, endpoints)],
+            enums: vec![EnumElement {
+                config: Default::default(),
+                inner: Type::enum_(
+                    "Side",
+                    vec![
+                        EnumVariant::new_with_description("Buy", "Bids.", 1),
+                        EnumVariant::new("Sell", 2),
+                    ],
+                ),
+            }],
+            structs: vec![StructElement {
+                config: Default::default(),
+                type_params: vec![],
+                inner: Type::struct_(
+                    "Order",
+                    vec![
+                        Field::new("order_id", Type::Int64),
+                        Field::new("side", Type::enum_ref("Side", false)),
+                        Field::new("note", Type::optional(Type::String)),
+                        Field::new("fills", Type::vec(Type::Float64)),
+                    ],
+                ),
+            }],
+            unions: vec![TaggedUnionElement {
+                config: Default::default(),
+                name: "OrderEvent".into(),
+                tag: "type".into(),
+                description: String::new(),
+                variants: vec![
+                    TaggedVariant {
+                        name: "Filled".into(),
+                        description: String::new(),
+                        fields: vec![Field::new("price", Type::BlockchainDecimal)],
+                    },
+                    TaggedVariant {
+                        name: "Cancelled".into(),
+                        description: String::new(),
+                        fields: vec![],
+                    },
+                ],
+            }],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    fn sample() -> Data {
+        let mut subscribe = EndpointSchema::new("SubscribeOrderEvents", 3, vec![], vec![]);
+        subscribe.stream_response = Some(Type::struct_ref("OrderEvent"));
+        data(vec![
+            endpoint(
+                EndpointSchema::new(
+                    "GetOrder",
+                    1,
+                    vec![Field::new("order_id", Type::Int64)],
+                    vec![Field::new("order", Type::struct_ref("Order"))],
+                )
+                .with_description("Looks up an order."),
+            ),
+            endpoint(EndpointSchema::new(
+                "PlaceOrder",
+                2,
+                vec![Field::new("order", Type::struct_ref("Order"))],
+                vec![],
+            )),
+            endpoint(subscribe),
+        ])
+    }
+
+    #[test]
+    fn endpoints_become_root_fields_over_object_and_input_types() {
+        let (sdl, resolvers) = build_graphql(&sample(), false).unwrap();
+
+        assert!(sdl.contains(
+            "type Query {\n  \"Looks up an order.\"\n  getOrder(\n    orderId: Int64!\n  ): GetOrderResponse!\n}"
+        ));
+        assert!(sdl.contains("type Mutation {\n  placeOrder(\n    order: OrderInput!\n  ): Boolean!\n}"));
+        assert!(sdl.contains("type Subscription {\n  subscribeOrderEvents: OrderEvent!\n}"));
+        assert!(sdl.contains("type Order {\n  orderId: Int64!\n  side: Side!\n  note: String\n  fills: [Float!]!\n}"));
+        assert!(sdl.contains("input OrderInput {\n  orderId: Int64!\n"));
+        assert!(sdl.contains("enum Side {\n  \"Bids.\"\n  BUY\n  SELL\n}"));
+        assert!(sdl.contains("scalar Int64"));
+
+        assert_eq!(resolvers.query["getOrder"], 1);
+        assert_eq!(resolvers.mutation["placeOrder"], 2);
+        assert_eq!(resolvers.subscription["subscribeOrderEvents"], 3);
+        assert_eq!(resolvers.enums["Side"]["SELL"], 2);
+    }
+
+    #[test]
+    fn unions_resolve_members_from_their_tag() {
+        let (sdl, resolvers) = build_graphql(&sample(), false).unwrap();
+        assert!(sdl.contains("union OrderEvent = OrderEventFilled | OrderEventCancelled"));
+        assert!(sdl.contains("type OrderEventCancelled {\n  type: String!\n}"));
+        assert_eq!(resolvers.unions["OrderEvent"].tag, "type");
+        assert_eq!(resolvers.unions["OrderEvent"].types["Filled"], "OrderEventFilled");
+    }
+
+    #[test]
+    fn the_root_type_is_inferred_unless_configured() {
+        assert_eq!(root_type(GraphqlRoot::Inferred, "UserGetBalance", false), "Query");
+        assert_eq!(root_type(GraphqlRoot::Inferred, "UserLogin", false), "Mutation");
+        assert_eq!(root_type(GraphqlRoot::Query, "UserLogin", false), "Query");
+        assert_eq!(root_type(GraphqlRoot::Mutation, "GetOrCreateUser", false), "Mutation");
+        assert_eq!(root_type(GraphqlRoot::Inferred, "UserLogin", true), "Subscription");
+    }
+
+    #[test]
+    fn unmappable_fields_are_reported_together() {
+        let mut sample = sample();
+        sample.services[0].endpoints.push(endpoint(EndpointSchema::new(
+            "ReplaceEvent",
+            4,
+            vec![
+                Field::new("event", Type::struct_ref("OrderEvent")),
+                Field::new("nothing", Type::Unit),
+            ],
+            vec![],
+        )));
+        let err = build_graphql(&sample, false).unwrap_err().to_string();
+        assert!(err.contains("GraphQL generation failed for 2 field(s)"), "{err}");
+        assert!(err.contains("parameter 'event': tagged union `OrderEvent` has no GraphQL input mapping"));
+        assert!(
+            err.contains("parameter 'nothing': `()` has no GraphQL mapping"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Standalone JSON Schema (draft 2020-12) emission.
+//!
+//! For consumers that validate payloads and want nothing else from the
+//! specification documents. Every shared definition becomes
+//! `docs/schemas/{Type}.schema.json`, and every endpoint gets
+//! `docs/schemas/endpoints/{Endpoint}.request.schema.json` and
+//! `.response.schema.json`. The schemas are the `components.schemas` of the
+//! other documents, cut apart: each file is its own schema resource with an
+//! absolute `$id`, and `#/components/schemas/X` becomes a relative `$ref` to
+//! `X.schema.json`, which resolves the same against the `$id` as against the
+//! file on disk.
+//!
+//! `docs/schemas/bundle.json` holds the same resources embedded under `$defs`
+//! with their `$id`s intact — the 2020-12 way to bundle — for validators that
+//! would rather load one file.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use endpoint_libs::model::{COMPONENTS_SCHEMAS_PREFIX, Type, relocate_refs};
+use eyre::{Context, Result, bail};
+use serde_json::{Value, json};
+
+use crate::docs::Data;
+use crate::spec_common::{
+    build_registry, collect_components, document_schemas, document_title, resolve_stand_ins, visible_services,
+};
+
+/// The dialect every emitted file declares.
+pub const DRAFT_2020_12: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The emitted files, by path relative to `docs/schemas/`, plus the bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonSchemas {
+    pub files: BTreeMap<PathBuf, Value>,
+    pub bundle: Value,
+}
+
+/// Base of every `$id`. Derived from the project name, which is the API's host
+/// name in most repos.
+pub fn id_base(data: &Data) -> String {
+    format!("https://{}/schemas/", data.project_name)
+}
+
+/// Builds every schema. Separated from writing so tests can assert on values.
+///
+/// With `public_only`, only the definitions the public endpoints reach are
+/// emitted; otherwise every shared definition is, referenced or not.
+pub fn build_json_schemas(data: &Data, public_only: bool) -> Result<JsonSchemas> {
+    let registry = build_registry(data);
+    let services = visible_services(data, public_only);
+    let components = collect_components(&services, &registry)?;
+
+    let mut schemas = document_schemas(&components);
+    if !public_only {
+        let mut defs = BTreeMap::new();
+        for ty in crate::rust::shared_type_definitions(data) {
+            // Through a ref, so a prefixed enum lands under its component name
+            // (`EnumSide`) rather than its registry name (`Side`).
+            let ty = match ty {
+                Type::Enum { name, .. } => {
+                    let prefixed = data
+                        .enums
+                        .iter()
+                        .any(|e| e.config.prefix_enum && matches!(&e.inner, Type::Enum { name: n, .. } if *n == name));
+                    Type::enum_ref(name, prefixed)
+                }
+                ty => ty,
+            };
+            ty.to_json_schema(&registry, &mut defs)
+                .wrap_err("collecting shared definitions")?;
+        }
+        for (name, mut schema) in defs {
+            relocate_refs(&mut schema, COMPONENTS_SCHEMAS_PREFIX);
+            schemas.entry(name).or_insert(schema);
+        }
+    }
+
+    let mut endpoints = serde_json::Map::new();
+    for service in &services {
+        for element in &service.endpoints {
+            let schema = &element.schema;
+            if endpoints.contains_key(&schema.name) {
+                bail!(
+                    "endpoint '{}' is declared in more than one service; its schema files would collide",
+                    schema.name
+                );
+            }
+            endpoints.insert(
+                schema.name.clone(),
+                json!({
+                    "request": components.request_schema(schema, &registry)?,
+                    "response": components.response_schema(schema, &registry)?,
+                }),
+            );
+        }
+    }
+
+    // Resolved as one document so stand-ins and their refs are patched together.
+    let mut document = json!({ "components": { "schemas": schemas }, "endpoints": endpoints });
+    resolve_stand_ins(&mut document, data);
+
+    let base = id_base(data);
+    let mut files = BTreeMap::new();
+    let mut defs = serde_json::Map::new();
+    if let Value::Object(schemas) = document["components"]["schemas"].take() {
+        for (name, schema) in schemas {
+            let path = PathBuf::from(format!("{name}.schema.json"));
+            let resource = resource(schema, &name, &base, &path, "");
+            defs.insert(name, resource.clone());
+            files.insert(path, resource);
+        }
+    }
+    if let Value::Object(endpoints) = document["endpoints"].take() {
+        for (name, mut pair) in endpoints {
+            for (part, title) in [("request", "Request"), ("response", "Response")] {
+                let path = Path::new("endpoints").join(format!("{name}.{part}.schema.json"));
+                let resource = resource(pair[part].take(), &format!("{name}{title}"), &base, &path, "../");
+                defs.insert(format!("{name}.{part}"), resource.clone());
+                files.insert(path, resource);
+            }
+        }
+    }
+
+    for resource in defs.values_mut() {
+        if let Value::Object(map) = resource {
+            // Embedded resources inherit the bundle's dialect.
+            map.remove("$schema");
+        }
+    }
+    let bundle = json!({
+        "$schema": DRAFT_2020_12,
+        "$id": format!("{base}bundle.json"),
+        "title": document_title(data),
+        "$defs": defs,
+    });
+    Ok(JsonSchemas { files, bundle })
+}
+
+/// Turns one schema into a standalone resource at `path`: adds `$schema`,
+/// `$id` and a title, and points its refs at the definition files, which are
+/// `up` from `path`'s directory.
+fn resource(mut schema: Value, title: &str, base: &str, path: &Path, up: &str) -> Value {
+    relocate_to_files(&mut schema, up);
+    let mut resource = serde_json::Map::new();
+    resource.insert("$schema".into(), json!(DRAFT_2020_12));
+    resource.insert(
+        "$id".into(),
+        json!(format!("{base}{}", path.to_string_lossy().replace('\\', "/"))),
+    );
+    if let Value::Object(map) = schema {
+        if !map.contains_key("title") {
+            resource.insert("title".into(), json!(title));
+        }
+        resource.extend(map);
+    }
+    Value::Object(resource)
+}
+
+/// Rewrites `#/components/schemas/X` to `{up}X.schema.json` throughout `value`,
+/// and drops OpenAPI's `discriminator`, which plain JSON Schema does not know.
+/// Tagged unions stay unambiguous without it: each variant has its tag as a
+/// `const`.
+fn relocate_to_files(value: &mut Value, up: &str) {
+    match value {
+        Value::Object(map) => {
+            if map.contains_key("oneOf") {
+                map.remove("discriminator");
+            }
+            if let Some(Value::String(target)) = map.get_mut("$ref")
+                && let Some(name) = target.strip_prefix(COMPONENTS_SCHEMAS_PREFIX)
+            {
+                *target = format!("{up}{name}.schema.json");
+            }
+            for child in map.values_mut() {
+                relocate_to_files(child, up);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                relocate_to_files(item, up);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Writes `docs/schemas/`, removing `.schema.json` files left behind by
+/// definitions and endpoints that no longer exist.
+pub fn gen_json_schemas(data: &Data, public_only: bool) -> Result<()> {
+    let schemas_dir = data.project_root.join("docs").join("schemas");
+    let built = build_json_schemas(data, public_only)?;
+
+    for dir in [schemas_dir.clone(), schemas_dir.join("endpoints")] {
+        std::fs::create_dir_all(&dir)?;
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let stale = path.to_string_lossy().ends_with(".schema.json")
+                && path
+                    .strip_prefix(&schemas_dir)
+                    .is_ok_and(|rel| !built.files.contains_key(rel));
+            if stale {
+                std::fs::remove_file(&path)?;
+            }
+        }
+    }
+
+    for (rel, schema) in built
+        .files
+        .iter()
+        .chain([(&PathBuf::from("bundle.json"), &built.bundle)])
+    {
+        let filename = schemas_dir.join(rel);
+        let file = std::fs::File::create(&filename)
+            .with_context(|| format!("Failed to create JSON Schema file: {}", filename.display()))?;
+        serde_json::to_writer_pretty(file, schema)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{
+        EndpointSchemaElement, EnumElement, GenService, RustGenConfig, StructElement, TaggedUnionElement, TaggedVariant,
+    };
+    use endpoint_libs::model::{EndpointSchema, EnumVariant, Field, Type, collect_refs};
+
+    fn shared(name: &str, fields: Vec<Field>) -> StructElement {
+        StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec![],
+            inner: Type::struct_(name, fields),
+        }
+    }
+
+    fn sample_data() -> Data {
+        let place = EndpointSchema::new(
+            "PlaceOrder",
+            20000,
+            vec![Field::new("order", Type::struct_ref("Order"))],
+            vec![Field::new("id", Type::UUID)],
+        );
+        let order = shared(
+            "Order",
+            vec![
+                Field::new("id", Type::UUID),
+                Field::new("fills", Type::vec(Type::struct_ref("Fill"))),
+            ],
+        );
+        let fill = shared(
+            "Fill",
+            vec![
+                Field::new("price", Type::Float64),
+                Field::new("side", Type::enum_ref("Side", true)),
+            ],
+        );
+        let unused = shared("AuditEntry", vec![Field::new("note", Type::String)]);
+        Data {
+            project_name: "api.example.com".into(),
+            spec: Default::default(),
+            project_root: PathBuf::from("/tmp/api.example.com"),
+            output_dir: PathBuf::from("/tmp/api.example.com/generated"),
+            services: vec![GenService::new(
+                "orders".into(),
+                2,
+                vec![EndpointSchemaElement {
+                    frontend_facing: false,
+                    config: RustGenConfig::default(),
+                    schema: place,
+                    ..Default::default()
+                }],
+            )],
+            enums: vec![EnumElement {
+                config: RustGenConfig {
+                    prefix_enum: true,
+                    ..Default::default()
+                },
+                inner: Type::enum_("Side", vec![EnumVariant::new("Buy", 1)]),
+            }],
+            structs: vec![order, fill, unused],
+            unions: vec![TaggedUnionElement {
+                config: RustGenConfig::default(),
+                name: "OrderEvent".into(),
+                tag: "type".into(),
+                description: String::new(),
+                variants: vec![TaggedVariant {
+                    name: "Cancelled".into(),
+                    description: String::new(),
+                    fields: vec![],
+                }],
+            }],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    #[test]
+    fn every_file_is_a_resource_with_relative_refs() {
+        let built = build_json_schemas(&sample_data(), false).unwrap();
+
+        let order = &built.files[Path::new("Order.schema.json")];
+        assert_eq!(order["$schema"], DRAFT_2020_12);
+        assert_eq!(order["$id"], "https://api.example.com/schemas/Order.schema.json");
+        assert_eq!(order["properties"]["fills"]["items"]["$ref"], "Fill.schema.json");
+
+        let fill = &built.files[Path::new("Fill.schema.json")];
+        assert_eq!(fill["properties"]["side"]["$ref"], "EnumSide.schema.json");
+        assert!(built.files.contains_key(Path::new("EnumSide.schema.json")));
+        assert!(
+            !built.files.contains_key(Path::new("Side.schema.json")),
+            "a prefixed enum is emitted once, under its component name"
+        );
+
+        let request = &built.files[Path::new("endpoints/PlaceOrder.request.schema.json")];
+        assert_eq!(
+            request["$id"],
+            "https://api.example.com/schemas/endpoints/PlaceOrder.request.schema.json"
+        );
+        assert_eq!(request["title"], "PlaceOrderRequest");
+        assert_eq!(request["properties"]["order"]["$ref"], "../Order.schema.json");
+
+        // Unreferenced definitions are still emitted, and every ref lands on a file.
+        assert!(built.files.contains_key(Path::new("AuditEntry.schema.json")));
+        // Unions keep the tag `const`s but not OpenAPI's discriminator.
+        let union = &built.files[Path::new("OrderEvent.schema.json")];
+        assert_eq!(union["oneOf"][0]["$ref"], "OrderEventCancelled.schema.json");
+        assert!(union.get("discriminator").is_none());
+        for (path, schema) in &built.files {
+            let mut refs = vec![];
+            collect_refs(schema, &mut refs);
+            for target in refs {
+                let resolved = path.parent().unwrap().join(&target);
+                let normalized: PathBuf = resolved.components().fold(PathBuf::new(), |mut acc, part| {
+                    match part {
+                        std::path::Component::ParentDir => {
+                            acc.pop();
+                        }
+                        other => acc.push(other),
+                    }
+                    acc
+                });
+                assert!(built.files.contains_key(&normalized), "{}: {target}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn the_bundle_embeds_each_resource_under_its_id() {
+        let built = build_json_schemas(&sample_data(), false).unwrap();
+        let defs = built.bundle["$defs"].as_object().unwrap();
+
+        assert_eq!(defs.len(), built.files.len());
+        assert_eq!(defs["Order"]["$id"], built.files[Path::new("Order.schema.json")]["$id"]);
+        assert!(defs["Order"].get("$schema").is_none());
+        assert_eq!(defs["PlaceOrder.response"]["required"], json!(["id"]));
+    }
+
+    #[test]
+    fn public_only_emits_only_what_public_endpoints_reach() {
+        let mut data = sample_data();
+        let built = build_json_schemas(&data, true).unwrap();
+        assert!(!built.files.keys().any(|p| p.starts_with("endpoints")));
+        assert!(!built.files.contains_key(Path::new("Order.schema.json")));
+
+        data.services[0].endpoints[0].frontend_facing = true;
+        let built = build_json_schemas(&data, true).unwrap();
+        assert!(built.files.contains_key(Path::new("Fill.schema.json")));
+        assert!(!built.files.contains_key(Path::new("AuditEntry.schema.json")));
+    }
+}
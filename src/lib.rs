@@ -1,11 +1,26 @@
 pub mod asyncapi;
+pub mod audiences;
 pub mod definitions;
 pub mod docs;
 pub mod error_codes;
+pub mod gateway;
+pub mod generics;
+pub mod graphql;
+pub mod json_schema;
+pub mod maps;
+pub mod mcp;
 pub mod openapi;
+pub mod openrpc;
+pub mod proto;
 pub mod rust;
+pub mod rust_item;
+pub mod serde_adapters;
 pub mod service;
 pub mod spec_common;
+pub mod spec_config;
+pub mod spec_output;
+pub mod sql;
+pub mod type_check;
 
 // Re-export the derive macro for convenience
 pub use endpoint_gen_macros::DefinitionVariant;
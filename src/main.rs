@@ -9,10 +9,24 @@ use clap::Parser;
 use convert_case::{Case, Casing};
 use endpoint_gen::{
     asyncapi,
-    definitions::{Definition, EndpointSchemaElement, EnumElement, ErrorCodeSchema, GenService, StructElement},
-    docs::{self, Data},
+    audiences::{self, AUDIENCES_DIR, CutOptions, SERVICES_DIR, validate_audiences},
+    definitions::{
+        self, Definition, EndpointSchemaElement, EnumElement, ErrorCodeSchema, GenService, McpPromptElement,
+        McpResourceElement, NewtypeElement, RawRustGenConfig, ServerEventElement, StructElement, TaggedUnionElement,
+    },
+    docs::{self, Data, MCP_ROLES_DIR},
     error_codes::{build_error_code_catalog, validate_endpoint_error_codes, validate_reserved_enum_names},
-    openapi, rust,
+    gateway::{self, GATEWAY_FILE, GatewayConfig, read_gateway_file},
+    graphql, json_schema, mcp, openapi, openrpc,
+    proto::{self, ProtoOptions},
+    rust,
+    spec_config::{SPEC_FILE, read_spec_file},
+    spec_output::{self, SPEC_DOCUMENTS, SPLIT_SCHEMAS_DIR, SpecFormat, SpecOutput},
+    sql,
+    type_check::{
+        DefinitionSources, check_mcp_catalog, check_mcp_tools, check_operational, check_references, check_sql_tables,
+        check_stream_cancellation, check_type_consistency, check_worktable_structs,
+    },
 };
 use endpoint_libs::model::Type;
 use eyre::*;
@@ -58,15 +72,102 @@ struct Cli {
     #[arg(long)]
     asyncapi: bool,
 
+    /// Emit `docs/openrpc.json` (OpenRPC 1.3): one method per endpoint, named
+    /// after it, with the wire code in `x-endpoint-code`.
+    ///
+    /// Off by default, like the other specification documents.
+    #[arg(long)]
+    openrpc: bool,
+
+    /// Emit standalone JSON Schema (draft 2020-12) into `docs/schemas/`: one
+    /// file per shared definition, a request and a response file per endpoint,
+    /// and `bundle.json` holding them all.
+    ///
+    /// Off by default, like the other specification documents.
+    #[arg(long)]
+    json_schema: bool,
+
+    /// The file format of `openapi`, `asyncapi`, `openrpc` and
+    /// `openapi.gateway`: `json` or `yaml`.
+    #[arg(long, value_name = "json|yaml", default_value_t = SpecFormat::Json)]
+    spec_format: SpecFormat,
+
+    /// Move the schemas of those documents into `docs/components/schemas/`,
+    /// one file per schema in `--spec-format`, referenced by relative `$ref`.
+    ///
+    /// The documents share their schemas, so they share the files too.
+    #[arg(long)]
+    split_components: bool,
+
     /// Emit only `frontend_facing` endpoints into the specification documents —
     /// the version you would hand to a third party.
     ///
-    /// Filtering is per endpoint, not per service. Only meaningful with
-    /// `--openapi` and/or `--asyncapi`; does not affect the Rust output or the
-    /// MCP tool lists.
+    /// Filtering is per endpoint, not per service. Only meaningful with the
+    /// specification documents, where `--json-schema` also drops definitions
+    /// no public endpoint reaches; does not affect the Rust output or the MCP
+    /// tool lists.
     #[arg(long)]
     public_only: bool,
 
+    /// Also write `docs/audiences/{audience}/` for every audience endpoints
+    /// list: the OpenAPI and AsyncAPI documents that are enabled, and the MCP
+    /// tool lists, holding only that audience's endpoints.
+    ///
+    /// Honours `--public-only`, `--spec-format` and `--split-components`.
+    #[arg(long)]
+    per_audience: bool,
+
+    /// Also write `docs/services/{serviceName}/`, like `--per-audience` but
+    /// holding one service each.
+    #[arg(long)]
+    per_service: bool,
+
+    /// Also write `docs/mcp/{role}/{serviceName}_mcp_tools.json`: the MCP tool
+    /// list a caller holding only that role is offered, one directory per
+    /// variant of the role enums.
+    ///
+    /// Off by default, like the other extra artifacts.
+    #[arg(long)]
+    mcp_by_role: bool,
+
+    /// Emit `generated/gateway.rs`, an axum router serving the OpenAPI paths
+    /// by forwarding each request to the WebSocket backend, and
+    /// `docs/openapi.gateway.json`, the OpenAPI document of that deployment.
+    ///
+    /// Servers and the HTTP status of each error code come from `gateway.ron`
+    /// next to `version.toml`. Honours `--public-only`.
+    #[arg(long)]
+    gateway: bool,
+
+    /// Emit `docs/graphql/schema.graphql` (GraphQL SDL) and
+    /// `docs/graphql/resolvers.json`, which maps each root field to its
+    /// endpoint code for a gateway.
+    ///
+    /// Honours `--public-only`.
+    #[arg(long)]
+    graphql: bool,
+
+    /// Emit `docs/sql/schema.sql` and `docs/sql/schema.json` for the structs
+    /// that set `sql_table`.
+    ///
+    /// When a previous `schema.json` is replaced by a different one, a forward
+    /// migration is also written to `docs/sql/migrations/`.
+    #[arg(long)]
+    sql: bool,
+
+    /// Emit `docs/proto/<project>.proto` (proto3) and keep its field numbers
+    /// in `docs/proto/fields.lock.json`.
+    ///
+    /// Commit the lockfile: it is what keeps field numbers stable when fields
+    /// are reordered, added or removed.
+    #[arg(long)]
+    proto: bool,
+
+    /// Map `Object` fields to `google.protobuf.Struct` in `--proto` output
+    /// instead of failing on them.
+    #[arg(long)]
+    proto_object_as_struct: bool,
+
     /// Verify the committed artifacts match the RON instead of writing them.
     ///
     /// Regenerates everything into a temporary directory, diffs it against the
@@ -107,25 +208,73 @@ fn main() -> Result<()> {
 
     let output_dir = generation_root.join("generated");
 
-    let input_objects = build_object_lists(config_dir, args.allow_empty_descriptions)?;
+    let spec = read_spec_file(&config_dir.join(SPEC_FILE))?;
+    spec.validate()?;
+    let spec = spec.resolve(&generation_root)?;
+
+    let input_objects = build_object_lists(config_dir.clone(), args.allow_empty_descriptions)?;
 
     let data = Data {
         project_name: generation_root
             .file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| "API".into()),
+        spec,
         project_root: generation_root,
         output_dir,
         services: input_objects.services,
         enums: input_objects.enums,
         structs: input_objects.structs,
+        unions: input_objects.unions,
+        newtypes: input_objects.newtypes,
         error_codes: input_objects.error_codes,
     };
 
+    for suggestion in check_type_consistency(&data, &input_objects.sources)? {
+        eprintln!("Note: {suggestion}");
+    }
+    for warning in check_references(&data, &input_objects.sources)? {
+        eprintln!("Warning: {warning}");
+    }
+    check_worktable_structs(&data, &input_objects.sources)?;
+    check_sql_tables(&data, &input_objects.sources)?;
+    check_stream_cancellation(&data, &input_objects.sources)?;
+    check_operational(&data, &input_objects.sources)?;
+    check_mcp_tools(&data, &input_objects.sources)?;
+    check_mcp_catalog(&data, &input_objects.sources)?;
+    validate_audiences(&data)?;
+    let proto = args.proto.then_some(ProtoOptions {
+        object_as_struct: args.proto_object_as_struct,
+    });
+    if let Some(options) = proto {
+        proto::check_proto(&data, options)?;
+    }
+
+    let gateway = if args.gateway {
+        let config = read_gateway_file(&config_dir.join(GATEWAY_FILE))?;
+        config.validate(&data.error_codes)?;
+        Some(config)
+    } else {
+        None
+    };
+
     let specs = SpecOptions {
         openapi: args.openapi,
         asyncapi: args.asyncapi,
+        openrpc: args.openrpc,
+        json_schema: args.json_schema,
         public_only: args.public_only,
+        output: SpecOutput {
+            format: args.spec_format,
+            split: args.split_components,
+        },
+        per_audience: args.per_audience,
+        per_service: args.per_service,
+        mcp_by_role: args.mcp_by_role,
+        graphql: args.graphql,
+        gateway: gateway.as_ref(),
+        sql: args.sql,
+        proto,
     };
 
     if args.check {
@@ -140,15 +289,31 @@ fn main() -> Result<()> {
 /// Both default to off: upgrading `endpoint-gen` must not add committed
 /// artifacts to a repository that never asked for them.
 #[derive(Debug, Clone, Copy)]
-struct SpecOptions {
+struct SpecOptions<'a> {
     openapi: bool,
     asyncapi: bool,
+    openrpc: bool,
+    json_schema: bool,
     public_only: bool,
+    output: SpecOutput,
+    per_audience: bool,
+    per_service: bool,
+    /// Not specification documents, but opt-in the same way.
+    mcp_by_role: bool,
+    graphql: bool,
+    gateway: Option<&'a GatewayConfig>,
+    sql: bool,
+    proto: Option<ProtoOptions>,
 }
 
-impl SpecOptions {
+impl SpecOptions<'_> {
     fn any(&self) -> bool {
-        self.openapi || self.asyncapi
+        self.documents() || self.json_schema
+    }
+
+    /// Whether any document written in `output`'s format and layout is on.
+    fn documents(&self) -> bool {
+        self.openapi || self.asyncapi || self.openrpc || self.gateway.is_some()
     }
 }
 
@@ -165,20 +330,69 @@ fn run_generation(data: &Data, specs: SpecOptions) -> Result<()> {
     // AsyncAPI document all camelCase field names themselves, matching the wire
     // format regardless of the snake_case_fields config.
     docs::gen_mcp_tools_json(data)?;
+    if specs.mcp_by_role {
+        docs::gen_mcp_tools_by_role(data)?;
+    }
+    mcp::gen_mcp_catalog_json(data)?;
+    if specs.documents() {
+        spec_output::clear_split_schemas(&data.project_root.join("docs"))?;
+    }
     if specs.openapi {
-        openapi::gen_openapi(data, specs.public_only)?;
+        openapi::gen_openapi(data, specs.public_only, specs.output)?;
     }
     if specs.asyncapi {
-        asyncapi::gen_asyncapi(data, specs.public_only)?;
+        asyncapi::gen_asyncapi(data, specs.public_only, specs.output)?;
+    }
+    if specs.openrpc {
+        openrpc::gen_openrpc(data, specs.public_only, specs.output)?;
+    }
+    if specs.json_schema {
+        json_schema::gen_json_schemas(data, specs.public_only)?;
     }
     if specs.any() {
-        docs::gen_spec_readme(&data.project_root, specs.openapi, specs.asyncapi)?;
+        docs::gen_spec_readme(
+            &data.project_root,
+            specs.openapi,
+            specs.asyncapi,
+            specs.openrpc,
+            specs.json_schema,
+            specs.gateway.is_some(),
+            specs.output,
+        )?;
+    }
+    audiences::gen_cuts(
+        data,
+        CutOptions {
+            per_audience: specs.per_audience,
+            per_service: specs.per_service,
+            openapi: specs.openapi,
+            asyncapi: specs.asyncapi,
+            public_only: specs.public_only,
+            output: specs.output,
+        },
+    )?;
+    if specs.graphql {
+        graphql::gen_graphql(data, specs.public_only)?;
+    }
+    if let Some(config) = specs.gateway {
+        openapi::gen_gateway_openapi(data, specs.public_only, config, specs.output)?;
+        gateway::gen_gateway(data, specs.public_only, config)?;
+    }
+    if specs.sql {
+        sql::gen_sql(data)?;
+    }
+    if let Some(options) = specs.proto {
+        proto::gen_proto(data, options)?;
     }
     rust::gen_model_rs(data)?;
     docs::gen_error_message_md(&data.project_root, &data.error_codes)?;
     Ok(())
 }
 
+/// Committed files a generation reads as well as writes, relative to the
+/// project root.
+const CARRIED_OVER: &[&str] = &["docs/proto/fields.lock.json"];
+
 /// Regenerates into a temp directory and diffs `docs/` against the working tree.
 ///
 /// Returns `Err` (non-zero exit) listing every drifted or missing file. Writes
@@ -190,13 +404,26 @@ fn run_check(data: &Data, specs: SpecOptions) -> Result<()> {
         // Same name, different location: the point of --check is to compare
         // content, so nothing but the output path may differ.
         project_name: data.project_name.clone(),
+        spec: data.spec.clone(),
         project_root: scratch.path().to_path_buf(),
         output_dir: scratch.path().join("generated"),
         services: data.services.clone(),
         enums: data.enums.clone(),
         structs: data.structs.clone(),
+        unions: data.unions.clone(),
+        newtypes: data.newtypes.clone(),
         error_codes: data.error_codes.clone(),
     };
+    // Inputs of the generation that live among its outputs, like the field
+    // numbers --proto keeps stable, start from their committed state.
+    for carried in CARRIED_OVER {
+        let committed = data.project_root.join(carried);
+        if committed.exists() {
+            let target = scratch.path().join(carried);
+            fs::create_dir_all(target.parent().expect("carried paths are under docs/"))?;
+            fs::copy(&committed, &target)?;
+        }
+    }
     run_generation(&staged, specs)?;
 
     let staged_docs = scratch.path().join("docs");
@@ -229,6 +456,58 @@ fn run_check(data: &Data, specs: SpecOptions) -> Result<()> {
         }
     }
 
+    // Switching --spec-format or --split-components leaves the other layout's
+    // files behind, where they look current. The staged tree holds only the
+    // layout asked for, so anything of the other one is stale. Generation
+    // removes them, as it does the directories of an audience or service
+    // since removed.
+    // Of `docs/mcp/`, only the role tool lists are ours.
+    let everything: fn(&Path) -> bool = |_| true;
+    let owned_dirs = [
+        (SPLIT_SCHEMAS_DIR, specs.documents(), everything),
+        (AUDIENCES_DIR, specs.per_audience, everything),
+        (SERVICES_DIR, specs.per_service, everything),
+        (MCP_ROLES_DIR, specs.mcp_by_role, docs::is_role_tools_file),
+    ];
+    let mut stale: Vec<PathBuf> = vec![];
+    for (dir, owned, owns) in owned_dirs {
+        if owned {
+            let dir = committed_docs.join(dir);
+            stale.extend(
+                WalkDir::new(&dir)
+                    .into_iter()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_file())
+                    .filter(|e| e.path().strip_prefix(&dir).is_ok_and(owns))
+                    .filter_map(|e| e.path().strip_prefix(&committed_docs).ok().map(Path::to_path_buf)),
+            );
+        }
+    }
+    // A service whose every endpoint is hidden from MCP gets no tool list, and
+    // one without resources or prompts gets no file for them. Generation
+    // removes the ones written before.
+    for service in &data.services {
+        for kind in ["tools", "resources", "prompts"] {
+            stale.push(PathBuf::from(format!("{}_mcp_{kind}.json", service.name)));
+        }
+    }
+    for stem in SPEC_DOCUMENTS {
+        for (format, other) in [
+            (SpecFormat::Json, SpecFormat::Yaml),
+            (SpecFormat::Yaml, SpecFormat::Json),
+        ] {
+            if staged_docs.join(format!("{stem}.{format}")).exists() {
+                stale.push(PathBuf::from(format!("{stem}.{other}")));
+            }
+        }
+    }
+    stale.sort();
+    for rel in stale {
+        if committed_docs.join(&rel).exists() && !staged_docs.join(&rel).exists() {
+            drift.push(format!("  stale:    docs/{}", rel.display()));
+        }
+    }
+
     if drift.is_empty() {
         println!("endpoint-gen --check: {compared} generated file(s) match the RON definitions.");
         return Ok(());
@@ -279,6 +558,14 @@ fn format_for_docs(data: &Data) -> Data {
                     endpoint
                 })
                 .collect();
+            for event in &mut gen_service.events {
+                if event.config.snake_case_fields {
+                    event.fields = std::mem::take(&mut event.fields)
+                        .into_iter()
+                        .map(camel_case_field)
+                        .collect();
+                }
+            }
 
             gen_service
         })
@@ -306,11 +593,14 @@ fn format_for_docs(data: &Data) -> Data {
 
     Data {
         project_name: data.project_name.clone(),
+        spec: data.spec.clone(),
         project_root: data.project_root.clone(),
         output_dir: data.output_dir.clone(),
         services: formatted_services,
         enums: data.enums.clone(),
         structs: formatted_structs,
+        unions: data.unions.clone(),
+        newtypes: data.newtypes.clone(),
         error_codes: data.error_codes.clone(),
     }
 }
@@ -329,8 +619,9 @@ fn process_file(file_path: &Path) -> eyre::Result<Option<Definition>> {
 
 /// Returns one violation string per missing/blank description in the
 /// definition. Endpoint descriptions become MCP tool descriptions and doc
-/// text; enum variant descriptions are emitted into the generated JSON
-/// schemas — both are validated.
+/// text, event and MCP prompt descriptions are what a client is shown for
+/// them, and enum variant descriptions are emitted into the generated JSON
+/// schemas — all are validated.
 fn description_violations(definition: &Definition, path: &Path) -> Vec<String> {
     fn blank(s: &str) -> bool {
         s.trim().is_empty()
@@ -397,14 +688,54 @@ fn description_violations(definition: &Definition, path: &Path) -> Vec<String> {
                 }
             }
         }
+        // Union variants are to a tagged union what variants are to an enum.
+        Definition::TaggedUnion(union) => {
+            for variant in &union.variants {
+                if blank(&variant.description) {
+                    violations.push(format!(
+                        "{}: tagged union '{}' variant '{}': missing or empty description",
+                        path.display(),
+                        union.name,
+                        variant.name
+                    ));
+                }
+            }
+        }
         // Struct fields cannot carry RON descriptions — Field.description is
         // #[serde(skip)] upstream — so struct definitions can never violate.
         Definition::Struct(_) | Definition::StructList(_) => {}
+        // A newtype's description is optional: the name usually says it all.
+        Definition::Newtype(_) => {}
+        Definition::ServerEvent(event) => {
+            if blank(&event.description) {
+                violations.push(format!(
+                    "{}: service '{}' event '{}': missing or empty description",
+                    path.display(),
+                    event.service_name,
+                    event.name
+                ));
+            }
+        }
+        // A resource without a description takes its endpoint's.
+        Definition::McpResource(_) => {}
+        Definition::McpPrompt(prompt) => {
+            if blank(&prompt.description) {
+                violations.push(format!(
+                    "{}: service '{}' MCP prompt '{}': missing or empty description",
+                    path.display(),
+                    prompt.service_name,
+                    prompt.name
+                ));
+            }
+        }
     }
     violations
 }
 
-fn process_input_files(dir: PathBuf, allow_empty_descriptions: bool) -> eyre::Result<Vec<Definition>> {
+fn process_input_files(
+    dir: PathBuf,
+    allow_empty_descriptions: bool,
+) -> eyre::Result<(Vec<Definition>, DefinitionSources)> {
     let root = dir.as_path();
 
     // Walk through the directory and all subdirectories
@@ -418,16 +749,24 @@ fn process_input_files(dir: PathBuf, allow_empty_descriptions: bool) -> eyre::Re
     paths.sort();
 
     let mut rust_configs: Vec<Definition> = vec![];
+    let mut sources = DefinitionSources::new();
     let mut valid_config_files_counter = 0u32;
     let mut config_errors = vec![];
     let mut description_errors = vec![];
     for path in paths {
+        if [DEFAULTS_FILE, GATEWAY_FILE, SPEC_FILE]
+            .iter()
+            .any(|file| path == root.join(file))
+        {
+            continue;
+        }
         match process_file(path.as_path()) {
             Ok(rust_config) => {
                 if let Some(config) = rust_config {
                     if !allow_empty_descriptions {
                         description_errors.extend(description_violations(&config, path.as_path()));
                     }
+                    sources.record(&config, path.as_path());
                     rust_configs.push(config);
                     valid_config_files_counter += 1;
                 }
@@ -460,25 +799,39 @@ fn process_input_files(dir: PathBuf, allow_empty_descriptions: bool) -> eyre::Re
         bail!("No valid RON config files found in given path, aborting generation process");
     }
 
-    Ok(rust_configs)
+    Ok((rust_configs, sources))
 }
 
 struct InputObjects {
     services: Vec<GenService>,
     enums: Vec<EnumElement>,
     structs: Vec<StructElement>,
+    unions: Vec<TaggedUnionElement>,
+    newtypes: Vec<NewtypeElement>,
     error_codes: Vec<ErrorCodeSchema>,
+    sources: DefinitionSources,
 }
 
 fn build_object_lists(dir: PathBuf, allow_empty_descriptions: bool) -> eyre::Result<InputObjects> {
-    let rust_configs = process_input_files(dir, allow_empty_descriptions)?;
+    let defaults = read_defaults_file(&dir.join(DEFAULTS_FILE))?;
+    // Every config is read on top of the defaults, parent lists included.
+    let (rust_configs, sources) =
+        definitions::with_project_defaults(&defaults.rust, || process_input_files(dir, allow_empty_descriptions))?;
 
     let mut service_schema_map: HashMap<(String, u16), Vec<EndpointSchemaElement>> = HashMap::new();
 
+    let mut service_event_map: HashMap<(String, u16), Vec<ServerEventElement>> = HashMap::new();
+
+    let mut service_resource_map: HashMap<(String, u16), Vec<McpResourceElement>> = HashMap::new();
+
+    let mut service_prompt_map: HashMap<(String, u16), Vec<McpPromptElement>> = HashMap::new();
+
     let mut services: Vec<GenService> = vec![];
 
     let mut enums: Vec<EnumElement> = vec![];
     let mut structs: Vec<StructElement> = vec![];
+    let mut unions: Vec<TaggedUnionElement> = vec![];
+    let mut newtypes: Vec<NewtypeElement> = vec![];
     let mut custom_error_codes: Vec<ErrorCodeSchema> = vec![];
 
     for config in rust_configs {
@@ -518,12 +871,46 @@ fn build_object_lists(dir: PathBuf, allow_empty_descriptions: bool) -> eyre::Res
                     ele
                 }))
             }
+            Definition::TaggedUnion(union) => unions.push(union),
+            Definition::Newtype(newtype) => newtypes.push(newtype),
+            Definition::ServerEvent(event) => service_event_map
+                .entry((event.service_name.clone(), event.service_id))
+                .or_default()
+                .push(event),
+            Definition::McpResource(resource) => service_resource_map
+                .entry((resource.service_name.clone(), resource.service_id))
+                .or_default()
+                .push(resource),
+            Definition::McpPrompt(prompt) => service_prompt_map
+                .entry((prompt.service_name.clone(), prompt.service_id))
+                .or_default()
+                .push(prompt),
         }
     }
 
+    // A service may push events without having any endpoints. Resources and
+    // prompts always need some, but keeping their service lets
+    // `check_mcp_catalog` say so instead of dropping them.
+    for key in service_event_map
+        .keys()
+        .chain(service_resource_map.keys())
+        .chain(service_prompt_map.keys())
+    {
+        service_schema_map.entry(key.clone()).or_default();
+    }
     if !service_schema_map.is_empty() {
         for ((service_name, service_id), endpoint_schemas) in service_schema_map {
-            services.push(GenService::new(service_name, service_id, endpoint_schemas));
+            let mut service = GenService::new(service_name, service_id, endpoint_schemas);
+            service.events = service_event_map
+                .remove(&(service.name.clone(), service.id))
+                .unwrap_or_default();
+            service.resources = service_resource_map
+                .remove(&(service.name.clone(), service.id))
+                .unwrap_or_default();
+            service.prompts = service_prompt_map
+                .remove(&(service.name.clone(), service.id))
+                .unwrap_or_default();
+            services.push(service);
         }
     }
 
@@ -538,14 +925,20 @@ fn build_object_lists(dir: PathBuf, allow_empty_descriptions: bool) -> eyre::Res
     // which is what lets `--check` be trusted in CI.
     services.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| a.name.cmp(&b.name)));
 
-    // Sort the endpoints of each service by their codes
-    services
-        .iter_mut()
-        .for_each(|service| service.endpoints.sort_by_key(|a| a.schema.code));
+    // Sort the endpoints and events of each service by their codes, and its
+    // resources and prompts by name
+    services.iter_mut().for_each(|service| {
+        service.endpoints.sort_by_key(|a| a.schema.code);
+        service.events.sort_by_key(|a| a.code);
+        service.resources.sort_by(|a, b| a.name.cmp(&b.name));
+        service.prompts.sort_by(|a, b| a.name.cmp(&b.name));
+    });
 
     // Sort enums and structs by their default ordering
     enums.sort();
     structs.sort();
+    unions.sort();
+    newtypes.sort();
 
     let error_codes = build_error_code_catalog(custom_error_codes)?;
     validate_reserved_enum_names(&enums)?;
@@ -555,7 +948,10 @@ fn build_object_lists(dir: PathBuf, allow_empty_descriptions: bool) -> eyre::Res
         services,
         enums,
         structs,
+        unions,
+        newtypes,
         error_codes,
+        sources,
     })
 }
 
@@ -564,6 +960,26 @@ struct Config {
     definition: Definition,
 }
 
+/// Project-wide settings, read from the root of the config directory.
+const DEFAULTS_FILE: &str = "defaults.ron";
+
+/// The contents of [`DEFAULTS_FILE`].
+#[derive(Debug, Default, Deserialize)]
+struct Defaults {
+    /// Goes under every definition's own `config`; see
+    /// [`RawRustGenConfig::resolve`].
+    #[serde(default)]
+    rust: RawRustGenConfig,
+}
+
+fn read_defaults_file(path: &Path) -> eyre::Result<Defaults> {
+    if !path.exists() {
+        return Ok(Defaults::default());
+    }
+    let content = fs::read_to_string(path)?;
+    from_str(&content).wrap_err_with(|| format!("Error parsing {}", path.display()))
+}
+
 #[derive(Debug, Deserialize)]
 struct VersionConfig {
     binary: BinaryVersion,
@@ -632,6 +1048,7 @@ mod tests {
     fn format_for_docs_camel_cases_endpoint_error_fields() {
         let data = Data {
             project_name: "test".into(),
+            spec: Default::default(),
             project_root: PathBuf::new(),
             output_dir: PathBuf::new(),
             services: vec![GenService::new(
@@ -657,10 +1074,13 @@ mod tests {
                                 Field::new("actual_length", Type::Int32),
                             ]),
                     ]),
+                    ..Default::default()
                 }],
             )],
             enums: vec![],
             structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
             error_codes: vec![],
         };
 
@@ -689,6 +1109,7 @@ mod tests {
                     config: RustGenConfig::default(),
                     schema: EndpointSchema::new(format!("Endpoint{i}"), 60000 + i as u32, vec![], vec![])
                         .with_description(*desc),
+                    ..Default::default()
                 })
                 .collect(),
         })
@@ -738,6 +1159,58 @@ mod tests {
         assert!(violations[1].contains("variant 'Variant2'"));
     }
 
+    #[test]
+    fn description_violations_flags_blank_server_events() {
+        use endpoint_gen::definitions::ServerEventElement;
+        let path = Path::new("config/events/maintenance.ron");
+        let event = |description: &str| {
+            Definition::ServerEvent(ServerEventElement {
+                service_name: "userApi".to_string(),
+                service_id: 6,
+                config: RustGenConfig::default(),
+                name: "Maintenance".to_string(),
+                code: 60100,
+                fields: vec![],
+                roles: vec![],
+                description: description.to_string(),
+            })
+        };
+        assert!(description_violations(&event("Announces downtime."), path).is_empty());
+        let violations = description_violations(&event(" "), path);
+        assert_eq!(violations.len(), 1, "{violations:?}");
+        assert!(
+            violations[0].contains("service 'userApi' event 'Maintenance'"),
+            "{violations:?}"
+        );
+        assert!(violations[0].contains("maintenance.ron"), "{violations:?}");
+    }
+
+    #[test]
+    fn description_violations_flags_blank_mcp_prompts() {
+        use endpoint_gen::definitions::McpPromptElement;
+        let path = Path::new("config/prompts/explain_order.ron");
+        let prompt = |description: &str| {
+            Definition::McpPrompt(McpPromptElement {
+                service_name: "userApi".to_string(),
+                service_id: 6,
+                name: "explain_order".to_string(),
+                title: None,
+                description: description.to_string(),
+                arguments: vec![],
+                endpoints: vec![],
+                template: "Explain the order.".to_string(),
+            })
+        };
+        assert!(description_violations(&prompt("Explains one order."), path).is_empty());
+        let violations = description_violations(&prompt(""), path);
+        assert_eq!(violations.len(), 1, "{violations:?}");
+        assert!(
+            violations[0].contains("service 'userApi' MCP prompt 'explain_order'"),
+            "{violations:?}"
+        );
+        assert!(violations[0].contains("explain_order.ron"), "{violations:?}");
+    }
+
     #[test]
     fn description_violations_ignores_structs() {
         // Struct fields cannot carry RON descriptions (Field.description is
@@ -767,4 +1240,52 @@ mod tests {
         assert!(violations[1].contains("error code 'Blank' (419)"), "{violations:?}");
         assert!(violations[0].contains("error_codes.ron"));
     }
+
+    #[test]
+    fn defaults_file_goes_under_every_definition_unless_overridden() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(DEFAULTS_FILE),
+            r#"(rust: (json_schema_gen: true, derives: ["PartialEq"], attributes: ["serde(deny_unknown_fields)"]))"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("structs.ron"),
+            r#"#![enable(unwrap_newtypes)]
+            #![enable(unwrap_variant_newtypes)]
+            Config(definition: StructList(
+                config: (derives: ["Hash"]),
+                struct_elements: [
+                    (inner: Struct(name: "Plain", fields: [])),
+                    (config: (override_parent: true), inner: Struct(name: "Own", fields: [])),
+                ],
+            ))"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("quiet.ron"),
+            r#"#![enable(unwrap_newtypes)]
+            #![enable(unwrap_variant_newtypes)]
+            Config(definition: Struct(config: (json_schema_gen: false), inner: Struct(name: "Quiet", fields: [])))"#,
+        )
+        .unwrap();
+
+        let objects = build_object_lists(dir.path().to_path_buf(), true).unwrap();
+        let config = |name: &str| {
+            let element = objects
+                .structs
+                .iter()
+                .find(|s| matches!(&s.inner, Type::Struct { name: n, .. } if n == name));
+            element.unwrap().config.clone()
+        };
+        let (plain, own) = (config("Plain"), config("Own"));
+        assert_eq!(plain.derives, ["PartialEq", "Hash"]);
+        assert_eq!(plain.attributes, ["serde(deny_unknown_fields)"]);
+        assert!(own.derives.is_empty() && own.attributes.is_empty());
+        assert!(plain.json_schema_gen && !own.json_schema_gen);
+        assert!(
+            !config("Quiet").json_schema_gen,
+            "a definition can switch a default flag off"
+        );
+    }
 }
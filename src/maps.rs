@@ -0,0 +1,342 @@
+//! Map fields: `StructRef("BTreeMap<String, f64>")`.
+//!
+//! `Type` has no map variant, so a map is written as a reference in Rust
+//! spelling, which `model.rs` then uses verbatim. Either `BTreeMap` or
+//! `HashMap` may be used; both are a JSON object on the wire. Keys are `String`
+//! or a shared enum by its generated name (`EnumSide`). Values are Rust type
+//! names: `i32`, `i64`, `u32`, `f64`, `bool`, `String`, `serde_json::Value`,
+//! `Vec<..>`, `Option<..>`, another map, or anything a `StructRef` resolves to.
+//!
+//! For schemas each map is registered as a placeholder struct whose single
+//! `value` field has the value type, so endpoint-libs collects whatever the
+//! values reference. [`inline_maps`] then replaces every reference to a
+//! placeholder with an inline `additionalProperties` object.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use convert_case::{Case, Casing};
+use endpoint_libs::model::{Field, Type};
+use serde_json::{Map, Value, json};
+
+use crate::definitions::EnumElement;
+use crate::docs::Data;
+use crate::generics::{find_enum_by_rust_name, parse_generic_ref, referencing_types};
+
+/// The key and value type names of a map reference, or `None` if `name` is not
+/// a `BTreeMap<K, V>` or `HashMap<K, V>`.
+pub fn parse_map_ref(name: &str) -> Option<(String, String)> {
+    let (base, mut args) = parse_generic_ref(name)?;
+    if !matches!(base.as_str(), "BTreeMap" | "HashMap") || args.len() != 2 {
+        return None;
+    }
+    let value = args.pop()?;
+    let key = args.pop()?;
+    Some((key, value))
+}
+
+/// The type a map value name stands for. Names that are neither primitives nor
+/// enums become a `StructRef`, to be resolved like any other.
+pub fn value_type(name: &str, enums: &[EnumElement]) -> Type {
+    let primitive = match name {
+        "i32" => Some(Type::Int32),
+        "i64" => Some(Type::Int64),
+        "u32" => Some(Type::UInt32),
+        "f64" => Some(Type::Float64),
+        "bool" => Some(Type::Boolean),
+        "String" => Some(Type::String),
+        "serde_json::Value" => Some(Type::Object),
+        _ => None,
+    };
+    if let Some(ty) = primitive {
+        return ty;
+    }
+    if parse_map_ref(name).is_none()
+        && let Some((base, args)) = parse_generic_ref(name)
+        && args.len() == 1
+    {
+        match base.as_str() {
+            "Vec" => return Type::vec(value_type(&args[0], enums)),
+            "Option" => return Type::optional(value_type(&args[0], enums)),
+            _ => {}
+        }
+    }
+    match find_enum_by_rust_name(enums, name) {
+        Some(element) => match &element.inner {
+            Type::Enum { name, .. } => Type::enum_ref(name.clone(), element.config.prefix_enum),
+            _ => unreachable!("EnumElement is validated to wrap an enum"),
+        },
+        None => Type::StructRef(name.to_string()),
+    }
+}
+
+/// The names inside a map value type that have to resolve, as written:
+/// everything but primitives and the `Vec`/`Option` wrappers.
+pub fn value_names(name: &str, out: &mut Vec<String>) {
+    match value_type(name, &[]) {
+        Type::StructRef(_) => {}
+        Type::Vec(_) | Type::Optional(_) => {
+            if let Some((_, args)) = parse_generic_ref(name) {
+                value_names(&args[0], out);
+            }
+            return;
+        }
+        _ => return,
+    }
+    out.push(name.to_string());
+}
+
+fn collect_map_refs(ty: &Type, enums: &[EnumElement], out: &mut BTreeMap<String, Type>) {
+    match ty {
+        Type::StructRef(name) | Type::StructTable { struct_ref: name } => {
+            if let Some((base, args)) = parse_generic_ref(name) {
+                if let Some((_, value)) = parse_map_ref(name)
+                    && !out.contains_key(name)
+                {
+                    let value = value_type(&value, enums);
+                    out.insert(
+                        name.clone(),
+                        Type::struct_(name.clone(), vec![Field::new("value", value.clone())]),
+                    );
+                    collect_map_refs(&value, enums, out);
+                } else if base != "BTreeMap" && base != "HashMap" {
+                    // Maps may sit inside an instantiation's arguments.
+                    for arg in args {
+                        collect_map_refs(&Type::StructRef(arg), enums, out);
+                    }
+                }
+            }
+        }
+        Type::Struct { fields, .. } => {
+            for field in fields {
+                collect_map_refs(&field.ty, enums, out);
+            }
+        }
+        Type::Vec(inner) | Type::Optional(inner) => collect_map_refs(inner, enums, out),
+        _ => {}
+    }
+}
+
+/// A placeholder struct for every map referenced in `data`, including maps
+/// nested in other maps' values and in generic instances.
+pub fn placeholders(data: &Data) -> Vec<Type> {
+    let mut maps = BTreeMap::new();
+    for ty in referencing_types(data)
+        .iter()
+        .chain(&crate::generics::instantiations(data))
+    {
+        collect_map_refs(ty, &data.enums, &mut maps);
+    }
+    maps.into_values().collect()
+}
+
+/// Which of `BTreeMap` and `HashMap` the maps in `data` use, so `model.rs`
+/// imports only those.
+pub fn kinds(data: &Data) -> BTreeSet<String> {
+    placeholders(data)
+        .iter()
+        .filter_map(|ty| match ty {
+            Type::Struct { name, .. } => parse_generic_ref(name).map(|(base, _)| base),
+            _ => None,
+        })
+        .collect()
+}
+
+/// How the key type constrains property names: any string, or the variant
+/// names of an enum (which serde uses for unit variants).
+fn property_names(key: &str, enums: &[EnumElement]) -> Option<Value> {
+    let element = find_enum_by_rust_name(enums, key)?;
+    let Type::Enum { variants, .. } = &element.inner else {
+        return None;
+    };
+    let names: Vec<String> = variants
+        .iter()
+        .map(|v| crate::rust_item::enum_variant_name(&v.name))
+        .collect();
+    Some(json!({ "enum": names }))
+}
+
+/// Replaces map placeholders with inline object schemas throughout `value`,
+/// and drops their definitions.
+pub fn inline_maps(value: &mut Value, data: &Data) {
+    let maps: BTreeMap<String, (String, String)> = placeholders(data)
+        .iter()
+        .filter_map(|ty| match ty {
+            Type::Struct { name, .. } => Some((name.to_case(Case::Pascal), parse_map_ref(name)?)),
+            _ => None,
+        })
+        .collect();
+    if maps.is_empty() {
+        return;
+    }
+
+    let mut schemas = BTreeMap::new();
+    take_placeholders(value, &maps, &data.enums, &mut schemas);
+    replace_refs(value, &schemas);
+}
+
+fn take_placeholders(
+    value: &mut Value,
+    maps: &BTreeMap<String, (String, String)>,
+    enums: &[EnumElement],
+    schemas: &mut BTreeMap<String, Value>,
+) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object.iter_mut() {
+                if (key == "schemas" || key == "$defs")
+                    && let Value::Object(defs) = child
+                {
+                    for (def, (key_type, value_type_name)) in maps {
+                        let Some(placeholder) = defs.remove(def) else {
+                            continue;
+                        };
+                        let mut values = placeholder["properties"]["value"].clone();
+                        // Optional values are dropped from `required` by the
+                        // placeholder; standalone they are nullable.
+                        if value_type_name.starts_with("Option<") {
+                            values = json!({ "anyOf": [values, { "type": "null" }] });
+                        }
+                        let mut schema = json!({ "type": "object", "additionalProperties": values });
+                        if let Some(names) = property_names(key_type, enums) {
+                            schema["propertyNames"] = names;
+                        }
+                        schemas.insert(def.clone(), schema);
+                    }
+                }
+                take_placeholders(child, maps, enums, schemas);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                take_placeholders(item, maps, enums, schemas);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn replace_refs(value: &mut Value, schemas: &BTreeMap<String, Value>) {
+    match value {
+        Value::Object(object) => {
+            let target = object
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|r| r.rsplit_once('/'))
+                .and_then(|(_, def)| schemas.get(def));
+            if let Some(schema) = target {
+                // Keep siblings such as a field description.
+                let mut inlined: Map<String, Value> = schema.as_object().cloned().unwrap_or_default();
+                for (key, sibling) in object.iter().filter(|(k, _)| *k != "$ref") {
+                    inlined.insert(key.clone(), sibling.clone());
+                }
+                *object = inlined;
+            }
+            for child in object.values_mut() {
+                replace_refs(child, schemas);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                replace_refs(item, schemas);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// How a map reads in `docs/README.md`: `Map<String, f64>`. Whether the Rust
+/// side uses a `BTreeMap` or a `HashMap` is invisible on the wire.
+pub fn display_name(name: &str) -> Option<String> {
+    let (key, value) = parse_map_ref(name)?;
+    let value = display_name(&value).unwrap_or(value);
+    Some(format!("Map<{key}, {value}>"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{EndpointSchemaElement, GenService, RustGenConfig};
+    use endpoint_libs::model::{EndpointSchema, EnumVariant};
+    use std::path::PathBuf;
+
+    fn data_returning(ty: Type) -> Data {
+        Data {
+            project_name: "test".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![GenService::new(
+                "s1".into(),
+                1,
+                vec![EndpointSchemaElement {
+                    frontend_facing: true,
+                    config: RustGenConfig::default(),
+                    schema: EndpointSchema::new("Balances", 1, vec![], vec![Field::new("balances", ty)])
+                        .with_description("Balances by asset."),
+                    ..Default::default()
+                }],
+            )],
+            enums: vec![EnumElement {
+                config: RustGenConfig {
+                    prefix_enum: true,
+                    ..Default::default()
+                },
+                inner: Type::enum_("side", vec![EnumVariant::new("Buy", 1), EnumVariant::new("Sell", 2)]),
+            }],
+            structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    #[test]
+    fn parses_keys_values_and_nested_value_types() {
+        assert_eq!(
+            parse_map_ref("HashMap<EnumSide, Vec<f64>>"),
+            Some(("EnumSide".into(), "Vec<f64>".into()))
+        );
+        assert_eq!(parse_map_ref("Paged<Position>"), None);
+
+        let data = data_returning(Type::Unit);
+        assert_eq!(
+            value_type("Option<Vec<EnumSide>>", &data.enums),
+            Type::optional(Type::vec(Type::enum_ref("side", true)))
+        );
+        assert_eq!(
+            value_type("BTreeMap<String, i64>", &data.enums),
+            Type::struct_ref("BTreeMap<String, i64>")
+        );
+    }
+
+    #[test]
+    fn maps_inline_as_additional_properties() {
+        let data = data_returning(Type::struct_ref("BTreeMap<EnumSide, BTreeMap<String, f64>>"));
+        let doc = crate::openapi::build_openapi(&data, false).unwrap();
+
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        assert!(schemas.keys().all(|k| !k.contains('<')), "{:?}", schemas.keys());
+
+        let response = &doc["paths"]["/s1/balances"]["post"]["responses"]["200"]["content"]["application/json"]["schema"]
+            ["properties"]["balances"];
+        assert_eq!(
+            *response,
+            json!({
+                "type": "object",
+                "propertyNames": { "enum": ["Buy", "Sell"] },
+                "additionalProperties": {
+                    "type": "object",
+                    "additionalProperties": { "type": "number" },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn display_name_hides_the_rust_map_kind() {
+        assert_eq!(
+            display_name("HashMap<String, BTreeMap<EnumSide, f64>>").as_deref(),
+            Some("Map<String, Map<EnumSide, f64>>")
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! MCP resources and prompts.
+//!
+//! Tools come from every exposed endpoint (see [`crate::docs::write_mcp_tools_json`]).
+//! Resources and prompts are declared on their own, as `McpResource` and
+//! `McpPrompt` definitions naming endpoints of their service, and are written
+//! twice:
+//!
+//! - `docs/{service}_mcp_resources.json` and `docs/{service}_mcp_prompts.json`,
+//!   next to the tool lists, in the shapes of `resources/list`,
+//!   `resources/templates/list` and `prompts/list`;
+//! - `MCP_RESOURCES` and `MCP_PROMPTS` in `model.rs`, for the server to serve
+//!   those methods from.
+//!
+//! A resource is read by calling its endpoint with the URI variables as
+//! parameters. A prompt's `template` is its one user message.
+
+use std::io::Write;
+use std::path::Path;
+
+use eyre::{Context, ContextCompat, Result};
+use serde_json::{Value, json};
+
+use crate::definitions::{GenService, McpResourceElement};
+use crate::docs::Data;
+
+/// [`write_mcp_catalog_json`] into `docs/`.
+pub fn gen_mcp_catalog_json(data: &Data) -> Result<()> {
+    write_mcp_catalog_json(data, &data.project_root.join("docs"))
+}
+
+/// Writes the resource and prompt files of every service into `docs_dir`. A
+/// service with none gets no file, and the file of an earlier run is removed.
+pub fn write_mcp_catalog_json(data: &Data, docs_dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(docs_dir)?;
+    for service in &data.services {
+        let resources = if service.resources.is_empty() {
+            None
+        } else {
+            Some(resources_json(service)?)
+        };
+        write_or_remove(
+            &docs_dir.join(format!("{}_mcp_resources.json", service.name)),
+            resources,
+        )?;
+        let prompts = if service.prompts.is_empty() {
+            None
+        } else {
+            Some(prompts_json(service)?)
+        };
+        write_or_remove(&docs_dir.join(format!("{}_mcp_prompts.json", service.name)), prompts)?;
+    }
+    Ok(())
+}
+
+fn write_or_remove(filename: &Path, contents: Option<Value>) -> Result<()> {
+    match contents {
+        Some(contents) => std::fs::write(filename, serde_json::to_string_pretty(&contents)?)
+            .with_context(|| format!("Failed to write {}", filename.display())),
+        None if filename.exists() => {
+            std::fs::remove_file(filename).with_context(|| format!("Failed to remove {}", filename.display()))
+        }
+        None => Ok(()),
+    }
+}
+
+/// The resources of `service`, each with its URI (or URI template), the
+/// endpoint code that reads it, and its description.
+struct Resource<'a> {
+    element: &'a McpResourceElement,
+    uri: String,
+    code: u32,
+    description: &'a str,
+}
+
+impl Resource<'_> {
+    /// A URI with variables is a template.
+    fn is_template(&self) -> bool {
+        self.uri.contains('{')
+    }
+}
+
+fn resources(service: &GenService) -> Result<Vec<Resource<'_>>> {
+    service
+        .resources
+        .iter()
+        .map(|element| {
+            let endpoint = service.endpoint(&element.endpoint).with_context(|| {
+                format!(
+                    "MCP resource {}: no endpoint {} in service {}",
+                    element.name, element.endpoint, service.name
+                )
+            })?;
+            let description = if element.description.trim().is_empty() {
+                &endpoint.schema.description
+            } else {
+                &element.description
+            };
+            Ok(Resource {
+                element,
+                uri: element.uri_template(&service.name, endpoint),
+                code: endpoint.schema.code,
+                description,
+            })
+        })
+        .collect()
+}
+
+fn resources_json(service: &GenService) -> Result<Value> {
+    let mut concrete = vec![];
+    let mut templates = vec![];
+    for resource in resources(service)? {
+        let uri_key = if resource.is_template() { "uriTemplate" } else { "uri" };
+        let mut entry = json!({
+            "name": resource.element.name,
+            uri_key: resource.uri,
+            "code": resource.code,
+            "description": resource.description,
+            "mimeType": resource.element.mime_type(),
+        });
+        if let Some(title) = &resource.element.title {
+            entry["title"] = json!(title);
+        }
+        if resource.is_template() {
+            templates.push(entry);
+        } else {
+            concrete.push(entry);
+        }
+    }
+    Ok(json!({ "resources": concrete, "resourceTemplates": templates }))
+}
+
+fn prompts_json(service: &GenService) -> Result<Value> {
+    let prompts = service
+        .prompts
+        .iter()
+        .map(|prompt| {
+            let mut entry = json!({
+                "name": prompt.name,
+                "description": prompt.description,
+                "arguments": prompt.arguments.iter().map(|argument| json!({
+                    "name": argument.name,
+                    "description": argument.description,
+                    "required": argument.required,
+                })).collect::<Vec<_>>(),
+                "tools": prompt_tools(service, &prompt.endpoints)?,
+                "messages": [{ "role": "user", "content": { "type": "text", "text": prompt.template } }],
+            });
+            if let Some(title) = &prompt.title {
+                entry["title"] = json!(title);
+            }
+            Ok(entry)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(json!({ "prompts": prompts }))
+}
+
+/// The tool names of the endpoints a prompt relies on.
+fn prompt_tools(service: &GenService, endpoints: &[String]) -> Result<Vec<String>> {
+    endpoints
+        .iter()
+        .map(|name| {
+            service
+                .endpoint(name)
+                .map(|endpoint| endpoint.tool_name())
+                .with_context(|| format!("no endpoint {name} in service {}", service.name))
+        })
+        .collect()
+}
+
+/// Emits `MCP_RESOURCES` and `MCP_PROMPTS` into `model.rs`, each with the
+/// struct it is a table of. Nothing is emitted for a table that would be empty.
+pub fn dump_mcp_catalog(data: &Data, mut writer: impl Write) -> Result<()> {
+    let mut resources_table = String::new();
+    let mut prompts_table = String::new();
+    for service in &data.services {
+        for resource in resources(service)? {
+            resources_table.push_str(&format!(
+                "    McpResource {{ name: {:?}, title: {:?}, uri_template: {:?}, description: {:?}, mime_type: {:?}, method_id: {} }},\n",
+                resource.element.name,
+                resource.element.title.as_deref(),
+                resource.uri,
+                resource.description,
+                resource.element.mime_type(),
+                resource.code,
+            ));
+        }
+        for prompt in &service.prompts {
+            let arguments = prompt
+                .arguments
+                .iter()
+                .map(|argument| {
+                    format!(
+                        "McpPromptArgument {{ name: {:?}, description: {:?}, required: {} }}",
+                        argument.name, argument.description, argument.required
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            prompts_table.push_str(&format!(
+                "    McpPrompt {{ name: {:?}, title: {:?}, description: {:?}, arguments: &[{arguments}], tools: &{:?}, template: {:?} }},\n",
+                prompt.name,
+                prompt.title.as_deref(),
+                prompt.description,
+                prompt_tools(service, &prompt.endpoints)?,
+                prompt.template,
+            ));
+        }
+    }
+
+    if !resources_table.is_empty() {
+        write!(
+            writer,
+            r#"
+/// An MCP resource, read by calling the endpoint `method_id` with the URI
+/// variables as parameters. A `uri_template` without variables is a concrete
+/// resource for `resources/list`; the others are for `resources/templates/list`.
+#[derive(Debug, Clone, Copy)]
+pub struct McpResource {{
+    pub name: &'static str,
+    pub title: Option<&'static str>,
+    pub uri_template: &'static str,
+    pub description: &'static str,
+    pub mime_type: &'static str,
+    pub method_id: u32,
+}}
+
+pub const MCP_RESOURCES: &[McpResource] = &[
+{resources_table}];
+"#
+        )?;
+    }
+    if !prompts_table.is_empty() {
+        write!(
+            writer,
+            r#"
+#[derive(Debug, Clone, Copy)]
+pub struct McpPromptArgument {{
+    pub name: &'static str,
+    pub description: &'static str,
+    pub required: bool,
+}}
+
+/// An MCP prompt. `template` is the text of its one user message, with
+/// `{{argument}}` placeholders; `tools` are the tools it has the agent call.
+#[derive(Debug, Clone, Copy)]
+pub struct McpPrompt {{
+    pub name: &'static str,
+    pub title: Option<&'static str>,
+    pub description: &'static str,
+    pub arguments: &'static [McpPromptArgument],
+    pub tools: &'static [&'static str],
+    pub template: &'static str,
+}}
+
+pub const MCP_PROMPTS: &[McpPrompt] = &[
+{prompts_table}];
+"#
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{EndpointSchemaElement, McpPromptArgument, McpPromptElement, Operational};
+    use endpoint_libs::model::{EndpointSchema, Field, Type};
+    use std::path::PathBuf;
+
+    fn read_only(name: &str, code: u32, parameters: Vec<Field>) -> EndpointSchemaElement {
+        EndpointSchemaElement {
+            schema: EndpointSchema::new(name, code, parameters, vec![]).with_description("Reads."),
+            operational: Operational {
+                safe: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn resource(name: &str, endpoint: &str) -> McpResourceElement {
+        McpResourceElement {
+            service_name: "userApi".into(),
+            service_id: 1,
+            name: name.into(),
+            endpoint: endpoint.into(),
+            uri_template: None,
+            title: None,
+            description: String::new(),
+            mime_type: None,
+        }
+    }
+
+    fn data() -> Data {
+        let mut service = GenService::new(
+            "userApi".into(),
+            1,
+            vec![
+                read_only("UserGetProfile", 10000, vec![]),
+                read_only(
+                    "UserGetOrder",
+                    10001,
+                    vec![
+                        Field::new("order_id", Type::Int64),
+                        Field::new("verbose", Type::optional(Type::Boolean)),
+                    ],
+                ),
+            ],
+        );
+        service.resources = vec![resource("profile", "UserGetProfile"), resource("order", "UserGetOrder")];
+        service.prompts = vec![McpPromptElement {
+            service_name: "userApi".into(),
+            service_id: 1,
+            name: "explain_order".into(),
+            title: Some("Explain an order".into()),
+            description: "Walks through one order.".into(),
+            arguments: vec![McpPromptArgument {
+                name: "order_id".into(),
+                description: "The order.".into(),
+                required: true,
+            }],
+            endpoints: vec!["UserGetOrder".into()],
+            template: "Fetch order {order_id} and explain its state.".into(),
+        }];
+        Data {
+            project_name: "api".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![service],
+            enums: vec![],
+            structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    #[test]
+    fn resources_split_into_concrete_ones_and_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        write_mcp_catalog_json(&data(), dir.path()).unwrap();
+
+        let read = |file: &str| -> Value {
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join(file)).unwrap()).unwrap()
+        };
+        let resources = read("userApi_mcp_resources.json");
+        assert_eq!(resources["resources"][0]["uri"], "userapi://user_get_profile");
+        assert_eq!(resources["resources"][0]["description"], "Reads.");
+        assert_eq!(
+            resources["resourceTemplates"][0]["uriTemplate"],
+            "userapi://user_get_order/{orderId}{?verbose}"
+        );
+        assert_eq!(resources["resourceTemplates"][0]["code"], 10001);
+
+        let prompts = read("userApi_mcp_prompts.json");
+        let prompt = &prompts["prompts"][0];
+        assert_eq!(prompt["title"], "Explain an order");
+        assert_eq!(prompt["tools"], json!(["user_get_order"]));
+        assert_eq!(prompt["arguments"][0]["required"], true);
+        assert_eq!(
+            prompt["messages"][0]["content"]["text"],
+            "Fetch order {order_id} and explain its state."
+        );
+
+        let mut data = data();
+        data.services[0].prompts.clear();
+        write_mcp_catalog_json(&data, dir.path()).unwrap();
+        assert!(!dir.path().join("userApi_mcp_prompts.json").exists());
+    }
+
+    #[test]
+    fn the_catalog_becomes_model_rs_tables() {
+        let mut out = Vec::new();
+        dump_mcp_catalog(&data(), &mut out).unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(code.contains("pub const MCP_RESOURCES: &[McpResource] = &["), "{code}");
+        assert!(
+            code.contains(r#"uri_template: "userapi://user_get_order/{orderId}{?verbose}""#),
+            "{code}"
+        );
+        assert!(code.contains("method_id: 10001"), "{code}");
+        assert!(code.contains("pub const MCP_PROMPTS: &[McpPrompt] = &["), "{code}");
+        assert!(code.contains(r#"tools: &["user_get_order"]"#), "{code}");
+
+        let mut out = Vec::new();
+        dump_mcp_catalog(
+            &Data {
+                services: vec![],
+                ..data()
+            },
+            &mut out,
+        )
+        .unwrap();
+        assert!(out.is_empty(), "no resources or prompts, no tables");
+    }
+}
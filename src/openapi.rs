@@ -12,6 +12,10 @@
 //! `docs/openapi-README.md`, because an undocumented synthetic path map is worse
 //! than no document at all: it looks usable.
 //!
+//! The one exception is a `--gateway` deployment, where
+//! [`crate::gateway`] serves these paths for real. [`build_gateway_openapi`]
+//! derives the document for that from this one, with the warning removed.
+//!
 //! Conventions (PLAN-2.1 §3.1), which mirror the RON namespace `(service_name,
 //! service_id)`:
 //!
@@ -21,6 +25,8 @@
 //!   tags:        [{serviceName}]
 //! ```
 
+use std::collections::BTreeMap;
+
 use convert_case::{Case, Casing};
 use endpoint_libs::model::{EndpointSchema, SchemaComponents, TypeRegistry, apply_meta};
 use eyre::{Context, Result};
@@ -28,13 +34,16 @@ use serde_json::{Value, json};
 
 use crate::definitions::{ErrorCodeSchema, GenService};
 use crate::docs::Data;
+use crate::gateway::GatewayConfig;
 use crate::spec_common::{
-    ERROR_ENVELOPE, build_registry, collect_components, document_schemas, document_title, error_code_list,
-    visible_services,
+    ERROR_ENVELOPE, build_registry, collect_components, document_schemas, error_code_list, operational_extensions,
+    resolve_stand_ins, visible_services,
 };
+use crate::spec_config::SESSION_TOKEN_SCHEME;
+use crate::spec_output::{SpecOutput, write_document};
 
-/// The security scheme name used for every operation.
-const SESSION_TOKEN_SCHEME: &str = "sessionToken";
+/// The security scheme of the gateway document.
+const BEARER_SCHEME: &str = "bearerAuth";
 
 /// Builds the document. Separated from writing so tests can assert on the value
 /// without touching a filesystem.
@@ -46,8 +55,13 @@ pub fn build_openapi(data: &Data, public_only: bool) -> Result<Value> {
 
     let mut paths = serde_json::Map::new();
     let mut tags = Vec::new();
+    // The session token, or any scheme `spec.ron` adds.
+    let security: Value = std::iter::once(SESSION_TOKEN_SCHEME)
+        .chain(data.spec.security_schemes.keys().map(String::as_str))
+        .map(|name| json!({ name: [] }))
+        .collect();
 
-    for service in &services {
+    for service in services.iter().filter(|service| !service.endpoints.is_empty()) {
         tags.push(json!({
             "name": service.name,
             "description": format!("Endpoints of the `{}` service (service id {}).", service.name, service.id),
@@ -56,7 +70,7 @@ pub fn build_openapi(data: &Data, public_only: bool) -> Result<Value> {
         for element in &service.endpoints {
             let schema = &element.schema;
             let path = operation_path(&service.name, &schema.name);
-            let operation = build_operation(
+            let mut operation = build_operation(
                 service,
                 schema,
                 element.frontend_facing,
@@ -65,18 +79,20 @@ pub fn build_openapi(data: &Data, public_only: bool) -> Result<Value> {
                 &data.error_codes,
             )
             .with_context(|| format!("endpoint {} ({})", schema.name, schema.code))?;
+            operation["security"] = security.clone();
+            // `meta` is the author's last word, so it wins over these.
+            let object = operation.as_object_mut().expect("operations are objects");
+            for (key, value) in operational_extensions(&element.operational) {
+                object.entry(key).or_insert(value);
+            }
 
             paths.insert(path, json!({ "post": operation }));
         }
     }
 
-    Ok(json!({
+    let mut document = json!({
         "openapi": "3.1.0",
-        "info": {
-            "title": document_title(data),
-            "version": "1.0.0",
-            "description": INFO_DESCRIPTION,
-        },
+        "info": data.spec.info(&data.project_name, INFO_DESCRIPTION),
         // A relative "/" rather than a plausible https:// URL. OpenAPI defaults
         // to this when `servers` is omitted, but linters flag an absent
         // `servers` as an error, and a real-looking host would invite exactly
@@ -99,22 +115,133 @@ pub fn build_openapi(data: &Data, public_only: bool) -> Result<Value> {
                 }
             },
         },
-    }))
+    });
+    for (name, scheme) in &data.spec.security_schemes {
+        document["components"]["securitySchemes"][name] = scheme.to_openapi();
+    }
+    if let Some(external_docs) = data.spec.external_docs() {
+        document["externalDocs"] = external_docs;
+    }
+    resolve_stand_ins(&mut document, data);
+    Ok(document)
 }
 
-/// Writes `docs/openapi.json`.
-pub fn gen_openapi(data: &Data, public_only: bool) -> Result<()> {
-    let docs_dir = data.project_root.join("docs");
-    std::fs::create_dir_all(&docs_dir)?;
-
+/// Writes `docs/openapi.json` or `.yaml`.
+pub fn gen_openapi(data: &Data, public_only: bool, output: SpecOutput) -> Result<()> {
     let document = build_openapi(data, public_only)?;
-    let filename = docs_dir.join("openapi.json");
-    let file = std::fs::File::create(&filename)
-        .with_context(|| format!("Failed to create OpenAPI file: {}", filename.display()))?;
-    serde_json::to_writer_pretty(file, &document)?;
-    Ok(())
+    write_document(&data.project_root.join("docs"), "openapi", document, output)
+}
+
+/// The document for a `--gateway` deployment, which serves the paths above for
+/// real. Built from [`build_openapi`], so paths, operations and components are
+/// the same. The servers are real, the security scheme is the bearer token the
+/// gateway reads, and each HTTP status an endpoint's declared errors map to has
+/// its own response.
+pub fn build_gateway_openapi(data: &Data, public_only: bool, config: &GatewayConfig) -> Result<Value> {
+    let mut document = build_openapi(data, public_only)?;
+    document["info"]["description"] = json!(GATEWAY_INFO_DESCRIPTION);
+    document["servers"] = if config.servers.is_empty() {
+        json!([{ "url": "/", "description": "The gateway's own origin." }])
+    } else {
+        config
+            .servers
+            .iter()
+            .map(|server| {
+                let mut entry = json!({ "url": server.url });
+                if !server.description.is_empty() {
+                    entry["description"] = json!(server.description);
+                }
+                entry
+            })
+            .collect()
+    };
+    document["components"]["securitySchemes"] = json!({
+        BEARER_SCHEME: {
+            "type": "http",
+            "scheme": "bearer",
+            "description": "The session token. The gateway passes it to the backend as the WebSocket subprotocol.",
+        }
+    });
+
+    let catalog: BTreeMap<&str, &ErrorCodeSchema> = data.error_codes.iter().map(|c| (c.name.as_str(), c)).collect();
+    for service in visible_services(data, public_only) {
+        for element in &service.endpoints {
+            let schema = &element.schema;
+            let operation = &mut document["paths"][operation_path(&service.name, &schema.name)]["post"];
+            operation["security"] = json!([{ BEARER_SCHEME: [] }]);
+            if let Some(Value::String(description)) = operation.get_mut("description") {
+                *description = description.replace(STREAM_NOTE, GATEWAY_STREAM_NOTE);
+            }
+
+            let responses = &mut operation["responses"];
+            let listed = responses["default"]
+                .as_object_mut()
+                .and_then(|default| default.remove("x-error-codes"));
+            let mut by_status: BTreeMap<u16, Vec<Value>> = BTreeMap::new();
+            for entry in listed.as_ref().and_then(Value::as_array).into_iter().flatten() {
+                if let Some(code) = entry["code"].as_str().and_then(|name| catalog.get(name)) {
+                    by_status.entry(config.status(code)).or_default().push(entry.clone());
+                }
+            }
+            // What the gateway itself answers with.
+            by_status.entry(400).or_default();
+            by_status.entry(502).or_default();
+
+            for (status, listed) in by_status {
+                let mut description: Vec<String> = listed
+                    .iter()
+                    .filter_map(|entry| entry["name"].as_str())
+                    .map(str::to_string)
+                    .collect();
+                match status {
+                    400 => description.push("a request body that is not JSON".into()),
+                    502 => description.push("a backend that cannot be reached".into()),
+                    _ => {}
+                }
+                let mut response = json!({
+                    "description": format!("Error envelope for {}.", description.join(", ")),
+                    "content": {
+                        "application/json": {
+                            "schema": { "$ref": format!("#/components/schemas/{ERROR_ENVELOPE}") }
+                        }
+                    },
+                });
+                if !listed.is_empty() {
+                    response["x-error-codes"] = Value::Array(listed);
+                }
+                responses[status.to_string()] = response;
+            }
+            responses["default"]["description"] =
+                json!("Error envelope for any other error, with the status the gateway's table gives its code.");
+        }
+    }
+    Ok(document)
+}
+
+/// Writes `docs/openapi.gateway.json` or `.yaml`.
+pub fn gen_gateway_openapi(data: &Data, public_only: bool, config: &GatewayConfig, output: SpecOutput) -> Result<()> {
+    let document = build_gateway_openapi(data, public_only, config)?;
+    write_document(&data.project_root.join("docs"), "openapi.gateway", document, output)
 }
 
+const GATEWAY_INFO_DESCRIPTION: &str = "\
+Served by the HTTP gateway generated with `endpoint-gen --gateway`.
+
+Each operation is a real POST. The gateway opens a WebSocket to the backend
+with the bearer token as its subprotocol, and sends the request body as the
+params of one {method, seq, params} frame, where `method` is the operation's
+x-endpoint-code. The reply's params are the response body. Error replies are
+the error envelope, with the HTTP status the gateway's table gives their code.
+
+Streaming endpoints answer with their initial response only; subscribe over
+the WebSocket for the pushes (see docs/asyncapi.json).
+
+Vendor extensions: x-endpoint-code (the wire method code), x-roles (RBAC roles
+required), x-frontend-facing, x-stream-response, x-error-codes.";
+
+const GATEWAY_STREAM_NOTE: &str = "This endpoint streams over the WebSocket. Through the gateway, only its \
+                                   initial response is returned; the pushes that follow are dropped.";
+
 /// The synthetic-path warning. Non-negotiable — see the module docs.
 const INFO_DESCRIPTION: &str = "\
 PROJECTION FOR TOOLING — NOT A SERVABLE HTTP API.
@@ -133,13 +260,17 @@ AsyncAPI document (docs/asyncapi.json).
 Vendor extensions: x-endpoint-code (the wire method code), x-roles (RBAC roles
 required), x-frontend-facing, x-stream-response, x-error-codes.";
 
+const STREAM_NOTE: &str = "This endpoint streams: the server may send multiple response \
+                           frames for one request. That has no HTTP equivalent and is not \
+                           represented in the responses below — see the AsyncAPI document.";
+
 /// `POST /{serviceName}/{endpoint_snake_name}`.
 ///
 /// Endpoint names are only conventionally unique across services — the RON
 /// namespace is `(service_name, service_id)` — so the path must carry the
 /// service. A flat `/rpc/{Name}` scheme collides the moment two services reuse
 /// a name, and `/rpc/` carries no information.
-fn operation_path(service_name: &str, endpoint_name: &str) -> String {
+pub fn operation_path(service_name: &str, endpoint_name: &str) -> String {
     format!("/{}/{}", service_name, endpoint_name.to_case(Case::Snake))
 }
 
@@ -179,21 +310,13 @@ fn build_operation(
 
     if schema.stream_response.is_some() {
         operation.insert("x-stream-response".into(), json!(true));
-        let note = "This endpoint streams: the server may send multiple response \
-                    frames for one request. That has no HTTP equivalent and is not \
-                    represented in the responses below — see the AsyncAPI document.";
         let described = match operation.get("description") {
-            Some(Value::String(existing)) => format!("{existing}\n\n{note}"),
-            _ => note.to_string(),
+            Some(Value::String(existing)) => format!("{existing}\n\n{STREAM_NOTE}"),
+            _ => STREAM_NOTE.to_string(),
         };
         operation.insert("description".into(), json!(described));
     }
 
-    operation.insert(
-        "security".into(),
-        json!([{ SESSION_TOKEN_SCHEME: Vec::<String>::new() }]),
-    );
-
     if !schema.parameters.is_empty() {
         let request = components.request_schema(schema, registry)?;
         operation.insert(
@@ -263,17 +386,21 @@ mod tests {
             frontend_facing,
             config: RustGenConfig::default(),
             schema,
+            ..Default::default()
         }
     }
 
     fn data_with(services: Vec<GenService>) -> Data {
         Data {
             project_name: "api.example.com".into(),
+            spec: Default::default(),
             project_root: PathBuf::from("/tmp/api.example.com"),
             output_dir: PathBuf::from("/tmp/api.example.com/generated"),
             services,
             enums: vec![],
             structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
             error_codes: vec![ErrorCodeSchema::new("BadRequest", 400, "The request was malformed.")],
         }
     }
@@ -350,6 +477,37 @@ mod tests {
         assert_eq!(listed["description"], "The request was malformed.");
     }
 
+    #[test]
+    fn the_gateway_document_gives_declared_errors_their_statuses() {
+        let mut data = sample_data();
+        data.error_codes = vec![
+            ErrorCodeSchema::new("BadRequest", 100400, "Bad request"),
+            ErrorCodeSchema::new("TooManyLoginAttempts", 200001, "Slow down."),
+        ];
+        data.services[0].endpoints[0]
+            .schema
+            .errors
+            .push(EndpointErrorSchema::new(
+                "LockedOut",
+                EndpointErrorCodeRef::new("TooManyLoginAttempts"),
+            ));
+        let config: GatewayConfig =
+            ron::from_str(r#"(servers: [(url: "https://gw.example.com")], statuses: {"TooManyLoginAttempts": 429})"#)
+                .unwrap();
+
+        let doc = build_gateway_openapi(&data, false, &config).unwrap();
+        assert_eq!(doc["servers"], json!([{ "url": "https://gw.example.com" }]));
+        assert!(!doc["info"]["description"].as_str().unwrap().contains("NOT A SERVABLE"));
+
+        let op = &doc["paths"]["/userApi/user_login"]["post"];
+        assert_eq!(op["security"], json!([{ "bearerAuth": [] }]));
+        let responses = &op["responses"];
+        assert_eq!(responses["400"]["x-error-codes"][0]["name"], "PasswordTooShort");
+        assert_eq!(responses["429"]["x-error-codes"][0]["name"], "LockedOut");
+        assert!(responses["502"].is_object());
+        assert!(responses["default"].get("x-error-codes").is_none());
+    }
+
     #[test]
     fn public_only_drops_exactly_the_non_frontend_facing_operations() {
         let full = build_openapi(&sample_data(), false).unwrap();
@@ -388,6 +546,7 @@ mod tests {
         )]);
         data.structs = vec![crate::definitions::StructElement {
             config: RustGenConfig::default(),
+            type_params: vec![],
             inner: registry_struct,
         }];
 
@@ -409,6 +568,186 @@ mod tests {
         assert!(!serialised.contains("$defs"), "no $defs may survive into the document");
     }
 
+    #[test]
+    fn generic_instances_become_monomorphised_components() {
+        let mut data = data_with(vec![GenService::new(
+            "positionApi".into(),
+            3,
+            vec![element(
+                EndpointSchema::new(
+                    "ListPositions",
+                    30000,
+                    vec![],
+                    vec![Field::new("page", Type::struct_ref("Paged<Position>"))],
+                )
+                .with_description("Lists positions."),
+                true,
+            )],
+        )]);
+        data.structs = vec![
+            crate::definitions::StructElement {
+                config: RustGenConfig::default(),
+                type_params: vec!["T".into()],
+                inner: Type::struct_(
+                    "Paged",
+                    vec![
+                        Field::new("items", Type::vec(Type::struct_ref("T"))),
+                        Field::new("cursor", Type::optional(Type::String)),
+                    ],
+                ),
+            },
+            crate::definitions::StructElement {
+                config: RustGenConfig::default(),
+                type_params: vec![],
+                inner: Type::struct_("Position", vec![Field::new("symbol", Type::String)]),
+            },
+        ];
+
+        let doc = build_openapi(&data, false).unwrap();
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        assert!(schemas.contains_key("Paged_Position"), "{:?}", schemas.keys());
+        assert!(schemas.contains_key("Position"));
+        assert!(schemas.keys().all(|k| !k.contains('<')));
+
+        let mut refs = vec![];
+        collect_refs(&doc, &mut refs);
+        assert!(refs.contains(&"#/components/schemas/Paged_Position".to_string()));
+        for target in &refs {
+            let name = target.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas.contains_key(name), "dangling ref: {target}");
+        }
+    }
+
+    #[test]
+    fn tagged_unions_become_one_of_with_a_discriminator() {
+        let mut data = data_with(vec![GenService::new(
+            "orderApi".into(),
+            4,
+            vec![element(
+                EndpointSchema::new(
+                    "GetOrderEvent",
+                    40000,
+                    vec![],
+                    vec![Field::new("event", Type::struct_ref("OrderEvent"))],
+                )
+                .with_description("Gets the latest order event."),
+                true,
+            )],
+        )]);
+        data.unions = vec![crate::definitions::TaggedUnionElement {
+            config: RustGenConfig::default(),
+            name: "OrderEvent".into(),
+            tag: "kind".into(),
+            description: String::new(),
+            variants: vec![
+                crate::definitions::TaggedVariant {
+                    name: "Filled".into(),
+                    description: "The order was filled.".into(),
+                    fields: vec![Field::new("qty", Type::Float64)],
+                },
+                crate::definitions::TaggedVariant {
+                    name: "Cancelled".into(),
+                    description: "The order was cancelled.".into(),
+                    fields: vec![],
+                },
+            ],
+        }];
+
+        let doc = build_openapi(&data, false).unwrap();
+        let schemas = &doc["components"]["schemas"];
+        let union = &schemas["OrderEvent"];
+        assert_eq!(union["discriminator"]["propertyName"], "kind");
+        assert_eq!(
+            union["discriminator"]["mapping"]["Filled"],
+            "#/components/schemas/OrderEventFilled"
+        );
+        assert_eq!(union["oneOf"].as_array().unwrap().len(), 2);
+        assert!(union.get("properties").is_none(), "placeholder must be replaced");
+
+        let filled = &schemas["OrderEventFilled"];
+        assert_eq!(filled["properties"]["kind"]["const"], "Filled");
+        assert_eq!(filled["required"], json!(["kind", "qty"]));
+        assert_eq!(schemas["OrderEventCancelled"]["required"], json!(["kind"]));
+
+        let mut refs = vec![];
+        collect_refs(&doc, &mut refs);
+        for target in &refs {
+            let name = target.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas.get(name).is_some(), "dangling ref: {target}");
+        }
+    }
+
+    #[test]
+    fn newtypes_are_named_components_of_the_underlying_type() {
+        let mut data = data_with(vec![GenService::new(
+            "userApi".into(),
+            1,
+            vec![element(
+                EndpointSchema::new(
+                    "GetUser",
+                    10000,
+                    vec![Field::new("user_id", Type::struct_ref("UserId"))],
+                    vec![Field::new("referrer", Type::optional(Type::struct_ref("UserId")))],
+                )
+                .with_description("Gets a user."),
+                true,
+            )],
+        )]);
+        data.newtypes = vec![crate::definitions::NewtypeElement {
+            config: RustGenConfig::default(),
+            name: "UserId".into(),
+            ty: Type::Int64,
+            description: "Identifies a user.".into(),
+        }];
+
+        let doc = build_openapi(&data, false).unwrap();
+        assert_eq!(
+            doc["components"]["schemas"]["UserId"],
+            json!({ "type": "integer", "title": "UserId", "description": "Identifies a user." })
+        );
+        let request =
+            &doc["paths"]["/userApi/get_user"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(request["properties"]["userId"]["$ref"], "#/components/schemas/UserId");
+    }
+
+    #[test]
+    fn operational_metadata_becomes_extensions_in_both_documents() {
+        let mut data = sample_data();
+        let login = &mut data.services[0].endpoints[0];
+        login.operational = ron::from_str(
+            r#"(
+                rate_limits: {"UserRole::Guest": (requests: 5, per_seconds: 60)},
+                timeout_ms: Some(2000),
+                safe: true,
+                cost: Some(3),
+            )"#,
+        )
+        .unwrap();
+        login.schema.meta.insert("x-cost", json!(7));
+
+        let doc = build_openapi(&data, false).unwrap();
+        let op = &doc["paths"]["/userApi/user_login"]["post"];
+        assert_eq!(
+            op["x-rate-limit"],
+            json!([{ "role": "UserRole::Guest", "requests": 5, "perSeconds": 60 }])
+        );
+        assert_eq!(op["x-timeout-ms"], 2000);
+        assert_eq!(op["x-safe"], true);
+        assert_eq!(op["x-idempotent"], true, "safe implies idempotent");
+        assert_eq!(op["x-cost"], 7, "meta wins");
+        assert!(op.get("x-max-payload-bytes").is_none());
+        assert!(
+            doc["paths"]["/userApi/admin_purge"]["post"]
+                .get("x-idempotent")
+                .is_none()
+        );
+
+        let asyncapi = crate::asyncapi::build_asyncapi(&data, false).unwrap();
+        let request = &asyncapi["components"]["messages"]["UserLoginRequest"];
+        assert_eq!(request["x-timeout-ms"], 2000);
+        assert_eq!(request["x-rate-limit"], op["x-rate-limit"]);
+    }
+
     #[test]
     fn servers_is_present_but_not_a_real_host() {
         // Linters error on absent `servers`; a plausible https:// URL would
@@ -438,4 +777,34 @@ mod tests {
         assert_eq!(op["security"], json!([{ "sessionToken": [] }]));
         assert!(doc["components"]["securitySchemes"]["sessionToken"].is_object());
     }
+
+    #[test]
+    fn spec_config_sets_the_info_block_and_adds_security_alternatives() {
+        let mut data = sample_data();
+        data.spec = ron::from_str(
+            r#"(
+                title: Some("Trading API"),
+                version: Some("2.3.0"),
+                license: Some((name: "MIT", identifier: Some("MIT"))),
+                external_docs: Some((url: "https://docs.example.com")),
+                servers: {"production": (host: "api.example.com", protocol: Wss)},
+                security_schemes: {"adminKey": ApiKey(name: "X-Admin-Key", location: Header)},
+            )"#,
+        )
+        .unwrap();
+        let doc = build_openapi(&data, false).unwrap();
+
+        assert_eq!(doc["info"]["title"], "Trading API");
+        assert_eq!(doc["info"]["version"], "2.3.0");
+        assert_eq!(doc["info"]["license"], json!({ "name": "MIT", "identifier": "MIT" }));
+        assert_eq!(doc["externalDocs"]["url"], "https://docs.example.com");
+        // AsyncAPI servers only: this document stays unservable.
+        assert_eq!(doc["servers"][0]["url"], "/");
+        assert_eq!(
+            doc["components"]["securitySchemes"]["adminKey"],
+            json!({ "type": "apiKey", "in": "header", "name": "X-Admin-Key" })
+        );
+        let op = &doc["paths"]["/userApi/user_login"]["post"];
+        assert_eq!(op["security"], json!([{ "sessionToken": [] }, { "adminKey": [] }]));
+    }
 }
@@ -0,0 +1,297 @@
+//! OpenRPC 1.3 emission.
+//!
+//! The wire protocol is method-code based — `{method, seq, params}` frames
+//! carrying a by-name params object — which OpenRPC describes far more directly
+//! than the synthesized paths of [`crate::openapi`]. Each endpoint is a method
+//! named after it, with its code in `x-endpoint-code`. One parameter per
+//! `parameters` field and one `result` over `returns`.
+//!
+//! This is still not JSON-RPC on the wire: the method is a number, and the
+//! envelope is not JSON-RPC 2.0's. The AsyncAPI document remains the authority
+//! on framing. Components come from [`document_schemas`], so
+//! `components.schemas` is identical to the other two documents'.
+
+use std::collections::BTreeMap;
+
+use convert_case::{Case, Casing};
+use endpoint_libs::model::{EndpointSchema, SchemaComponents, TypeRegistry, apply_meta};
+use eyre::{Context, Result, bail};
+use serde_json::{Value, json};
+
+use crate::definitions::{ErrorCodeSchema, GenService};
+use crate::docs::Data;
+use crate::spec_common::{
+    build_registry, collect_components, document_schemas, error_code_list, resolve_stand_ins, visible_services,
+};
+use crate::spec_output::{SpecOutput, write_document};
+
+/// Builds the document. Separated from writing so tests can assert on the value.
+pub fn build_openrpc(data: &Data, public_only: bool) -> Result<Value> {
+    let registry = build_registry(data);
+    let services = visible_services(data, public_only);
+    let components = collect_components(&services, &registry)?;
+
+    // OpenRPC method names must be unique; endpoint names only conventionally are.
+    let mut seen: BTreeMap<&str, &str> = BTreeMap::new();
+    let mut duplicates = vec![];
+    for service in &services {
+        for element in &service.endpoints {
+            if let Some(other) = seen.insert(&element.schema.name, &service.name) {
+                duplicates.push(format!(
+                    "endpoint '{}' is declared in services '{other}' and '{}'",
+                    element.schema.name, service.name
+                ));
+            }
+        }
+    }
+    if !duplicates.is_empty() {
+        bail!("OpenRPC method names must be unique:\n  {}", duplicates.join("\n  "));
+    }
+
+    let mut methods = vec![];
+    for service in &services {
+        for element in &service.endpoints {
+            let schema = &element.schema;
+            let method = build_method(
+                service,
+                schema,
+                element.frontend_facing,
+                &components,
+                &registry,
+                &data.error_codes,
+            )
+            .with_context(|| format!("endpoint {} ({})", schema.name, schema.code))?;
+            methods.push(method);
+        }
+    }
+
+    let mut document = json!({
+        "openrpc": "1.3.2",
+        "info": data.spec.info_with_license_url(&data.project_name, INFO_DESCRIPTION),
+        "methods": methods,
+        "components": {
+            "schemas": document_schemas(&components),
+        },
+    });
+    if let Some(external_docs) = data.spec.external_docs() {
+        document["externalDocs"] = external_docs;
+    }
+    resolve_stand_ins(&mut document, data);
+    Ok(document)
+}
+
+/// Writes `docs/openrpc.json` or `.yaml`.
+pub fn gen_openrpc(data: &Data, public_only: bool, output: SpecOutput) -> Result<()> {
+    let document = build_openrpc(data, public_only)?;
+    write_document(&data.project_root.join("docs"), "openrpc", document, output)
+}
+
+const INFO_DESCRIPTION: &str = "\
+Generated from declarative RON endpoint definitions (endpoint-libs /
+endpoint-gen). Methods are sent over a persistent WebSocket as
+{method, seq, params} frames, where `method` is the numeric x-endpoint-code of
+the method below, not its name, and `params` is an object. The envelope is not
+JSON-RPC 2.0; see the AsyncAPI document for the framing.
+
+Vendor extensions: x-endpoint-code (the wire method code), x-service,
+x-roles (RBAC roles required), x-frontend-facing, x-stream-response.";
+
+fn build_method(
+    service: &GenService,
+    schema: &EndpointSchema,
+    frontend_facing: bool,
+    components: &SchemaComponents,
+    registry: &TypeRegistry,
+    error_codes: &[ErrorCodeSchema],
+) -> Result<Value> {
+    let mut method = serde_json::Map::new();
+    method.insert("name".into(), json!(schema.name));
+    method.insert("tags".into(), json!([{ "name": service.name }]));
+    if !schema.description.is_empty() {
+        let summary = schema.description.lines().next().unwrap_or_default().trim();
+        if !summary.is_empty() {
+            method.insert("summary".into(), json!(summary));
+        }
+        method.insert("description".into(), json!(schema.description));
+    }
+    method.insert("paramStructure".into(), json!("by-name"));
+    method.insert("params".into(), json!(params(schema, components, registry)?));
+    method.insert(
+        "result".into(),
+        json!({
+            "name": format!("{}Response", schema.name),
+            "schema": components.response_schema(schema, registry)?,
+        }),
+    );
+    if let Some(errors) = errors(schema, error_codes) {
+        method.insert("errors".into(), errors);
+    }
+
+    method.insert("x-endpoint-code".into(), json!(schema.code));
+    method.insert("x-service".into(), json!(service.name));
+    method.insert("x-frontend-facing".into(), json!(frontend_facing));
+    if !schema.roles.is_empty() {
+        method.insert("x-roles".into(), json!(schema.roles));
+    }
+    if schema.stream_response.is_some() {
+        method.insert("x-stream-response".into(), json!(true));
+    }
+
+    let mut method = Value::Object(method);
+    apply_meta(&mut method, &schema.meta, &format!("endpoint {}", schema.name))?;
+    Ok(method)
+}
+
+/// One content descriptor per parameter, in declaration order, cut from the
+/// same object schema the other documents use for the request.
+fn params(schema: &EndpointSchema, components: &SchemaComponents, registry: &TypeRegistry) -> Result<Vec<Value>> {
+    let request = components.request_schema(schema, registry)?;
+    let required: Vec<&Value> = request["required"].as_array().into_iter().flatten().collect();
+    let Some(Value::Object(properties)) = request.get("properties") else {
+        return Ok(vec![]);
+    };
+    Ok(schema
+        .parameters
+        .iter()
+        .filter_map(|field| {
+            let key = field.name.to_case(Case::Camel);
+            let mut param = serde_json::Map::new();
+            param.insert("name".into(), json!(key));
+            if !field.description.is_empty() {
+                param.insert("description".into(), json!(field.description));
+            }
+            param.insert("required".into(), json!(required.contains(&&json!(key))));
+            param.insert("schema".into(), properties.get(&key)?.clone());
+            Some(Value::Object(param))
+        })
+        .collect())
+}
+
+/// OpenRPC error objects for the catalog codes an endpoint may return. Codes
+/// missing from the catalog have no number to give, and are left out.
+fn errors(schema: &EndpointSchema, error_codes: &[ErrorCodeSchema]) -> Option<Value> {
+    let listed = error_code_list(schema, error_codes)?;
+    let errors: Vec<Value> = listed
+        .as_array()?
+        .iter()
+        .filter(|entry| entry.get("value").is_some())
+        .map(|entry| {
+            let message = entry
+                .get("message")
+                .or_else(|| entry.get("description"))
+                .unwrap_or(&entry["name"]);
+            json!({
+                "code": entry["value"],
+                "message": message,
+                "data": { "name": entry["name"], "errorCode": entry["code"] },
+            })
+        })
+        .collect();
+    (!errors.is_empty()).then_some(Value::Array(errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{EndpointSchemaElement, RustGenConfig};
+    use endpoint_libs::model::{EndpointErrorCodeRef, EndpointErrorSchema, Field, Type, collect_refs};
+    use std::path::PathBuf;
+
+    fn element(schema: EndpointSchema) -> EndpointSchemaElement {
+        EndpointSchemaElement {
+            frontend_facing: true,
+            config: RustGenConfig::default(),
+            schema,
+            ..Default::default()
+        }
+    }
+
+    fn sample_data() -> Data {
+        let login = EndpointSchema::new(
+            "UserLogin",
+            10000,
+            vec![
+                Field::new("user_name", Type::String),
+                Field::new("cursor", Type::optional(Type::String)),
+            ],
+            vec![Field::new("access_token", Type::String)],
+        )
+        .with_description("Logs a user in.\nReturns a session token.")
+        .with_errors(vec![
+            EndpointErrorSchema::new("PasswordTooShort", EndpointErrorCodeRef::new("BadRequest"))
+                .with_message("Password too short"),
+        ]);
+
+        Data {
+            project_name: "api.example.com".into(),
+            spec: Default::default(),
+            project_root: PathBuf::from("/tmp/api.example.com"),
+            output_dir: PathBuf::from("/tmp/api.example.com/generated"),
+            services: vec![GenService::new("userApi".into(), 1, vec![element(login)])],
+            enums: vec![],
+            structs: vec![],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![ErrorCodeSchema::new("BadRequest", 400, "The request was malformed.")],
+        }
+    }
+
+    #[test]
+    fn methods_are_endpoints_with_by_name_params_and_a_result() {
+        let doc = build_openrpc(&sample_data(), false).unwrap();
+        assert_eq!(doc["openrpc"], "1.3.2");
+        let method = &doc["methods"][0];
+
+        assert_eq!(method["name"], "UserLogin");
+        assert_eq!(method["x-endpoint-code"], 10000);
+        assert_eq!(method["summary"], "Logs a user in.");
+        assert_eq!(method["paramStructure"], "by-name");
+        assert_eq!(method["params"][0]["name"], "userName");
+        assert_eq!(method["params"][0]["required"], true);
+        assert_eq!(method["params"][1]["name"], "cursor");
+        assert_eq!(method["params"][1]["required"], false);
+        assert_eq!(method["result"]["name"], "UserLoginResponse");
+        assert_eq!(method["result"]["schema"]["required"], json!(["accessToken"]));
+    }
+
+    #[test]
+    fn errors_are_resolved_against_the_catalog() {
+        let doc = build_openrpc(&sample_data(), false).unwrap();
+        assert_eq!(
+            doc["methods"][0]["errors"],
+            json!([{
+                "code": 400,
+                "message": "Password too short",
+                "data": { "name": "PasswordTooShort", "errorCode": "BadRequest" },
+            }])
+        );
+    }
+
+    #[test]
+    fn shared_components_match_the_other_documents() {
+        let data = sample_data();
+        let openrpc = build_openrpc(&data, false).unwrap();
+        let openapi = crate::openapi::build_openapi(&data, false).unwrap();
+        assert_eq!(openrpc["components"]["schemas"], openapi["components"]["schemas"]);
+
+        let mut refs = vec![];
+        collect_refs(&openrpc, &mut refs);
+        for target in refs {
+            let name = target.strip_prefix("#/components/schemas/").unwrap();
+            assert!(openrpc["components"]["schemas"].get(name).is_some(), "{target}");
+        }
+    }
+
+    #[test]
+    fn endpoint_names_shared_across_services_are_rejected() {
+        let mut data = sample_data();
+        let again = data.services[0].clone();
+        data.services
+            .push(GenService::new("adminApi".into(), 2, again.endpoints));
+        let err = build_openrpc(&data, false).unwrap_err().to_string();
+        assert!(
+            err.contains("endpoint 'UserLogin' is declared in services 'userApi' and 'adminApi'"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,659 @@
+//! Protocol Buffers (proto3) emission.
+//!
+//! Each [`GenService`](crate::definitions::GenService) becomes a `service`, each
+//! endpoint an rpc taking `{Endpoint}Request` and returning `{Endpoint}Response`.
+//! Endpoints with a `stream_response` become server-streaming rpcs returning a
+//! stream of the stream item message; on the WebSocket the first frame is still
+//! `{Endpoint}Response`, which has no place in a gRPC stream.
+//!
+//! Shared structs become messages, shared enums become enums, tagged unions
+//! become a message with a `oneof` over one message per variant, and newtypes
+//! are replaced by their underlying type. Each generic instantiation becomes a
+//! message of its own, named as in the specification documents
+//! (`Paged_Order`). Field names are snake case, so the
+//! proto3 JSON mapping produces the camelCase names used on the wire.
+//!
+//! Field numbers come from `docs/proto/fields.lock.json`, so they survive
+//! reordering and regeneration. A field missing from the lockfile gets the next
+//! number after every number its message has ever used; a field removed from
+//! the RON stays in the lockfile and is `reserved` in the message, so its
+//! number is never reused.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use convert_case::{Boundary, Case, Casing};
+use endpoint_libs::model::{EnumVariant, Field, Type};
+use eyre::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::docs::Data;
+use crate::generics::parse_generic_ref;
+use crate::maps::{parse_map_ref, value_type};
+use crate::rust::ToRust;
+use crate::rust_item::{enum_variant_name, struct_name};
+use crate::spec_common::monomorphised_name;
+
+/// Mapping choices that have no single right answer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProtoOptions {
+    /// Map `Object` to `google.protobuf.Struct` instead of failing.
+    pub object_as_struct: bool,
+}
+
+/// The field numbers ever assigned, by message and then field name. The format
+/// of `docs/proto/fields.lock.json`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldLock {
+    pub messages: BTreeMap<String, BTreeMap<String, u32>>,
+}
+
+impl FieldLock {
+    /// The number of `field` in `message`, assigning the next free one if it
+    /// has none yet.
+    fn number(&mut self, message: &str, field: &str) -> u32 {
+        let fields = self.messages.entry(message.to_string()).or_default();
+        if let Some(number) = fields.get(field) {
+            return *number;
+        }
+        let next = fields.values().max().map_or(1, |max| max + 1);
+        // 19000-19999 are reserved by protobuf itself.
+        let next = if (19000..20000).contains(&next) { 20000 } else { next };
+        fields.insert(field.to_string(), next);
+        next
+    }
+}
+
+struct Builder<'a> {
+    data: &'a Data,
+    options: ProtoOptions,
+    lock: &'a mut FieldLock,
+    /// Rendered messages and enums by name; `None` while a message is being
+    /// built, so recursive types terminate.
+    messages: BTreeMap<String, Option<String>>,
+    enums: BTreeMap<String, String>,
+    /// Every generic instantiation in use; see [`crate::generics::instantiations`].
+    instances: Vec<Type>,
+    uses_struct: bool,
+    errors: Vec<String>,
+}
+
+impl Builder<'_> {
+    fn message(&mut self, name: &str, fields: &[Field], site: &str) -> String {
+        self.named_message(struct_name(name), fields, site)
+    }
+
+    /// A generic instantiation, e.g. `Paged<Order>` as `Paged_Order`.
+    fn instance(&mut self, name: &str, site: &str) -> Option<String> {
+        let instance = self
+            .instances
+            .iter()
+            .find(|s| matches!(s, Type::Struct { name: n, .. } if n == name))
+            .cloned();
+        let Some(Type::Struct { fields, .. }) = instance else {
+            self.errors.push(format!("{site}: unknown generic type `{name}`"));
+            return None;
+        };
+        Some(self.named_message(monomorphised_name(name), &fields, &format!("struct '{name}'")))
+    }
+
+    fn named_message(&mut self, name: String, fields: &[Field], site: &str) -> String {
+        if self.messages.contains_key(&name) {
+            return name;
+        }
+        self.messages.insert(name.clone(), None);
+        let mut body = String::new();
+        let mut present = BTreeSet::new();
+        for field in fields {
+            let field_name = field.name.to_case(Case::Snake);
+            let field_site = format!("{site} field '{}'", field.name);
+            if let Some(ty) = self.field_type(&field.ty, &field_site) {
+                let number = self.lock.number(&name, &field_name);
+                writeln!(body, "  {ty} {field_name} = {number};").unwrap();
+            }
+            present.insert(field_name);
+        }
+        body.push_str(&self.reserved(&name, &present));
+        self.messages
+            .insert(name.clone(), Some(format!("message {name} {{\n{body}}}\n")));
+        name
+    }
+
+    /// `reserved` lines for the fields of `message` that no longer exist.
+    fn reserved(&self, message: &str, present: &BTreeSet<String>) -> String {
+        let removed: Vec<(&String, &u32)> = self
+            .lock
+            .messages
+            .get(message)
+            .into_iter()
+            .flatten()
+            .filter(|(field, _)| !present.contains(*field))
+            .collect();
+        if removed.is_empty() {
+            return String::new();
+        }
+        let mut numbers: Vec<u32> = removed.iter().map(|(_, n)| **n).collect();
+        numbers.sort();
+        let numbers: Vec<String> = numbers.iter().map(u32::to_string).collect();
+        let names: Vec<String> = removed.iter().map(|(f, _)| format!("\"{f}\"")).collect();
+        format!("  reserved {};\n  reserved {};\n", numbers.join(", "), names.join(", "))
+    }
+
+    fn union(&mut self, union: &crate::definitions::TaggedUnionElement) {
+        let name = struct_name(&union.name);
+        self.messages.insert(name.clone(), None);
+        let mut body = String::from("  oneof kind {\n");
+        let mut present = BTreeSet::new();
+        for variant in &union.variants {
+            let site = format!("union '{}' variant '{}'", union.name, variant.name);
+            let message = self.message(&format!("{}{}", union.name, variant.name), &variant.fields, &site);
+            let field_name = variant.name.to_case(Case::Snake);
+            let number = self.lock.number(&name, &field_name);
+            writeln!(body, "    {message} {field_name} = {number};").unwrap();
+            present.insert(field_name);
+        }
+        body.push_str("  }\n");
+        body.push_str(&self.reserved(&name, &present));
+        self.messages
+            .insert(name.clone(), Some(format!("message {name} {{\n{body}}}\n")));
+    }
+
+    fn enum_(&mut self, name: &str, variants: &[EnumVariant]) -> String {
+        let name = struct_name(name);
+        if self.enums.contains_key(&name) {
+            return name;
+        }
+        let prefix = name.to_case(Case::UpperSnake);
+        let mut body = String::new();
+        // proto3 enums must start at zero.
+        if !variants.iter().any(|v| v.value == 0) {
+            writeln!(body, "  {prefix}_UNSPECIFIED = 0;").unwrap();
+        }
+        let mut sorted: Vec<&EnumVariant> = variants.iter().collect();
+        sorted.sort_by_key(|v| v.value);
+        for variant in sorted {
+            if !variant.description.is_empty() {
+                writeln!(body, "  // {}", first_line(&variant.description)).unwrap();
+            }
+            let value_name = enum_variant_name(&variant.name).to_case(Case::UpperSnake);
+            writeln!(body, "  {prefix}_{value_name} = {};", variant.value).unwrap();
+        }
+        self.enums.insert(name.clone(), format!("enum {name} {{\n{body}}}\n"));
+        name
+    }
+
+    /// A field's type with its label: `optional string`, `repeated Order`,
+    /// `map<string, double>`.
+    fn field_type(&mut self, ty: &Type, site: &str) -> Option<String> {
+        let ty = self.resolve_newtype(ty);
+        match &ty {
+            Type::Optional(inner) => {
+                let inner = self.resolve_newtype(inner);
+                if self.is_repeated(&inner) {
+                    self.errors.push(format!(
+                        "{site}: an optional list or map has no protobuf mapping, as repeated and map fields cannot be optional"
+                    ));
+                    return None;
+                }
+                Some(format!("optional {}", self.element_type(&inner, site)?))
+            }
+            Type::Vec(inner) => {
+                let inner = self.resolve_newtype(inner);
+                if self.is_repeated(&inner) || matches!(inner, Type::Optional(_)) {
+                    self.errors.push(format!(
+                        "{site}: `{}` has no protobuf mapping, as repeated elements cannot be lists, maps or optional",
+                        ty.to_rust_ref(false)
+                    ));
+                    return None;
+                }
+                Some(format!("repeated {}", self.element_type(&inner, site)?))
+            }
+            Type::StructTable { struct_ref } => Some(format!(
+                "repeated {}",
+                self.element_type(&Type::StructRef(struct_ref.clone()), site)?
+            )),
+            Type::StructRef(name) if parse_map_ref(name).is_some() => {
+                let (_, value) = parse_map_ref(name).expect("checked above");
+                let value = self.resolve_newtype(&value_type(&value, &self.data.enums));
+                if self.is_repeated(&value) || matches!(value, Type::Optional(_)) {
+                    self.errors.push(format!(
+                        "{site}: `{name}` has no protobuf mapping, as map values cannot be lists, maps or optional"
+                    ));
+                    return None;
+                }
+                // Map keys cannot be enums; an enum key is its variant name.
+                Some(format!("map<string, {}>", self.element_type(&value, site)?))
+            }
+            _ => self.element_type(&ty, site),
+        }
+    }
+
+    fn is_repeated(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Vec(_) | Type::StructTable { .. } => true,
+            Type::StructRef(name) => parse_map_ref(name).is_some(),
+            _ => false,
+        }
+    }
+
+    /// The type a newtype reference stands for, or `ty` itself.
+    fn resolve_newtype(&self, ty: &Type) -> Type {
+        match ty {
+            Type::StructRef(name) => match self.data.newtypes.iter().find(|n| &n.name == name) {
+                Some(newtype) => self.resolve_newtype(&newtype.ty),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    /// An unlabelled type.
+    fn element_type(&mut self, ty: &Type, site: &str) -> Option<String> {
+        Some(
+            match ty {
+                Type::Int32 => "int32",
+                Type::Int64 | Type::TimeStampMs => "int64",
+                Type::UInt32 => "uint32",
+                Type::Float64 => "double",
+                Type::Boolean => "bool",
+                Type::Bytea => "bytes",
+                // Strings on the wire, so strings here too.
+                Type::String
+                | Type::UUID
+                | Type::NanoId { .. }
+                | Type::IpAddr
+                | Type::BlockchainDecimal
+                | Type::BlockchainAddress
+                | Type::BlockchainTransactionHash => "string",
+                Type::Object if self.options.object_as_struct => {
+                    self.uses_struct = true;
+                    "google.protobuf.Struct"
+                }
+                Type::Object => {
+                    self.errors.push(format!(
+                        "{site}: Object has no protobuf mapping; pass --proto-object-as-struct to map it to \
+                         google.protobuf.Struct"
+                    ));
+                    return None;
+                }
+                Type::Struct { name, fields } => return Some(self.message(name, fields, &format!("struct '{name}'"))),
+                Type::Enum { name, variants } => return Some(self.enum_(name, variants)),
+                Type::EnumRef { name, .. } => return Some(struct_name(name)),
+                Type::StructRef(name) if parse_generic_ref(name).is_some() => return self.instance(name, site),
+                Type::StructRef(name) => {
+                    if self.is_type_param(name) {
+                        self.errors
+                            .push(format!("{site}: generic type `{name}` has no protobuf mapping"));
+                        return None;
+                    }
+                    return Some(struct_name(name));
+                }
+                other => {
+                    self.errors.push(format!(
+                        "{site}: `{}` has no protobuf mapping",
+                        other.to_rust_ref(false)
+                    ));
+                    return None;
+                }
+            }
+            .to_string(),
+        )
+    }
+
+    fn is_type_param(&self, name: &str) -> bool {
+        self.data
+            .structs
+            .iter()
+            .any(|s| s.type_params.iter().any(|p| p == name))
+    }
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default().trim()
+}
+
+/// The package name: the project name in snake case, keeping digits with
+/// their word (`api2`, not `api_2`).
+pub fn package_name(data: &Data) -> String {
+    data.project_name
+        .remove_boundaries(&Boundary::digits())
+        .to_case(Case::Snake)
+}
+
+/// The `.proto` file for `data`, numbering fields through `lock` and adding
+/// any new numbers to it.
+pub fn build_proto(data: &Data, options: ProtoOptions, lock: &mut FieldLock) -> Result<String> {
+    let mut builder = Builder {
+        data,
+        options,
+        lock,
+        messages: BTreeMap::new(),
+        enums: BTreeMap::new(),
+        instances: crate::generics::instantiations(data),
+        uses_struct: false,
+        errors: vec![],
+    };
+
+    for element in &data.enums {
+        if let Type::Enum { name, variants } = &element.inner {
+            builder.enum_(name, variants);
+        }
+    }
+    // Generic structs only exist instantiated; each instantiation is a message.
+    for element in data.structs.iter().filter(|s| s.type_params.is_empty()) {
+        if let Type::Struct { name, fields } = &element.inner {
+            builder.message(name, fields, &format!("struct '{name}'"));
+        }
+    }
+    for instance in builder.instances.clone() {
+        if let Type::Struct { name, .. } = &instance {
+            builder.instance(name, &format!("struct '{name}'"));
+        }
+    }
+    for union in &data.unions {
+        builder.union(union);
+    }
+
+    let mut services = String::new();
+    for service in &data.services {
+        writeln!(services, "service {} {{", struct_name(&service.name)).unwrap();
+        for endpoint in &service.endpoints {
+            let schema = &endpoint.schema;
+            let site = format!("service '{}' endpoint '{}'", service.name, schema.name);
+            let request = builder.message(&format!("{}Request", schema.name), &schema.parameters, &site);
+            let response = builder.message(&format!("{}Response", schema.name), &schema.returns, &site);
+            let returns = match &schema.stream_response {
+                None => response,
+                Some(stream) => {
+                    let item = stream.clone().try_unwrap().unwrap_or_else(|| stream.clone());
+                    match builder.resolve_newtype(&item) {
+                        item @ (Type::Struct { .. } | Type::StructRef(_)) => {
+                            let Some(item) = builder.element_type(&item, &format!("{site} stream_response")) else {
+                                continue;
+                            };
+                            format!("stream {item}")
+                        }
+                        _ => {
+                            builder.errors.push(format!(
+                                "{site} stream_response: only struct stream items have a protobuf mapping"
+                            ));
+                            continue;
+                        }
+                    }
+                }
+            };
+            if !schema.description.is_empty() {
+                writeln!(services, "  // {}", first_line(&schema.description)).unwrap();
+            }
+            writeln!(
+                services,
+                "  rpc {}({request}) returns ({returns});",
+                struct_name(&schema.name)
+            )
+            .unwrap();
+        }
+        writeln!(services, "}}\n").unwrap();
+    }
+
+    if !builder.errors.is_empty() {
+        bail!(
+            "Protobuf generation failed for {} field(s):\n  {}",
+            builder.errors.len(),
+            builder.errors.join("\n  ")
+        );
+    }
+
+    let mut out = String::from("// Generated by endpoint-gen from the RON definitions. Do not edit.\n");
+    out.push_str("syntax = \"proto3\";\n\n");
+    writeln!(out, "package {};\n", package_name(data)).unwrap();
+    if builder.uses_struct {
+        out.push_str("import \"google/protobuf/struct.proto\";\n\n");
+    }
+    out.push_str(&services);
+    for message in builder.messages.into_values().flatten() {
+        out.push_str(&message);
+        out.push('\n');
+    }
+    for enum_ in builder.enums.values() {
+        out.push_str(enum_);
+        out.push('\n');
+    }
+    Ok(out.trim_end().to_string() + "\n")
+}
+
+/// Checks that everything `--proto` would emit has a protobuf mapping, so an
+/// unmappable field fails generation before any file is written.
+pub fn check_proto(data: &Data, options: ProtoOptions) -> Result<()> {
+    // Field numbers cannot make a mapping fail, so a scratch lock will do.
+    build_proto(data, options, &mut FieldLock::default()).map(|_| ())
+}
+
+/// Writes `docs/proto/{package}.proto` and updates `docs/proto/fields.lock.json`.
+pub fn gen_proto(data: &Data, options: ProtoOptions) -> Result<()> {
+    let proto_dir = data.project_root.join("docs").join("proto");
+    std::fs::create_dir_all(&proto_dir)?;
+
+    let lock_file = proto_dir.join("fields.lock.json");
+    let mut lock: FieldLock = if lock_file.exists() {
+        serde_json::from_str(&std::fs::read_to_string(&lock_file)?)
+            .with_context(|| format!("Failed to parse field-number lockfile: {}", lock_file.display()))?
+    } else {
+        FieldLock::default()
+    };
+
+    let proto = build_proto(data, options, &mut lock)?;
+    std::fs::write(proto_dir.join(format!("{}.proto", package_name(data))), proto)?;
+    let file = std::fs::File::create(&lock_file)
+        .with_context(|| format!("Failed to create field-number lockfile: {}", lock_file.display()))?;
+    serde_json::to_writer_pretty(file, &lock)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{
+        EndpointSchemaElement, EnumElement, GenService, RustGenConfig, StructElement, TaggedUnionElement, TaggedVariant,
+    };
+    use endpoint_libs::model::EndpointSchema;
+    use std::path::PathBuf;
+
+    fn data(order_fields: Vec<Field>) -> Data {
+        let mut subscribe = EndpointSchema::new("SubscribeOrders", 2, vec![], vec![Field::new("id", Type::UUID)]);
+        subscribe.stream_response = Some(Type::vec(Type::struct_ref("Order")));
+        Data {
+            project_name: "trading-api2".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![GenService::new(
+                "orders".into(),
+                1,
+                vec![
+                    EndpointSchemaElement {
+                        frontend_facing: true,
+                        config: RustGenConfig::default(),
+                        schema: EndpointSchema::new(
+                            "GetOrder",
+                            1,
+                            vec![Field::new("order_id", Type::Int64)],
+                            vec![Field::new("order", Type::optional(Type::struct_ref("Order")))],
+                        )
+                        .with_description("Fetch one order."),
+                        ..Default::default()
+                    },
+                    EndpointSchemaElement {
+                        frontend_facing: true,
+                        config: RustGenConfig::default(),
+                        schema: subscribe,
+                        ..Default::default()
+                    },
+                ],
+            )],
+            enums: vec![EnumElement {
+                config: RustGenConfig::default(),
+                inner: Type::enum_("Side", vec![EnumVariant::new("Buy", 1), EnumVariant::new("Sell", 2)]),
+            }],
+            structs: vec![StructElement {
+                config: RustGenConfig::default(),
+                type_params: vec![],
+                inner: Type::struct_("Order", order_fields),
+            }],
+            unions: vec![TaggedUnionElement {
+                config: RustGenConfig::default(),
+                name: "OrderEvent".into(),
+                tag: "type".into(),
+                description: String::new(),
+                variants: vec![
+                    TaggedVariant {
+                        name: "Filled".into(),
+                        description: String::new(),
+                        fields: vec![Field::new("price", Type::BlockchainDecimal)],
+                    },
+                    TaggedVariant {
+                        name: "Cancelled".into(),
+                        description: String::new(),
+                        fields: vec![],
+                    },
+                ],
+            }],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    fn order_fields() -> Vec<Field> {
+        vec![
+            Field::new("id", Type::Int64),
+            Field::new("side", Type::enum_ref("Side", false)),
+            Field::new("tags", Type::vec(Type::String)),
+            Field::new("fees", Type::struct_ref("BTreeMap<String, f64>")),
+            Field::new("note", Type::optional(Type::String)),
+        ]
+    }
+
+    #[test]
+    fn services_structs_enums_and_unions_map_to_proto3() {
+        let proto = build_proto(
+            &data(order_fields()),
+            ProtoOptions::default(),
+            &mut FieldLock::default(),
+        )
+        .unwrap();
+        assert!(proto.contains("package trading_api2;"), "{proto}");
+        assert!(
+            proto.contains(
+                "service Orders {\n  // Fetch one order.\n  rpc GetOrder(GetOrderRequest) returns (GetOrderResponse);\n  \
+                 rpc SubscribeOrders(SubscribeOrdersRequest) returns (stream Order);\n}"
+            ),
+            "{proto}"
+        );
+        assert!(
+            proto.contains(
+                "message Order {\n  int64 id = 1;\n  Side side = 2;\n  repeated string tags = 3;\n  \
+                 map<string, double> fees = 4;\n  optional string note = 5;\n}"
+            ),
+            "{proto}"
+        );
+        assert!(
+            proto.contains("enum Side {\n  SIDE_UNSPECIFIED = 0;\n  SIDE_BUY = 1;\n  SIDE_SELL = 2;\n}"),
+            "{proto}"
+        );
+        assert!(
+            proto.contains(
+                "message OrderEvent {\n  oneof kind {\n    OrderEventFilled filled = 1;\n    \
+                 OrderEventCancelled cancelled = 2;\n  }\n}"
+            ),
+            "{proto}"
+        );
+        assert!(
+            proto.contains("message OrderEventFilled {\n  string price = 1;\n}"),
+            "{proto}"
+        );
+    }
+
+    #[test]
+    fn generic_instantiations_become_monomorphised_messages() {
+        let mut data = data(order_fields());
+        data.structs.push(StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec!["T".into()],
+            inner: Type::struct_(
+                "Paged",
+                vec![
+                    Field::new("items", Type::vec(Type::struct_ref("T"))),
+                    Field::new("total", Type::Int64),
+                ],
+            ),
+        });
+        data.services[0].endpoints[0].schema.returns = vec![Field::new("page", Type::struct_ref("Paged<Order>"))];
+
+        let proto = build_proto(&data, ProtoOptions::default(), &mut FieldLock::default()).unwrap();
+        assert!(
+            proto.contains("message GetOrderResponse {\n  Paged_Order page = 1;\n}"),
+            "{proto}"
+        );
+        assert!(
+            proto.contains("message Paged_Order {\n  repeated Order items = 1;\n  int64 total = 2;\n}"),
+            "{proto}"
+        );
+        assert!(!proto.contains("message Paged {"), "{proto}");
+    }
+
+    #[test]
+    fn locked_numbers_survive_reordering_and_removed_ones_are_reserved() {
+        let mut lock = FieldLock::default();
+        build_proto(&data(order_fields()), ProtoOptions::default(), &mut lock).unwrap();
+
+        let mut fields = order_fields();
+        fields.retain(|f| f.name != "tags");
+        fields.reverse();
+        fields.push(Field::new("venue", Type::String));
+        let proto = build_proto(&data(fields), ProtoOptions::default(), &mut lock).unwrap();
+        assert!(
+            proto.contains(
+                "message Order {\n  optional string note = 5;\n  map<string, double> fees = 4;\n  Side side = 2;\n  \
+                 int64 id = 1;\n  string venue = 6;\n  reserved 3;\n  reserved \"tags\";\n}"
+            ),
+            "{proto}"
+        );
+        assert_eq!(lock.messages["Order"]["tags"], 3);
+    }
+
+    #[test]
+    fn unsupported_mappings_are_errors_unless_configured() {
+        let fields = vec![
+            Field::new("extra", Type::Object),
+            Field::new("fees", Type::optional(Type::vec(Type::BlockchainDecimal))),
+        ];
+        let err = build_proto(
+            &data(fields.clone()),
+            ProtoOptions::default(),
+            &mut FieldLock::default(),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.starts_with("Protobuf generation failed for 2 field(s)"), "{err}");
+        assert!(
+            err.contains("struct 'Order' field 'extra': Object has no protobuf mapping; pass --proto-object-as-struct"),
+            "{err}"
+        );
+        assert!(err.contains("struct 'Order' field 'fees': an optional list"), "{err}");
+
+        let options = ProtoOptions { object_as_struct: true };
+        let err = build_proto(&data(fields), options, &mut FieldLock::default())
+            .unwrap_err()
+            .to_string();
+        assert!(!err.contains("'extra'"), "{err}");
+        let proto = build_proto(
+            &data(vec![Field::new("extra", Type::Object)]),
+            options,
+            &mut FieldLock::default(),
+        )
+        .unwrap();
+        assert!(proto.contains("import \"google/protobuf/struct.proto\";"), "{proto}");
+        assert!(check_proto(&data(vec![Field::new("extra", Type::Object)]), options).is_ok());
+        assert!(check_proto(&data(vec![Field::new("extra", Type::Object)]), ProtoOptions::default()).is_err());
+        assert!(proto.contains("google.protobuf.Struct extra = 1;"), "{proto}");
+    }
+}
@@ -1,10 +1,11 @@
-use crate::definitions::EnumElement;
+use crate::definitions::{EndpointSchemaElement, EnumElement, RustGenConfig};
 use crate::docs::Data;
+use crate::rust_item::{RustItem, enum_name, struct_name};
 use convert_case::{Case, Casing};
-use endpoint_libs::model::{EndpointErrorSchema, EnumVariant, Type};
+use endpoint_libs::model::{EndpointErrorSchema, EndpointSchema, EnumVariant, Field, Type};
 use eyre::bail;
 use itertools::Itertools;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -12,8 +13,8 @@ use std::process::Command;
 
 pub trait ToRust {
     fn to_rust_ref(&self, serde_with: bool) -> String;
+    /// The declaration; without `add_derives`, just the bare item.
     fn to_rust_decl(&self, serde_with: bool, add_derives: bool) -> String;
-    fn add_derives(&self, input: String) -> String;
 }
 
 impl ToRust for Type {
@@ -24,7 +25,7 @@ impl ToRust for Type {
             Type::Int64 => "i64".to_owned(),
             Type::Float64 => "f64".to_owned(),
             Type::TimeStampMs => "i64".to_owned(),
-            Type::Struct { name, .. } => name.clone(),
+            Type::Struct { name, .. } => struct_name(name),
             Type::StructRef(name) => name.clone(),
             Type::Object => "serde_json::Value".to_owned(),
             // Type::DataTable { name, .. } => format!("Vec<{name}>"),
@@ -42,14 +43,8 @@ impl ToRust for Type {
             Type::UUID => "Uuid".to_owned(),
             Type::NanoId { len } => format!("Nanoid<{len}, Base62Alphabet>"),
             Type::IpAddr => "IpAddr".to_owned(),
-            Type::Enum { name, .. } => format!("Enum{}", name.to_case(Case::Pascal),),
-            Type::EnumRef { name, prefixed_name } => {
-                if *prefixed_name {
-                    format!("Enum{}", name.to_case(Case::Pascal),)
-                } else {
-                    name.to_case(Case::Pascal)
-                }
-            }
+            Type::Enum { name, .. } => enum_name(name, true),
+            Type::EnumRef { name, prefixed_name } => enum_name(name, *prefixed_name),
             Type::BlockchainDecimal => "Decimal".to_owned(),
             Type::BlockchainAddress if serde_with => "Address".to_owned(),
             Type::BlockchainTransactionHash if serde_with => "H256".to_owned(),
@@ -67,123 +62,9 @@ impl ToRust for Type {
     }
 
     fn to_rust_decl(&self, serde_with: bool, add_derives: bool) -> String {
-        let code_regex = regex::Regex::new(r"=\s*(\d+)").expect("Error building regex to extract endpoint code");
-
-        match self {
-            Type::Struct { name, fields } => {
-                let mut fields = fields.iter().map(|x| {
-                    let opt = matches!(&x.ty, Type::Optional(_));
-                    let serde_with_opt = match &x.ty {
-                        Type::BlockchainDecimal => "rust_decimal::serde::str",
-                        Type::BlockchainAddress if serde_with => "WithBlockchainAddress",
-                        Type::BlockchainTransactionHash if serde_with => "WithBlockchainTransactionHash",
-                        // TODO: handle optional decimals
-                        // Type::Optional(t) if matches!(**t, Type::BlockchainDecimal) => {
-                        //     "WithBlockchainDecimal"
-                        // }
-                        // Type::Optional(t) if matches!(**t, Type::BlockchainAddress) => {
-                        //     "WithBlockchainAddress"
-                        // }
-                        // Type::Optional(t) if matches!(**t, Type::BlockchainTransactionHash) => {
-                        //     "WithBlockchainTransactionHash"
-                        // }
-                        _ => "",
-                    };
-                    format!(
-                        "{} {} pub {}: {}",
-                        if opt { "#[serde(default)]" } else { "" },
-                        if serde_with_opt.is_empty() {
-                            "".to_string()
-                        } else {
-                            format!("#[serde(with = \"{serde_with_opt}\")]")
-                        },
-                        x.name,
-                        x.ty.to_rust_ref(serde_with)
-                    )
-                });
-                let input = format!("pub struct {} {{{}}}", name, fields.join(","));
-
-                if add_derives { self.add_derives(input) } else { input }
-            }
-            Type::Enum { name, variants: fields } => {
-                let mut fields = fields
-                    .iter()
-                    .map(|x| {
-                        let variant_name = if x.name.chars().last().unwrap().is_lowercase() {
-                            x.name.to_case(Case::Pascal)
-                        } else {
-                            x.name.clone()
-                        };
-                        // A blank description must omit the `///` line entirely: an
-                        // empty doc comment trips clippy::empty_docs downstream.
-                        if x.description.trim().is_empty() {
-                            format!(
-                                r#"
-    {} = {}
-"#,
-                                variant_name, x.value
-                            )
-                        } else {
-                            format!(
-                                r#"
-    /// {}
-    {} = {}
-"#,
-                                x.description, variant_name, x.value
-                            )
-                        }
-                    })
-                    .sorted_by(|a, b| {
-                        // Sort by the endpoint code
-                        let code_a = {
-                            match code_regex.captures(a) {
-                                Some(code) => code[1].parse::<u64>().unwrap_or_else(|err| {
-                                    eprintln!("Sorting error: {err}: Rust output may not be sorted correctly");
-                                    0
-                                }),
-                                None => {
-                                    eprintln!("Sorting error: Rust output may not be sorted correctly");
-                                    0
-                                }
-                            }
-                        };
-
-                        let code_b = {
-                            match code_regex.captures(b) {
-                                Some(code) => code[1].parse::<u64>().unwrap_or_else(|err| {
-                                    eprintln!("Sorting error: {err}: Rust output may not be sorted correctly");
-                                    0
-                                }),
-                                None => {
-                                    eprintln!("Sorting error: Rust output may not be sorted correctly");
-                                    0
-                                }
-                            }
-                        };
-
-                        code_a.cmp(&code_b)
-                    });
-                let enum_content = format!(
-                    r#"pub enum Enum{} {{{}}}"#,
-                    name.to_case(Case::Pascal),
-                    fields.join(",")
-                );
-
-                if add_derives {
-                    self.add_derives(enum_content)
-                } else {
-                    enum_content
-                }
-            }
-            x => x.to_rust_ref(serde_with),
-        }
-    }
-
-    fn add_derives(&self, input: String) -> String {
-        match self {
-            Self::Enum { .. } => Self::add_default_enum_derives(input),
-            Self::Struct { .. } => Self::add_default_struct_derives(input),
-            _ => input,
+        match RustItem::from_type(self, serde_with) {
+            Some(item) => item.render(add_derives),
+            None => self.to_rust_ref(serde_with),
         }
     }
 }
@@ -307,47 +188,20 @@ pub fn gen_model_rs(data: &Data) -> eyre::Result<()> {
         std::fs::create_dir_all(parent)?;
     }
 
-    let worktable_imports = if data.enums.iter().any(|e| e.config.worktable_support)
-        || data.structs.iter().any(|s| s.config.worktable_support)
-    {
-        r#"use worktable::prelude::*;
-           use rkyv::Archive;
-        "#
-    } else {
-        ""
-    };
-
-    let json_schema_imports = if data.enums.iter().any(|e| e.config.json_schema_gen)
-        || data.structs.iter().any(|s| s.config.json_schema_gen)
-    {
-        r#"use schemars::{schema_for, JsonSchema};"#
-    } else {
-        ""
-    };
-
-    let mut model_file = File::create(&db_filename)?;
-    write!(
-        &mut model_file,
-        "use endpoint_libs::libs::error_code::ErrorCode;
-        use endpoint_libs::libs::ws::*;
-        use endpoint_libs::libs::types::*;
-        use endpoint_libs::libs::ws::toolbox::CustomError;
-        use num_derive::FromPrimitive;
-        use serde::*;
-        use strum_macros::{{Display, EnumString}};
-        use uuid::Uuid;
-        use psc_nanoid::{{Nanoid, alphabet::Base62Alphabet}};
-        use std::net::IpAddr;
-        {worktable_imports}
-        {json_schema_imports}
-        ",
-    )?;
+    // Everything after the `use` block, which depends on what gets derived.
+    let mut model_file: Vec<u8> = Vec::new();
+    let mut derives: BTreeSet<String> = BTreeSet::new();
 
-    for e in &data.enums {
-        writeln!(&mut model_file, "{}", e.to_rust_decl(false, true))?;
-    }
-    for s in &data.structs {
-        writeln!(&mut model_file, "{}", s.to_rust_decl(false, true))?;
+    let shared_items = data
+        .enums
+        .iter()
+        .map(|e| e.rust_item())
+        .chain(data.structs.iter().map(|s| s.rust_item(false)))
+        .chain(data.unions.iter().map(|u| u.rust_item(false)))
+        .chain(data.newtypes.iter().map(|n| n.rust_item(false)));
+    for item in shared_items {
+        derives.extend(item.derives.iter().cloned());
+        writeln!(&mut model_file, "{}", item.render(true))?;
     }
     check_endpoint_codes(data, &mut model_file)?;
     dump_endpoint_schema(data, &mut model_file)?;
@@ -360,7 +214,10 @@ pub fn gen_model_rs(data: &Data) -> eyre::Result<()> {
             .map(|x| EnumVariant::new_with_description(error_code_variant_name(&x.name), x.description.clone(), x.code))
             .collect(),
     );
-    writeln!(&mut model_file, "{}", enum_.to_rust_decl(false, true))?;
+    // `EnumEndpoint` above has the same derives.
+    let error_code_item = RustItem::from_type(&enum_, false).expect("an enum is an item");
+    derives.extend(error_code_item.derives.iter().cloned());
+    writeln!(&mut model_file, "{}", error_code_item.render(true))?;
     writeln!(
         &mut model_file,
         r#"
@@ -372,40 +229,88 @@ impl From<EnumErrorCode> for ErrorCode {{
     "#
     )?;
 
-    let mut endpoint_reqres_types = BTreeSet::new();
+    // Each type with the config of the first endpoint it appears in.
+    let mut endpoint_reqres_types: BTreeMap<Type, &RustGenConfig> = BTreeMap::new();
     for s in &data.services {
         for e in &s.endpoints {
             let req = Type::struct_(format!("{}Request", e.schema.name), e.schema.parameters.clone());
             let resp = Type::struct_(format!("{}Response", e.schema.name), e.schema.returns.clone());
-            endpoint_reqres_types.extend(
-                [
-                    collect_rust_recursive_types(req),
-                    collect_rust_recursive_types(resp),
-                    e.schema
-                        .stream_response
-                        .clone()
-                        .into_iter()
-                        .flat_map(Type::try_unwrap)
-                        .collect::<Vec<_>>(),
-                    e.schema
-                        .errors
-                        .iter()
-                        .flat_map(|error| {
-                            error
-                                .fields
-                                .iter()
-                                .flat_map(|field| collect_rust_recursive_types(field.ty.clone()))
-                        })
-                        .collect::<Vec<_>>(),
-                ]
-                .concat(),
-            );
+            let types = [
+                collect_rust_recursive_types(req),
+                collect_rust_recursive_types(resp),
+                e.schema
+                    .stream_response
+                    .clone()
+                    .into_iter()
+                    .flat_map(Type::try_unwrap)
+                    .collect::<Vec<_>>(),
+                e.schema
+                    .errors
+                    .iter()
+                    .flat_map(|error| {
+                        error
+                            .fields
+                            .iter()
+                            .flat_map(|field| collect_rust_recursive_types(field.ty.clone()))
+                    })
+                    .collect::<Vec<_>>(),
+            ]
+            .concat();
+            for ty in types {
+                endpoint_reqres_types.entry(ty).or_insert(&e.config);
+            }
+        }
+        for event in &s.events {
+            for ty in collect_rust_recursive_types(event.payload()) {
+                endpoint_reqres_types.entry(ty).or_insert(&event.config);
+            }
         }
     }
-    for s in endpoint_reqres_types {
-        write!(&mut model_file, r#"{}"#, s.to_rust_decl(true, true))?;
+    // An inline declaration identical to a shared one (type_check guarantees
+    // identical, not merely same-named) is already emitted above; writing it
+    // again would be a "defined multiple times" error.
+    let shared_names: BTreeSet<String> = data
+        .structs
+        .iter()
+        .map(|s| s.to_rust_ref(false))
+        .chain(data.enums.iter().map(|e| e.to_rust_ref(false)))
+        .collect();
+    let endpoint_reqres_types: Vec<(Type, &RustGenConfig)> = endpoint_reqres_types
+        .into_iter()
+        .filter(|(s, _)| !shared_names.contains(&s.to_rust_ref(true)))
+        .collect();
+    for (s, config) in &endpoint_reqres_types {
+        // Anything else is a reference, declared elsewhere.
+        if let Some(item) = RustItem::from_type(s, true) {
+            let item = item.with_extras(config);
+            derives.extend(item.derives.iter().cloned());
+            write!(&mut model_file, "{}", item.render(true))?;
+        }
     }
 
+    // Shared definitions are emitted without serde_with, endpoint types with.
+    let union_variants: Vec<Type> = data
+        .unions
+        .iter()
+        .flat_map(|u| {
+            u.variants
+                .iter()
+                .map(|v| Type::struct_(v.name.clone(), v.fields.clone()))
+        })
+        .collect();
+    let adapted = data
+        .structs
+        .iter()
+        .map(|s| (&s.inner, false))
+        .chain(union_variants.iter().map(|v| (v, false)))
+        .chain(data.newtypes.iter().map(|n| (&n.ty, false)))
+        .chain(endpoint_reqres_types.iter().map(|(s, _)| (s, true)));
+    for module in crate::serde_adapters::helper_modules(adapted).values() {
+        writeln!(&mut model_file, "{module}")?;
+    }
+
+    write!(&mut model_file, "{}", crate::sql::row_impls(data)?)?;
+
     for s in &data.services {
         for endpoint in &s.endpoints {
             gen_endpoint_error_enum(&endpoint.schema.name, &endpoint.schema.errors, &mut model_file)?;
@@ -434,20 +339,65 @@ impl WsResponse for {end_name2}Response {{
 ",
                 end_name2 = endpoint.schema.name.to_case(Case::Pascal),
                 code = endpoint.schema.code,
-                schema = serde_json::to_string_pretty(&endpoint.schema).unwrap()
+                schema = serde_json::to_string_pretty(&runtime_schema(endpoint, data)?)?
             )?;
+            write_operational_consts(endpoint, &data.enums, &mut model_file)?;
         }
     }
-    model_file.flush()?;
-    drop(model_file);
+    dump_server_events(data, &mut model_file)?;
+    crate::mcp::dump_mcp_catalog(data, &mut model_file)?;
+    let mut file = File::create(&db_filename)?;
+    write!(file, "{}", model_imports(&derives, &crate::maps::kinds(data)))?;
+    file.write_all(&model_file)?;
+    file.flush()?;
+    drop(file);
     rustfmt(&db_filename)?;
 
     Ok(())
 }
 
+/// The `use` block of `model.rs`: what the fixed parts of the file need, plus
+/// the crates behind whichever derives were actually emitted and the map
+/// types (`BTreeMap`, `HashMap`) the fields use.
+fn model_imports(derives: &BTreeSet<String>, maps: &BTreeSet<String>) -> String {
+    let mut imports = vec![
+        "use endpoint_libs::libs::error_code::ErrorCode;".to_string(),
+        "use endpoint_libs::libs::ws::*;".to_string(),
+        "use endpoint_libs::libs::types::*;".to_string(),
+        "use endpoint_libs::libs::ws::toolbox::CustomError;".to_string(),
+        "use serde::*;".to_string(),
+        "use uuid::Uuid;".to_string(),
+        "use psc_nanoid::{Nanoid, alphabet::Base62Alphabet};".to_string(),
+        "use std::net::IpAddr;".to_string(),
+    ];
+    match maps.len() {
+        0 => {}
+        1 => imports.push(format!("use std::collections::{};", maps.iter().join(""))),
+        _ => imports.push(format!("use std::collections::{{{}}};", maps.iter().join(", "))),
+    }
+    let has = |derive: &str| derives.contains(derive);
+    if has("FromPrimitive") {
+        imports.push("use num_derive::FromPrimitive;".to_string());
+    }
+    let strum: Vec<&str> = ["Display", "EnumString"].into_iter().filter(|d| has(d)).collect();
+    if !strum.is_empty() {
+        imports.push(format!("use strum_macros::{{{}}};", strum.join(", ")));
+    }
+    if has("MemStat") || has("SizeMeasure") {
+        imports.push("use worktable::prelude::*;".to_string());
+    }
+    if has("Archive") {
+        imports.push("use rkyv::Archive;".to_string());
+    }
+    if has("JsonSchema") {
+        imports.push("use schemars::{schema_for, JsonSchema};".to_string());
+    }
+    imports.join("\n") + "\n"
+}
+
 /// Resolves the IDs of roles from a list of role names and a list of enum types.
 /// endpoint_roles: vec!["Role1::Value1", "Role1::Value2"]
-fn resolve_roles_ids(endpoint_roles: &Vec<String>, all_enums: &Vec<EnumElement>) -> Vec<i64> {
+pub(crate) fn resolve_roles_ids(endpoint_roles: &Vec<String>, all_enums: &Vec<EnumElement>) -> Vec<i64> {
     let mut all_enums_typed: HashMap<String, Vec<EnumVariant>> = HashMap::new();
     for e in all_enums {
         if let Type::Enum { name: _, variants } = &e.inner {
@@ -493,16 +443,157 @@ pub fn rustfmt(f: &Path) -> eyre::Result<()> {
     Ok(())
 }
 
+/// The schema `WsRequest::SCHEMA` carries: the endpoint's, in its
+/// [`runtime_type`]s, with `mcp.usage` appended to the description as in the
+/// tool files, since endpoint-libs builds `tools/list` from it. The rest of
+/// the `mcp` section goes under `meta["x-mcp"]`; endpoint-libs 2.1 does not
+/// read it.
+fn runtime_schema(endpoint: &EndpointSchemaElement, data: &Data) -> eyre::Result<EndpointSchema> {
+    let mut schema = endpoint.schema.clone();
+    let fields = schema
+        .parameters
+        .iter_mut()
+        .chain(schema.returns.iter_mut())
+        .chain(schema.errors.iter_mut().flat_map(|error| error.fields.iter_mut()));
+    for field in fields {
+        *field = runtime_field(field, data);
+    }
+    if let Some(stream) = &mut schema.stream_response {
+        *stream = runtime_type(stream, data);
+    }
+    schema.description = crate::docs::tool_description(endpoint);
+    if !endpoint.mcp.is_default() {
+        schema.meta.insert("x-mcp", serde_json::to_value(&endpoint.mcp)?);
+    }
+    Ok(schema)
+}
+
+/// Emits the operational metadata of an endpoint that declares any as consts
+/// on its request type. They cannot go on the `WsRequest` impl, as the trait
+/// does not declare them.
+fn write_operational_consts(
+    endpoint: &EndpointSchemaElement,
+    enums: &Vec<EnumElement>,
+    mut writer: impl Write,
+) -> eyre::Result<()> {
+    let operational = &endpoint.operational;
+    if operational.is_empty() {
+        return Ok(());
+    }
+    let rate_limits = operational
+        .rate_limits
+        .iter()
+        .flat_map(|(role, limit)| {
+            resolve_roles_ids(&vec![role.clone()], enums)
+                .into_iter()
+                .map(move |id| format!("({id}, {}, {})", limit.requests, limit.per_seconds))
+        })
+        .join(", ");
+    let option = |value: Option<String>| value.map_or("None".to_string(), |v| format!("Some({v})"));
+    writeln!(
+        writer,
+        "
+impl {name}Request {{
+    /// `(role id, requests, per seconds)` for each rate-limited role.
+    pub const RATE_LIMITS: &'static [(u32, u32, u32)] = &[{rate_limits}];
+    pub const TIMEOUT_MS: Option<u64> = {timeout};
+    /// Whether a retry is harmless.
+    pub const IDEMPOTENT: bool = {idempotent};
+    /// Whether the request changes nothing.
+    pub const SAFE: bool = {safe};
+    pub const MAX_PAYLOAD_BYTES: Option<u64> = {max_payload};
+    pub const COST: Option<u32> = {cost};
+}}",
+        name = endpoint.schema.name.to_case(Case::Pascal),
+        timeout = option(operational.timeout_ms.map(|v| v.to_string())),
+        idempotent = operational.is_idempotent(),
+        safe = operational.safe,
+        max_payload = option(operational.max_payload_bytes.map(|v| v.to_string())),
+        cost = option(operational.cost.map(|v| v.to_string())),
+    )?;
+    Ok(())
+}
+
+/// Emits `EnumEndpoint`, and `EnumServerEvent` if any service pushes events.
+/// If they compile, neither has duplicate codes or names. An event and an
+/// endpoint sharing a code would be told apart by nothing on the wire, so
+/// that is rejected here.
 pub fn check_endpoint_codes(data: &Data, mut writer: impl Write) -> eyre::Result<()> {
     let mut variants = vec![];
+    let mut endpoint_names = HashMap::new();
     for s in &data.services {
         for e in &s.endpoints {
             variants.push(EnumVariant::new(e.schema.name.clone(), e.schema.code as _));
+            endpoint_names.insert(e.schema.code, &e.schema.name);
         }
     }
     let enum_ = Type::enum_("Endpoint", variants);
     writeln!(writer, "{}", enum_.to_rust_decl(false, true))?;
     // if it compiles, there're no duplicate codes or names
+
+    let events = data.services.iter().flat_map(|s| &s.events).collect::<Vec<_>>();
+    let clashes = events
+        .iter()
+        .filter_map(|event| {
+            endpoint_names.get(&event.code).map(|endpoint| {
+                format!(
+                    "event '{}' and endpoint '{endpoint}' both use code {}",
+                    event.name, event.code
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    if !clashes.is_empty() {
+        bail!("Events must not reuse endpoint codes:\n  {}", clashes.join("\n  "));
+    }
+    if !events.is_empty() {
+        let variants = events
+            .iter()
+            .map(|event| EnumVariant::new(event.name.clone(), event.code as _))
+            .collect();
+        writeln!(
+            writer,
+            "{}",
+            Type::enum_("ServerEvent", variants).to_rust_decl(false, true)
+        )?;
+    }
+    Ok(())
+}
+
+/// Emits the `WsPush` trait and its impl for every `{name}Event`. endpoint-libs
+/// has no counterpart to `WsRequest` for pushes, so the trait is generated.
+fn dump_server_events(data: &Data, mut writer: impl Write) -> eyre::Result<()> {
+    let events = data.services.iter().flat_map(|s| &s.events).collect::<Vec<_>>();
+    if events.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        writer,
+        r#"
+/// A message the server sends without a request. It travels as an `Immediate`
+/// frame whose `method` is [`WsPush::METHOD_ID`] and whose `seq` is 0.
+pub trait WsPush: Serialize + serde::de::DeserializeOwned + Send + Sync + Clone {{
+    const METHOD_ID: u32;
+    /// The roles the event is sent to.
+    const ROLES: &'static [u32];
+}}"#
+    )?;
+    for event in events {
+        let roles_list = resolve_roles_ids(&event.roles, &data.enums)
+            .into_iter()
+            .map(|x| x.to_string())
+            .join(", ");
+        writeln!(
+            writer,
+            "
+impl WsPush for {name}Event {{
+    const METHOD_ID: u32 = {code};
+    const ROLES: &[u32] = &[{roles_list}];
+}}",
+            name = event.name.to_case(Case::Pascal),
+            code = event.code,
+        )?;
+    }
     Ok(())
 }
 pub fn dump_endpoint_schema(data: &Data, mut writer: impl Write) -> eyre::Result<()> {
@@ -534,8 +625,22 @@ pub fn dump_endpoint_schema(data: &Data, mut writer: impl Write) -> eyre::Result
 
 /// Collects every shared type definition (structs, enums, the generated
 /// `ErrorCode` enum) that endpoint schemas may reference by name.
+///
+/// Tagged unions, newtypes and maps appear as placeholder structs (see
+/// [`TaggedUnionElement::placeholder`](crate::definitions::TaggedUnionElement::placeholder)),
+/// which the spec emitters replace with their real schemas.
 pub fn shared_type_definitions(data: &Data) -> Vec<Type> {
-    let mut types: Vec<Type> = data.structs.iter().map(|s| s.inner.clone()).collect();
+    // Generic templates cannot be resolved by name; their instantiations can.
+    let mut types: Vec<Type> = data
+        .structs
+        .iter()
+        .filter(|s| s.type_params.is_empty())
+        .map(|s| s.inner.clone())
+        .collect();
+    types.extend(crate::generics::instantiations(data));
+    types.extend(data.unions.iter().map(|u| u.placeholder()));
+    types.extend(data.newtypes.iter().map(|n| n.placeholder()));
+    types.extend(crate::maps::placeholders(data));
     types.extend(data.enums.iter().map(|e| e.inner.clone()));
     types.push(Type::enum_(
         "ErrorCode",
@@ -547,12 +652,73 @@ pub fn shared_type_definitions(data: &Data) -> Vec<Type> {
     types
 }
 
+/// `ty` as it is on the wire, for the schemas the runtime reads: a newtype is
+/// `#[serde(transparent)]`, so a ref to one is replaced by its underlying type,
+/// and a map is an `Object`. [`Type`] has no map of its own, so a field that
+/// is a map carries its value type under `meta["x-map-value"]`.
+pub(crate) fn runtime_type(ty: &Type, data: &Data) -> Type {
+    match ty {
+        Type::StructRef(name) => {
+            if let Some(newtype) = data.newtypes.iter().find(|n| &n.name == name) {
+                runtime_type(&newtype.ty, data)
+            } else if crate::maps::parse_map_ref(name).is_some() {
+                Type::Object
+            } else {
+                ty.clone()
+            }
+        }
+        Type::Struct { name, fields } => Type::struct_(
+            name.clone(),
+            fields.iter().map(|field| runtime_field(field, data)).collect(),
+        ),
+        Type::Vec(inner) => Type::vec(runtime_type(inner, data)),
+        Type::Optional(inner) => Type::optional(runtime_type(inner, data)),
+        _ => ty.clone(),
+    }
+}
+
+fn runtime_field(field: &Field, data: &Data) -> Field {
+    let mut field = field.clone();
+    let map = match &field.ty {
+        Type::Optional(inner) => &**inner,
+        ty => ty,
+    };
+    if let Type::StructRef(name) = map
+        && let Some((_, value)) = crate::maps::parse_map_ref(name)
+    {
+        let value = runtime_type(&crate::maps::value_type(&value, &data.enums), data);
+        field
+            .meta
+            .insert("x-map-value", serde_json::to_value(value).expect("types serialize"));
+    }
+    field.ty = runtime_type(&field.ty, data);
+    field
+}
+
+/// The shared definitions the runtime registry gets: [`shared_type_definitions`]
+/// in their [`runtime_type`]s, without the newtype placeholders nothing refers
+/// to any more, or the map placeholders. A tagged union is still described as an object with one
+/// optional property per variant.
+pub fn runtime_type_definitions(data: &Data) -> Vec<Type> {
+    let newtypes: BTreeSet<&str> = data.newtypes.iter().map(|n| n.name.as_str()).collect();
+    shared_type_definitions(data)
+        .iter()
+        .filter(|ty| match ty {
+            Type::Struct { name, .. } => {
+                !newtypes.contains(name.as_str()) && crate::maps::parse_map_ref(name).is_none()
+            }
+            _ => true,
+        })
+        .map(|ty| runtime_type(ty, data))
+        .collect()
+}
+
 /// Emits `TYPE_DEFINITIONS` and `type_registry()` into the generated model:
 /// the full list of shared type definitions serialized as JSON (same embedding
 /// pattern as `WsRequest::SCHEMA`), plus a helper that deserializes them into
 /// an `endpoint_libs::model::TypeRegistry` for `WebsocketServer::enable_mcp()`.
 pub fn dump_type_registry(data: &Data, mut writer: impl Write) -> eyre::Result<()> {
-    let types = shared_type_definitions(data);
+    let types = runtime_type_definitions(data);
     let serialized = serde_json::to_string_pretty(&types)?;
     let code = format!(
         r##"
@@ -576,8 +742,6 @@ pub fn type_registry() -> endpoint_libs::model::TypeRegistry {{
 
 #[cfg(test)]
 mod tests {
-    use regex::Regex;
-
     use super::*;
     use crate::definitions::{EndpointSchemaElement, RustGenConfig};
     use endpoint_libs::model::{EndpointSchema, Field};
@@ -613,6 +777,7 @@ mod tests {
         let role = Type::enum_("role", vec![EnumVariant::new("Admin", 1)]);
         Data {
             project_name: "api.example.com".into(),
+            spec: Default::default(),
             project_root: std::path::PathBuf::new(),
             output_dir: std::path::PathBuf::new(),
             services: vec![crate::definitions::GenService::new(
@@ -628,6 +793,7 @@ mod tests {
                         vec![Field::new("profile", Type::struct_ref("UserInfo"))],
                     )
                     .with_description("Fetches a user profile."),
+                    ..Default::default()
                 }],
             )],
             enums: vec![crate::definitions::EnumElement {
@@ -636,12 +802,150 @@ mod tests {
             }],
             structs: vec![crate::definitions::StructElement {
                 config: RustGenConfig::default(),
+                type_params: vec![],
                 inner: user_info,
             }],
+            unions: vec![],
+            newtypes: vec![],
             error_codes: vec![],
         }
     }
 
+    fn maintenance_notice(code: u32) -> crate::definitions::ServerEventElement {
+        crate::definitions::ServerEventElement {
+            service_name: "user".into(),
+            service_id: 1,
+            config: RustGenConfig::default(),
+            name: "MaintenanceNotice".into(),
+            code,
+            fields: vec![Field::new("starts_at", Type::TimeStampMs)],
+            roles: vec!["Role::Admin".into()],
+            description: "Announces a maintenance window.".into(),
+        }
+    }
+
+    #[test]
+    fn server_events_get_a_code_enum_and_ws_push_impls() {
+        let mut data = test_data();
+        data.services[0].events = vec![maintenance_notice(10900)];
+
+        let mut codes = Vec::new();
+        check_endpoint_codes(&data, &mut codes).unwrap();
+        let codes = String::from_utf8(codes).unwrap();
+        assert!(codes.contains("pub enum EnumServerEvent {"), "{codes}");
+        assert!(codes.contains("MaintenanceNotice = 10900"), "{codes}");
+
+        let mut pushes = Vec::new();
+        dump_server_events(&data, &mut pushes).unwrap();
+        let pushes = String::from_utf8(pushes).unwrap();
+        assert!(pushes.contains("pub trait WsPush"), "{pushes}");
+        assert!(pushes.contains("impl WsPush for MaintenanceNoticeEvent {"), "{pushes}");
+        assert!(pushes.contains("const METHOD_ID: u32 = 10900;"), "{pushes}");
+        assert!(pushes.contains("const ROLES: &[u32] = &[1];"), "{pushes}");
+
+        data.services[0].events = vec![maintenance_notice(10010)];
+        let err = check_endpoint_codes(&data, Vec::new()).unwrap_err().to_string();
+        assert!(
+            err.contains("event 'MaintenanceNotice' and endpoint 'UserGetProfile' both use code 10010"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn operational_metadata_becomes_consts_on_the_request_type() {
+        let mut data = test_data();
+        let endpoint = &mut data.services[0].endpoints[0];
+        endpoint.schema.roles = vec!["Role::Admin".into()];
+        endpoint.operational = ron::from_str(
+            r#"(rate_limits: {"Role::Admin": (requests: 10, per_seconds: 1)}, idempotent: true, cost: Some(2))"#,
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        write_operational_consts(endpoint, &data.enums, &mut out).unwrap();
+        let code = String::from_utf8(out).unwrap();
+        assert!(code.contains("impl UserGetProfileRequest {"), "{code}");
+        assert!(
+            code.contains("RATE_LIMITS: &'static [(u32, u32, u32)] = &[(1, 10, 1)];"),
+            "{code}"
+        );
+        assert!(code.contains("TIMEOUT_MS: Option<u64> = None;"), "{code}");
+        assert!(code.contains("IDEMPOTENT: bool = true;"), "{code}");
+        assert!(code.contains("COST: Option<u32> = Some(2);"), "{code}");
+
+        let mut out = Vec::new();
+        write_operational_consts(&test_data().services[0].endpoints[0], &data.enums, &mut out).unwrap();
+        assert!(out.is_empty(), "nothing declared, nothing emitted");
+    }
+
+    #[test]
+    fn the_mcp_section_rides_along_in_the_runtime_schema() {
+        let mut data = test_data();
+        let endpoint = &mut data.services[0].endpoints[0];
+        assert!(runtime_schema(endpoint, &test_data()).unwrap().meta.is_empty());
+
+        endpoint.mcp =
+            ron::from_str(r#"(name: Some("get_profile"), read_only: Some(true), usage: Some("Cheap."))"#).unwrap();
+        let schema = runtime_schema(endpoint, &test_data()).unwrap();
+        assert_eq!(schema.description, "Fetches a user profile.\n\nCheap.");
+        assert_eq!(
+            schema.meta.get("x-mcp"),
+            Some(&serde_json::json!({ "expose": true, "name": "get_profile", "read_only": true, "usage": "Cheap." }))
+        );
+        assert!(endpoint.schema.meta.is_empty(), "the documents keep the plain schema");
+    }
+
+    #[test]
+    fn newtypes_are_their_underlying_type_at_runtime() {
+        let mut data = test_data();
+        data.newtypes = vec![crate::definitions::NewtypeElement {
+            config: RustGenConfig::default(),
+            name: "UserId".into(),
+            ty: Type::Int64,
+            description: String::new(),
+        }];
+        let endpoint = &mut data.services[0].endpoints[0];
+        endpoint.schema.parameters = vec![Field::new("user_id", Type::struct_ref("UserId"))];
+        let endpoint = endpoint.clone();
+
+        let schema = runtime_schema(&endpoint, &data).unwrap();
+        assert_eq!(schema.parameters[0].ty, Type::Int64);
+
+        let types = runtime_type_definitions(&data);
+        assert!(
+            !types
+                .iter()
+                .any(|t| matches!(t, Type::Struct { name, .. } if name == "UserId"))
+        );
+        let mut registry = endpoint_libs::model::TypeRegistry::new();
+        registry.add_all(types.iter());
+        let input = schema.to_mcp_input_schema(&registry).unwrap();
+        assert_eq!(input["properties"]["userId"], serde_json::json!({ "type": "integer" }));
+    }
+
+    #[test]
+    fn maps_are_objects_of_their_value_type_at_runtime() {
+        let mut data = test_data();
+        let endpoint = &mut data.services[0].endpoints[0];
+        endpoint.schema.returns = vec![Field::new(
+            "balances",
+            Type::optional(Type::struct_ref("BTreeMap<String, f64>")),
+        )];
+        let endpoint = endpoint.clone();
+
+        let schema = runtime_schema(&endpoint, &data).unwrap();
+        assert_eq!(schema.returns[0].ty, Type::optional(Type::Object));
+        assert_eq!(
+            schema.returns[0].meta.get("x-map-value"),
+            Some(&serde_json::to_value(Type::Float64).unwrap())
+        );
+        assert!(
+            !runtime_type_definitions(&data)
+                .iter()
+                .any(|t| matches!(t, Type::Struct { name, .. } if name.contains("Map")))
+        );
+    }
+
     #[test]
     fn type_registry_dump_round_trips() {
         let data = test_data();
@@ -675,35 +979,78 @@ mod tests {
     }
 
     #[test]
-    fn test_extract_number_from_error_code() {
-        let re = Regex::new(r"=\s*(\d+)").unwrap();
-
-        // Test with newline between number and comma
-        let text1 = r#"    ///
-      LoginStep2 = 10003
-  ,"#;
-        let caps1 = re.captures(text1).expect("Should match");
-        let number1: u64 = caps1[1].parse().expect("Should parse as u64");
-        assert_eq!(number1, 10003);
-
-        // Test with spaces but no newline
-        let text2 = "Authorize = 10000,";
-        let caps2 = re.captures(text2).expect("Should match");
-        let number2: u64 = caps2[1].parse().expect("Should parse as u64");
-        assert_eq!(number2, 10000);
-
-        // Test with no spaces
-        let text3 = "SomeError=12345,";
-        let caps3 = re.captures(text3).expect("Should match");
-        let number3: u64 = caps3[1].parse().expect("Should parse as u64");
-        assert_eq!(number3, 12345);
-
-        // Test with multiple spaces
-        let text4 = r#"/// SQL R0019 UnauthorizedMessage
-    UnauthorizedMessage = 45349677
-, "#;
-        let caps4 = re.captures(text4).expect("Should match");
-        let number4: u64 = caps4[1].parse().expect("Should parse as u64");
-        assert_eq!(number4, 45349677);
+    fn generic_structs_keep_rust_generics_and_registry_gets_instances() {
+        let mut data = test_data();
+        let paged = crate::definitions::StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec!["T".into()],
+            inner: Type::struct_(
+                "Paged",
+                vec![
+                    Field::new("items", Type::vec(Type::struct_ref("T"))),
+                    Field::new("total", Type::Int64),
+                ],
+            ),
+        };
+        let decl = paged.to_rust_decl(false, false);
+        assert!(decl.contains("pub struct Paged<T>"), "{decl}");
+        assert!(decl.contains("Vec<T>"), "{decl}");
+
+        data.structs.push(paged);
+        data.services[0].endpoints[0].schema.returns = vec![Field::new("page", Type::struct_ref("Paged<UserInfo>"))];
+
+        let types = shared_type_definitions(&data);
+        let names: Vec<_> = types
+            .iter()
+            .filter_map(|t| match t {
+                Type::Struct { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["UserInfo", "Paged<UserInfo>"]);
+    }
+
+    #[test]
+    fn model_imports_follow_the_emitted_derives() {
+        let plain: BTreeSet<String> = crate::rust_item::ENUM_DERIVES.iter().map(|d| d.to_string()).collect();
+        let imports = model_imports(&plain, &BTreeSet::new());
+        assert!(
+            imports.contains("use strum_macros::{Display, EnumString};"),
+            "{imports}"
+        );
+        assert!(
+            !imports.contains("schemars") && !imports.contains("worktable") && !imports.contains("collections"),
+            "{imports}"
+        );
+
+        let mut schema = plain.clone();
+        schema.insert("JsonSchema".into());
+        assert!(model_imports(&schema, &BTreeSet::new()).contains("use schemars::{schema_for, JsonSchema};"));
+
+        let maps = BTreeSet::from(["HashMap".to_string()]);
+        assert!(model_imports(&plain, &maps).contains("use std::collections::HashMap;"));
+    }
+
+    #[test]
+    fn error_code_enum_is_sorted_by_numeric_code() {
+        let e = Type::enum_(
+            "ErrorCode",
+            vec![
+                EnumVariant::new_with_description("UnauthorizedMessage", "SQL R0019 = 1", 45349677),
+                EnumVariant::new("LoginStep2", 10003),
+                EnumVariant::new("Authorize", 10000),
+            ],
+        );
+        let decl = e.to_rust_decl(false, true);
+        let positions: Vec<usize> = [
+            "Authorize = 10000",
+            "LoginStep2 = 10003",
+            "UnauthorizedMessage = 45349677",
+        ]
+        .iter()
+        .map(|v| decl.find(v).unwrap_or_else(|| panic!("{v} missing:\n{decl}")))
+        .collect();
+        assert!(positions.is_sorted(), "{decl}");
+        assert!(decl.contains("pub enum EnumErrorCode {"), "{decl}");
     }
 }
@@ -0,0 +1,485 @@
+//! One intermediate representation for every item written into `model.rs`.
+//!
+//! Shared structs and enums, inline endpoint types, tagged unions and newtypes
+//! are all lowered to a [`RustItem`] and rendered by [`RustItem::render`], so
+//! naming, field attributes, variant order and derives are decided in one
+//! place instead of once per [`ToRust`](crate::rust::ToRust) impl.
+
+use convert_case::{Case, Casing};
+use endpoint_libs::model::{EnumVariant, Field, Type};
+
+use crate::definitions::RustGenConfig;
+use crate::rust::ToRust;
+
+/// Derives of a plain generated struct.
+pub const STRUCT_DERIVES: &[&str] = &["Serialize", "Deserialize", "Debug", "Clone"];
+
+/// Derives of a plain generated enum.
+pub const ENUM_DERIVES: &[&str] = &[
+    "Debug",
+    "Clone",
+    "Copy",
+    "Serialize",
+    "Deserialize",
+    "FromPrimitive",
+    "PartialEq",
+    "Eq",
+    "PartialOrd",
+    "Ord",
+    "EnumString",
+    "Display",
+    "Hash",
+];
+
+/// Derives of an enum stored in a worktable.
+pub const WORKTABLE_ENUM_DERIVES: &[&str] = &[
+    "MemStat",
+    "Archive",
+    "Clone",
+    "Copy",
+    "Debug",
+    "Display",
+    "PartialEq",
+    "PartialOrd",
+    "Eq",
+    "Hash",
+    "Ord",
+    "EnumString",
+    "rkyv::Deserialize",
+    "rkyv::Serialize",
+    "serde::Serialize",
+    "serde::Deserialize",
+];
+
+/// Attributes of any item stored in a worktable: archived values compare
+/// against live ones, and print.
+pub const WORKTABLE_RKYV_ATTR: &str = "#[rkyv(compare(PartialEq), derive(Debug))]";
+
+/// Derives of a struct stored in a worktable. `Copy` and the total-order
+/// derives are only added when every field has them.
+pub fn worktable_struct_derives(fields: &[Field]) -> Vec<String> {
+    let copy = fields.iter().all(|f| is_copy(&f.ty));
+    let total = fields.iter().all(|f| is_totally_ordered(&f.ty));
+    let mut derives = vec!["MemStat", "SizeMeasure", "Archive", "Clone"];
+    if copy {
+        derives.push("Copy");
+    }
+    derives.extend(["Debug", "PartialEq"]);
+    if total {
+        derives.extend(["Eq", "Hash", "PartialOrd", "Ord"]);
+    }
+    derives.extend([
+        "rkyv::Deserialize",
+        "rkyv::Serialize",
+        "serde::Serialize",
+        "serde::Deserialize",
+    ]);
+    to_strings(&derives)
+}
+
+/// Whether the generated type for `ty` is `Copy`. Every generated enum is;
+/// structs are not assumed to be.
+fn is_copy(ty: &Type) -> bool {
+    match ty {
+        Type::Int32 | Type::Int64 | Type::UInt32 | Type::Float64 | Type::TimeStampMs | Type::Boolean | Type::UUID => {
+            true
+        }
+        Type::EnumRef { .. } => true,
+        Type::Optional(inner) => is_copy(inner),
+        _ => false,
+    }
+}
+
+/// Whether the generated type for `ty` has `Eq`, `Hash` and `Ord`.
+fn is_totally_ordered(ty: &Type) -> bool {
+    match ty {
+        Type::Int32
+        | Type::Int64
+        | Type::UInt32
+        | Type::TimeStampMs
+        | Type::Boolean
+        | Type::UUID
+        | Type::String
+        | Type::Bytea
+        | Type::EnumRef { .. } => true,
+        Type::Optional(inner) | Type::Vec(inner) => is_totally_ordered(inner),
+        _ => false,
+    }
+}
+
+/// The Rust name of a struct: Pascal case, like every other generated item.
+pub fn struct_name(name: &str) -> String {
+    name.to_case(Case::Pascal)
+}
+
+/// The Rust name of an enum, with the `Enum` prefix if `prefixed`.
+pub fn enum_name(name: &str, prefixed: bool) -> String {
+    let name = name.to_case(Case::Pascal);
+    if prefixed { format!("Enum{name}") } else { name }
+}
+
+/// The Rust name of an enum variant. Names ending in a lowercase letter are
+/// Pascal-cased; anything else (`USD`, `V2`) is kept as written.
+pub fn enum_variant_name(name: &str) -> String {
+    if name.chars().last().is_some_and(|c| c.is_lowercase()) {
+        name.to_case(Case::Pascal)
+    } else {
+        name.to_string()
+    }
+}
+
+/// A generated item before rendering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustItem {
+    pub doc: String,
+    pub derives: Vec<String>,
+    /// Item attributes after the derive, e.g. `#[serde(transparent)]`.
+    pub attrs: Vec<String>,
+    pub name: String,
+    pub generics: Vec<String>,
+    pub kind: RustItemKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RustItemKind {
+    Struct(Vec<RustField>),
+    /// A tuple struct; its fields' names are unused.
+    Tuple(Vec<RustField>),
+    Enum(Vec<RustVariant>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustField {
+    pub attrs: Vec<String>,
+    pub name: String,
+    pub ty: String,
+}
+
+/// An enum variant: a unit variant with a discriminant, or a struct variant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RustVariant {
+    pub doc: String,
+    pub name: String,
+    pub discriminant: Option<i64>,
+    pub fields: Vec<RustField>,
+}
+
+impl RustField {
+    /// A field with the attributes its type needs: `#[serde(default)]` for
+    /// options and a `with` adapter for blockchain types.
+    pub fn from_field(field: &Field, serde_with: bool) -> Self {
+        let mut attrs = vec![];
+        if matches!(&field.ty, Type::Optional(_)) {
+            attrs.push("#[serde(default)]".to_string());
+        }
+        let with = crate::serde_adapters::with_attribute(&field.ty, serde_with);
+        if !with.is_empty() {
+            attrs.push(with);
+        }
+        Self {
+            attrs,
+            name: field.name.clone(),
+            ty: field.ty.to_rust_ref(serde_with),
+        }
+    }
+}
+
+impl RustItem {
+    /// A struct with the default struct derives and camelCase fields.
+    pub fn struct_(name: &str, fields: &[Field], serde_with: bool) -> Self {
+        Self {
+            doc: String::new(),
+            derives: to_strings(STRUCT_DERIVES),
+            attrs: vec![r#"#[serde(rename_all = "camelCase")]"#.to_string()],
+            name: struct_name(name),
+            generics: vec![],
+            kind: RustItemKind::Struct(fields.iter().map(|f| RustField::from_field(f, serde_with)).collect()),
+        }
+    }
+
+    /// A fieldless enum with the default enum derives, its variants in
+    /// ascending order of value.
+    pub fn unit_enum(name: &str, prefixed: bool, variants: &[EnumVariant]) -> Self {
+        let mut variants: Vec<RustVariant> = variants
+            .iter()
+            .map(|v| RustVariant {
+                doc: v.description.clone(),
+                name: enum_variant_name(&v.name),
+                discriminant: Some(v.value),
+                fields: vec![],
+            })
+            .collect();
+        variants.sort_by_key(|v| v.discriminant);
+        Self {
+            doc: String::new(),
+            derives: to_strings(ENUM_DERIVES),
+            attrs: vec![],
+            name: enum_name(name, prefixed),
+            generics: vec![],
+            kind: RustItemKind::Enum(variants),
+        }
+    }
+
+    /// The item for a `Type::Struct` or `Type::Enum`, or `None` for types that
+    /// are only ever referenced.
+    pub fn from_type(ty: &Type, serde_with: bool) -> Option<Self> {
+        match ty {
+            Type::Struct { name, fields } => Some(Self::struct_(name, fields, serde_with)),
+            // Inline enums have no config, and their references are prefixed.
+            Type::Enum { name, variants } => Some(Self::unit_enum(name, true, variants)),
+            _ => None,
+        }
+    }
+
+    /// Applies the parts of `config` every item kind shares.
+    pub fn with_config(mut self, config: &RustGenConfig) -> Self {
+        if config.json_schema_gen {
+            self.add_derive("JsonSchema");
+        }
+        self.with_extras(config)
+    }
+
+    /// Applies only the configured derives and attributes. Endpoint request
+    /// and response types take these from their endpoint's config, but not
+    /// `json_schema_gen`, which has only ever covered shared definitions.
+    pub fn with_extras(mut self, config: &RustGenConfig) -> Self {
+        for derive in &config.derives {
+            self.add_derive(derive);
+        }
+        for attr in &config.attributes {
+            let attr = attribute(attr);
+            if !self.attrs.contains(&attr) {
+                self.attrs.push(attr);
+            }
+        }
+        if !config.field_attributes.is_empty() {
+            for field in self.fields_mut() {
+                for attr in config.field_attributes.get(&field.name).into_iter().flatten() {
+                    field.attrs.push(attribute(attr));
+                }
+            }
+        }
+        self
+    }
+
+    fn add_derive(&mut self, derive: &str) {
+        if !self.derives.iter().any(|d| d == derive) {
+            self.derives.push(derive.to_string());
+        }
+    }
+
+    /// Every named field: a struct's, or those of every struct variant.
+    fn fields_mut(&mut self) -> Vec<&mut RustField> {
+        match &mut self.kind {
+            RustItemKind::Struct(fields) => fields.iter_mut().collect(),
+            RustItemKind::Tuple(_) => vec![],
+            RustItemKind::Enum(variants) => variants.iter_mut().flat_map(|v| v.fields.iter_mut()).collect(),
+        }
+    }
+
+    /// Rust source for the item. Without `with_derives` the derives and item
+    /// attributes are left off, leaving the bare declaration.
+    pub fn render(&self, with_derives: bool) -> String {
+        let mut out = doc_comment(&self.doc, "");
+        if with_derives {
+            if !self.derives.is_empty() {
+                out.push_str(&format!("#[derive({})]\n", self.derives.join(", ")));
+            }
+            for attr in &self.attrs {
+                out.push_str(attr);
+                out.push('\n');
+            }
+        }
+        let generics = if self.generics.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", self.generics.join(", "))
+        };
+        match &self.kind {
+            RustItemKind::Struct(fields) => {
+                out.push_str(&format!("pub struct {}{generics} {{\n", self.name));
+                for field in fields {
+                    out.push_str(&format!(
+                        "    {}pub {}: {},\n",
+                        attrs_prefix(&field.attrs),
+                        field.name,
+                        field.ty
+                    ));
+                }
+                out.push_str("}\n");
+            }
+            RustItemKind::Tuple(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|f| format!("{}pub {}", attrs_prefix(&f.attrs), f.ty))
+                    .collect();
+                out.push_str(&format!("pub struct {}{generics}({});\n", self.name, fields.join(", ")));
+            }
+            RustItemKind::Enum(variants) => {
+                out.push_str(&format!("pub enum {}{generics} {{\n", self.name));
+                for variant in variants {
+                    out.push_str(&doc_comment(&variant.doc, "    "));
+                    out.push_str(&format!("    {}", variant.name));
+                    if let Some(value) = variant.discriminant {
+                        out.push_str(&format!(" = {value}"));
+                    }
+                    if !variant.fields.is_empty() {
+                        let fields: Vec<String> = variant
+                            .fields
+                            .iter()
+                            .map(|f| format!("{}{}: {}", attrs_prefix(&f.attrs), f.name, f.ty))
+                            .collect();
+                        out.push_str(&format!(" {{ {} }}", fields.join(", ")));
+                    }
+                    out.push_str(",\n");
+                }
+                out.push_str("}\n");
+            }
+        }
+        out
+    }
+}
+
+/// An attribute as configured, `serde(default)`, as source: `#[serde(default)]`.
+/// Already bracketed text is kept as is.
+fn attribute(text: &str) -> String {
+    let text = text.trim();
+    if text.starts_with("#[") {
+        text.to_string()
+    } else {
+        format!("#[{text}]")
+    }
+}
+
+pub(crate) fn to_strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+/// A `///` line per line of `doc`. A blank doc emits nothing: an empty doc
+/// comment trips clippy::empty_docs downstream.
+fn doc_comment(doc: &str, indent: &str) -> String {
+    if doc.trim().is_empty() {
+        return String::new();
+    }
+    doc.lines().map(|line| format!("{indent}/// {line}\n")).collect()
+}
+
+fn attrs_prefix(attrs: &[String]) -> String {
+    attrs.iter().map(|attr| format!("{attr} ")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enum_variants_are_sorted_by_value_not_by_text() {
+        let item = RustItem::unit_enum(
+            "side",
+            false,
+            &[
+                EnumVariant::new("Sell", 100),
+                EnumVariant::new("Buy", 9),
+                EnumVariant::new_with_description("Hold", "Not = 1 either.", 10),
+            ],
+        );
+        let decl = item.render(false);
+        let order: Vec<usize> = ["Buy = 9", "Hold = 10", "Sell = 100"]
+            .iter()
+            .map(|v| decl.find(v).unwrap_or_else(|| panic!("{v} missing:\n{decl}")))
+            .collect();
+        assert!(order.is_sorted(), "{decl}");
+        assert!(decl.starts_with("pub enum Side {"), "{decl}");
+    }
+
+    #[test]
+    fn types_and_elements_name_items_the_same_way() {
+        let inline = Type::struct_("user_info", vec![Field::new("user_id", Type::Int64)]);
+        let item = RustItem::from_type(&inline, false).unwrap();
+        assert_eq!(item.name, inline.to_rust_ref(false));
+        assert_eq!(item.name, "UserInfo");
+
+        let inline_enum = Type::enum_("side", vec![]);
+        assert_eq!(RustItem::from_type(&inline_enum, false).unwrap().name, "EnumSide");
+        assert_eq!(RustItem::unit_enum("side", false, &[]).name, "Side");
+        assert_eq!(RustItem::from_type(&Type::Int64, false), None);
+    }
+
+    #[test]
+    fn render_puts_derives_before_attributes_and_skips_them_on_request() {
+        let item = RustItem::struct_("Quote", &[Field::new("bid", Type::optional(Type::Float64))], false).with_config(
+            &RustGenConfig {
+                json_schema_gen: true,
+                ..Default::default()
+            },
+        );
+        let decl = item.render(true);
+        assert_eq!(
+            decl,
+            "#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]\n\
+             #[serde(rename_all = \"camelCase\")]\n\
+             pub struct Quote {\n    #[serde(default)] pub bid: Option<f64>,\n}\n"
+        );
+        assert!(item.render(false).starts_with("pub struct Quote {"));
+    }
+
+    #[test]
+    fn configured_derives_and_attributes_are_added_once() {
+        let config = RustGenConfig {
+            derives: vec!["PartialEq".into(), "Clone".into()],
+            attributes: vec!["serde(deny_unknown_fields)".into()],
+            field_attributes: [("bid".to_string(), vec!["#[serde(alias = \"b\")]".to_string()])].into(),
+            ..Default::default()
+        };
+        let decl = RustItem::struct_("Quote", &[Field::new("bid", Type::Float64)], false)
+            .with_config(&config)
+            .render(true);
+        assert!(
+            decl.starts_with(
+                "#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]\n\
+                 #[serde(rename_all = \"camelCase\")]\n\
+                 #[serde(deny_unknown_fields)]\n"
+            ),
+            "{decl}"
+        );
+        assert!(decl.contains("#[serde(alias = \"b\")] pub bid: f64"), "{decl}");
+    }
+
+    #[test]
+    fn worktable_structs_only_derive_what_every_field_has() {
+        let plain = [
+            Field::new("id", Type::Int64),
+            Field::new("side", Type::enum_ref("Side", true)),
+        ];
+        let derives = worktable_struct_derives(&plain);
+        assert!(derives.contains(&"Copy".to_string()), "{derives:?}");
+        assert!(derives.contains(&"Ord".to_string()), "{derives:?}");
+
+        let owned = [
+            Field::new("id", Type::Int64),
+            Field::new("tags", Type::vec(Type::String)),
+        ];
+        let derives = worktable_struct_derives(&owned);
+        assert!(!derives.contains(&"Copy".to_string()), "{derives:?}");
+        assert!(derives.contains(&"Hash".to_string()), "{derives:?}");
+
+        let element = crate::definitions::StructElement {
+            config: RustGenConfig {
+                worktable_support: true,
+                ..Default::default()
+            },
+            type_params: vec![],
+            inner: Type::struct_("Fill", vec![Field::new("price", Type::Float64)]),
+        };
+        let decl = element.rust_item(false).render(true);
+        assert!(
+            decl.starts_with(
+                "#[derive(MemStat, SizeMeasure, Archive, Clone, Copy, Debug, PartialEq, rkyv::Deserialize, \
+                 rkyv::Serialize, serde::Serialize, serde::Deserialize)]\n\
+                 #[rkyv(compare(PartialEq), derive(Debug))]\n\
+                 #[serde(rename_all = \"camelCase\")]\n"
+            ),
+            "{decl}"
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! `#[serde(with = "...")]` adapters for blockchain types, however nested.
+//!
+//! `BlockchainDecimal` always needs `rust_decimal::serde::str`, and with
+//! `serde_with` the raw `Address`/`H256` need endpoint-libs'
+//! `WithBlockchainAddress`/`WithBlockchainTransactionHash`. A `with` module
+//! only applies to the field's exact type, so `Option<Decimal>` or
+//! `Vec<Option<Address>>` need their own. One is generated into `model.rs` for
+//! every wrapper in use, named after the nesting (`WithOptionVecBlockchainDecimal`)
+//! and defined through the adapter one level in, so every nesting serializes
+//! elements exactly as the bare type does.
+//!
+//! The module bodies are the `serde_adapters/*.rs.in` templates, which the
+//! tests below compile as well.
+
+use std::collections::BTreeMap;
+
+use endpoint_libs::model::Type;
+
+use crate::rust::ToRust;
+
+const ELEMENT: &str = include_str!("serde_adapters/element.rs.in");
+const OPTION: &str = include_str!("serde_adapters/option.rs.in");
+const VEC: &str = include_str!("serde_adapters/vec.rs.in");
+
+/// The name a type contributes to an adapter module name, if it needs one.
+fn adapted_name(ty: &Type, serde_with: bool) -> Option<String> {
+    match ty {
+        Type::BlockchainDecimal => Some("BlockchainDecimal".into()),
+        Type::BlockchainAddress if serde_with => Some("BlockchainAddress".into()),
+        Type::BlockchainTransactionHash if serde_with => Some("BlockchainTransactionHash".into()),
+        Type::Optional(inner) => Some(format!("Option{}", adapted_name(inner, serde_with)?)),
+        Type::Vec(inner) => Some(format!("Vec{}", adapted_name(inner, serde_with)?)),
+        _ => None,
+    }
+}
+
+/// The `with` module a field of type `ty` needs, or `None` if its own
+/// `Serialize` impl already gives the right encoding.
+pub fn adapter(ty: &Type, serde_with: bool) -> Option<String> {
+    match ty {
+        Type::BlockchainDecimal => Some("rust_decimal::serde::str".into()),
+        _ => Some(format!("With{}", adapted_name(ty, serde_with)?)),
+    }
+}
+
+/// The field attribute for [`adapter`], or an empty string.
+pub fn with_attribute(ty: &Type, serde_with: bool) -> String {
+    match adapter(ty, serde_with) {
+        Some(module) => format!("#[serde(with = \"{module}\")]"),
+        None => String::new(),
+    }
+}
+
+/// Adds the module for `ty`, and for every wrapper inside it, to `out`.
+fn collect(ty: &Type, serde_with: bool, out: &mut BTreeMap<String, String>) {
+    let (inner, body) = match ty {
+        Type::Optional(inner) => (inner, OPTION),
+        Type::Vec(inner) => (inner, VEC),
+        Type::Struct { fields, .. } => {
+            for field in fields {
+                collect(&field.ty, serde_with, out);
+            }
+            return;
+        }
+        _ => return,
+    };
+    let (Some(module), Some(inner_module)) = (adapter(ty, serde_with), adapter(inner, serde_with)) else {
+        return;
+    };
+    // Adapters one level in are siblings in `model.rs`.
+    let inner_path = if inner_module.contains("::") {
+        inner_module
+    } else {
+        format!("super::{inner_module}")
+    };
+    out.entry(module.clone()).or_insert_with(|| {
+        format!(
+            "#[allow(non_snake_case)]\npub mod {module} {{\nuse super::*;\nuse {inner_path} as inner;\ntype Inner = {};\n{ELEMENT}\n{body}}}\n",
+            inner.to_rust_ref(serde_with)
+        )
+    });
+    collect(inner, serde_with, out);
+}
+
+/// The source of every adapter module the given types' fields need, keyed by
+/// module name. Each entry is the type and the `serde_with` it is emitted with.
+pub fn helper_modules<'a>(types: impl IntoIterator<Item = (&'a Type, bool)>) -> BTreeMap<String, String> {
+    let mut modules = BTreeMap::new();
+    for (ty, serde_with) in types {
+        collect(ty, serde_with, &mut modules);
+    }
+    modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use endpoint_libs::model::Field;
+    use rust_decimal::Decimal;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::json;
+    use std::str::FromStr;
+
+    // What `model.rs` gets for these types, built from the same templates.
+    #[allow(non_snake_case)]
+    mod WithVecBlockchainDecimal {
+        use super::*;
+        use rust_decimal::serde::str as inner;
+        type Inner = Decimal;
+        include!("serde_adapters/element.rs.in");
+        include!("serde_adapters/vec.rs.in");
+    }
+    #[allow(non_snake_case)]
+    mod WithOptionVecBlockchainDecimal {
+        use super::WithVecBlockchainDecimal as inner;
+        use super::*;
+        type Inner = Vec<Decimal>;
+        include!("serde_adapters/element.rs.in");
+        include!("serde_adapters/option.rs.in");
+    }
+    #[allow(non_snake_case)]
+    mod WithOptionBlockchainTransactionHash {
+        use super::*;
+        use endpoint_libs::libs::types::WithBlockchainTransactionHash as inner;
+        type Inner = endpoint_libs::libs::types::H256;
+        include!("serde_adapters/element.rs.in");
+        include!("serde_adapters/option.rs.in");
+    }
+    #[allow(non_snake_case)]
+    mod WithVecBlockchainAddress {
+        use super::*;
+        use endpoint_libs::libs::types::WithBlockchainAddress as inner;
+        type Inner = endpoint_libs::libs::types::Address;
+        include!("serde_adapters/element.rs.in");
+        include!("serde_adapters/vec.rs.in");
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Bare {
+        #[serde(with = "rust_decimal::serde::str")]
+        price: Decimal,
+        #[serde(with = "endpoint_libs::libs::types::WithBlockchainTransactionHash")]
+        hash: endpoint_libs::libs::types::H256,
+        #[serde(with = "endpoint_libs::libs::types::WithBlockchainAddress")]
+        address: endpoint_libs::libs::types::Address,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Nested {
+        #[serde(default, with = "WithOptionVecBlockchainDecimal")]
+        prices: Option<Vec<Decimal>>,
+        #[serde(default, with = "WithOptionBlockchainTransactionHash")]
+        hash: Option<endpoint_libs::libs::types::H256>,
+        #[serde(with = "WithVecBlockchainAddress")]
+        addresses: Vec<endpoint_libs::libs::types::Address>,
+    }
+
+    #[test]
+    fn nested_blockchain_types_encode_like_the_bare_type() {
+        let price = Decimal::from_str("1.10").unwrap();
+        let hash = endpoint_libs::libs::types::H256::repeat_byte(0xab);
+        let address = endpoint_libs::libs::types::Address::repeat_byte(0xcd);
+        let bare = serde_json::to_value(Bare { price, hash, address }).unwrap();
+
+        let nested = Nested {
+            prices: Some(vec![price, price]),
+            hash: Some(hash),
+            addresses: vec![address],
+        };
+        let encoded = serde_json::to_value(&nested).unwrap();
+        assert_eq!(encoded["prices"], json!([bare["price"], bare["price"]]));
+        assert_eq!(encoded["hash"], bare["hash"]);
+        assert_eq!(encoded["addresses"], json!([bare["address"]]));
+
+        // Addresses are left out: endpoint-libs cannot parse its own encoding
+        // of them back, bare or not.
+        let decoded: Nested = serde_json::from_value(json!({
+            "prices": encoded["prices"],
+            "hash": encoded["hash"],
+            "addresses": [],
+        }))
+        .unwrap();
+        assert_eq!(decoded.prices, nested.prices);
+        assert_eq!(decoded.hash, nested.hash);
+
+        let empty: Nested = serde_json::from_value(json!({ "prices": null, "addresses": [] })).unwrap();
+        assert_eq!(empty.prices, None);
+        assert_eq!(empty.hash, None);
+    }
+
+    #[test]
+    fn adapters_cover_every_nesting_and_only_blockchain_types() {
+        let decimals = Type::optional(Type::vec(Type::BlockchainDecimal));
+        assert_eq!(
+            with_attribute(&decimals, false),
+            "#[serde(with = \"WithOptionVecBlockchainDecimal\")]"
+        );
+        assert_eq!(
+            adapter(&Type::BlockchainDecimal, false).as_deref(),
+            Some("rust_decimal::serde::str")
+        );
+        assert_eq!(adapter(&Type::optional(Type::BlockchainAddress), false), None);
+        assert_eq!(
+            adapter(&Type::optional(Type::BlockchainAddress), true).as_deref(),
+            Some("WithOptionBlockchainAddress")
+        );
+        assert_eq!(adapter(&Type::vec(Type::Int64), true), None);
+
+        let owner = Type::struct_("Quote", vec![Field::new("prices", decimals)]);
+        let modules = helper_modules([(&owner, false)]);
+        assert_eq!(
+            modules.keys().collect::<Vec<_>>(),
+            ["WithOptionVecBlockchainDecimal", "WithVecBlockchainDecimal"]
+        );
+        let outer = &modules["WithOptionVecBlockchainDecimal"];
+        assert!(
+            outer.contains("use super::WithVecBlockchainDecimal as inner;"),
+            "{outer}"
+        );
+        assert!(outer.contains("type Inner = Vec<Decimal>;"), "{outer}");
+        assert!(modules["WithVecBlockchainDecimal"].contains("use rust_decimal::serde::str as inner;"));
+    }
+}
@@ -8,17 +8,19 @@
 
 use std::collections::BTreeMap;
 
+use convert_case::{Case, Casing};
 use endpoint_libs::model::{EndpointSchema, SchemaComponents, TypeRegistry};
 use eyre::{Result, WrapErr};
 use serde_json::{Value, json};
 
-use crate::definitions::GenService;
+use crate::definitions::{GenService, NewtypeElement, Operational, TaggedUnionElement};
 use crate::docs::Data;
 
 /// Name of the shared error payload schema, referenced from both documents.
 pub const ERROR_ENVELOPE: &str = "ErrorEnvelope";
 
-/// Builds the type registry over every struct, enum and endpoint in the project.
+/// Builds the type registry over every struct, enum, endpoint and event in the
+/// project.
 pub fn build_registry(data: &Data) -> TypeRegistry {
     let mut registry = TypeRegistry::new();
     registry.add_all(crate::rust::shared_type_definitions(data).iter());
@@ -26,6 +28,9 @@ pub fn build_registry(data: &Data) -> TypeRegistry {
         for endpoint in &service.endpoints {
             registry.add_endpoint(&endpoint.schema);
         }
+        for event in &service.events {
+            registry.add_endpoint(&event.endpoint_schema());
+        }
     }
     registry
 }
@@ -34,7 +39,8 @@ pub fn build_registry(data: &Data) -> TypeRegistry {
 ///
 /// Filtering is per endpoint, not per service: a service with a mix keeps only
 /// its frontend-facing operations. A service left with nothing is dropped
-/// entirely, so neither document carries an empty channel or tag.
+/// entirely, so neither document carries an empty channel or tag. Events are
+/// kept whatever the filter: every client may receive them.
 pub fn visible_services(data: &Data, public_only: bool) -> Vec<GenService> {
     data.services
         .iter()
@@ -45,7 +51,7 @@ pub fn visible_services(data: &Data, public_only: bool) -> Vec<GenService> {
                 .filter(|e| !public_only || e.frontend_facing)
                 .cloned()
                 .collect();
-            if endpoints.is_empty() {
+            if endpoints.is_empty() && service.events.is_empty() {
                 return None;
             }
             let mut service = service.clone();
@@ -63,9 +69,14 @@ pub fn all_endpoints(services: &[GenService]) -> Vec<EndpointSchema> {
         .collect()
 }
 
-/// Collects shared components for a set of services.
+/// Collects shared components for a set of services, events included.
 pub fn collect_components(services: &[GenService], registry: &TypeRegistry) -> Result<SchemaComponents> {
-    let endpoints = all_endpoints(services);
+    let mut endpoints = all_endpoints(services);
+    endpoints.extend(
+        services
+            .iter()
+            .flat_map(|s| s.events.iter().map(|e| e.endpoint_schema())),
+    );
     SchemaComponents::collect(&endpoints, registry).wrap_err("collecting shared schema components")
 }
 
@@ -80,6 +91,199 @@ pub fn document_schemas(components: &SchemaComponents) -> BTreeMap<String, Value
     schemas
 }
 
+/// Component name for a generic instantiation: `Paged<Position>` becomes
+/// `Paged_Position`, `Pair<A, Paged<B>>` becomes `Pair_A_Paged_B`.
+///
+/// `<`, `>` and `,` are not valid in component keys, so specs cannot use the
+/// Rust spelling the way `model.rs` does; see [`crate::generics`].
+pub fn monomorphised_name(name: &str) -> String {
+    name.split(|c: char| matches!(c, '<' | '>' | ',') || c.is_whitespace())
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| segment.to_case(Case::Pascal))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Maps the definition name endpoint-libs gives each generic instantiation
+/// (its written name, Pascal-cased) to its [`monomorphised_name`].
+pub fn instance_names(data: &Data) -> BTreeMap<String, String> {
+    crate::generics::instantiations(data)
+        .iter()
+        .filter_map(|instance| match instance {
+            endpoint_libs::model::Type::Struct { name, .. } => {
+                Some((name.to_case(Case::Pascal), monomorphised_name(name)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Renames generic instances throughout a document: keys of `schemas` and
+/// `$defs` maps, and the last segment of every `$ref` pointing at them.
+pub fn rename_instances(value: &mut Value, names: &BTreeMap<String, String>) {
+    if names.is_empty() {
+        return;
+    }
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if key == "$ref"
+                    && let Value::String(target) = child
+                    && let Some((prefix, def)) = target.rsplit_once('/')
+                    && let Some(renamed) = names.get(def)
+                {
+                    *target = format!("{prefix}/{renamed}");
+                    continue;
+                }
+                if (key == "schemas" || key == "$defs")
+                    && let Value::Object(defs) = child
+                {
+                    *defs = std::mem::take(defs)
+                        .into_iter()
+                        .map(|(def, schema)| (names.get(&def).cloned().unwrap_or(def), schema))
+                        .collect();
+                }
+                rename_instances(child, names);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                rename_instances(item, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Gives every stand-in struct in `value` its real schema and name: tagged
+/// unions, newtypes and maps are registered as placeholder structs, and
+/// generic instances under their Rust spelling. Used on whole documents and on
+/// self-contained MCP tool schemas alike.
+pub fn resolve_stand_ins(value: &mut Value, data: &Data) {
+    apply_tagged_unions(value, &data.unions);
+    apply_newtypes(value, &data.newtypes);
+    crate::maps::inline_maps(value, data);
+    rename_instances(value, &instance_names(data));
+}
+
+/// Calls `patch` on every `schemas` or `$defs` map in `value`, with the prefix
+/// a `$ref` into that map uses.
+fn for_each_definitions_map(value: &mut Value, patch: &mut impl FnMut(&mut serde_json::Map<String, Value>, &str)) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let prefix = match key.as_str() {
+                    "schemas" => endpoint_libs::model::COMPONENTS_SCHEMAS_PREFIX,
+                    "$defs" => "#/$defs/",
+                    _ => "",
+                };
+                if !prefix.is_empty()
+                    && let Value::Object(defs) = child
+                {
+                    patch(defs, prefix);
+                }
+                for_each_definitions_map(child, patch);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                for_each_definitions_map(item, patch);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replaces each tagged union's placeholder schema with a `oneOf` over its
+/// variants plus an OpenAPI `discriminator`, and adds the tag property to every
+/// variant schema.
+///
+/// The tag is also a `const` in each variant, which keeps the `oneOf`
+/// unambiguous for plain JSON Schema validators that ignore `discriminator`.
+pub fn apply_tagged_unions(value: &mut Value, unions: &[TaggedUnionElement]) {
+    if unions.is_empty() {
+        return;
+    }
+    for_each_definitions_map(value, &mut |defs, prefix| {
+        for union in unions {
+            patch_tagged_union(defs, union, prefix);
+        }
+    });
+}
+
+/// Replaces each newtype's placeholder schema with its underlying type's,
+/// keeping the newtype's name as the component name and `title`.
+pub fn apply_newtypes(value: &mut Value, newtypes: &[NewtypeElement]) {
+    if newtypes.is_empty() {
+        return;
+    }
+    for_each_definitions_map(value, &mut |defs, _| {
+        for newtype in newtypes {
+            let name = newtype.name.to_case(Case::Pascal);
+            let Some(placeholder) = defs.get(&name) else {
+                continue;
+            };
+            let mut schema = placeholder["properties"]["value"].clone();
+            // The placeholder's object schema expresses Optional by leaving
+            // `value` out of `required`; standalone, it has to be nullable.
+            if matches!(newtype.ty, endpoint_libs::model::Type::Optional(_)) {
+                schema = json!({ "anyOf": [schema, { "type": "null" }] });
+            }
+            // Keywords next to a `$ref` are ignored by much 2020-12 tooling.
+            if let Value::Object(map) = &mut schema
+                && !map.contains_key("$ref")
+            {
+                map.insert("title".into(), json!(name));
+                if !newtype.description.is_empty() {
+                    map.insert("description".into(), json!(newtype.description));
+                }
+            }
+            defs.insert(name, schema);
+        }
+    });
+}
+
+fn patch_tagged_union(defs: &mut serde_json::Map<String, Value>, union: &TaggedUnionElement, prefix: &str) {
+    let name = union.name.to_case(Case::Pascal);
+    if !defs.contains_key(&name) {
+        return;
+    }
+    // As written, like the `#[serde(tag)]` of the Rust enum: the tag is not
+    // a field, so `rename_all_fields` does not camelCase it.
+    let tag = union.tag.clone();
+
+    let mut one_of = vec![];
+    let mut mapping = serde_json::Map::new();
+    for variant in &union.variants {
+        let def = union.variant_struct_name(variant).to_case(Case::Pascal);
+        let target = format!("{prefix}{def}");
+        if let Some(Value::Object(schema)) = defs.get_mut(&def) {
+            schema.insert("title".into(), json!(variant.name));
+            if !variant.description.is_empty() {
+                schema.insert("description".into(), json!(variant.description));
+            }
+            if let Some(Value::Object(properties)) = schema.get_mut("properties") {
+                properties.insert(tag.clone(), json!({ "const": variant.name }));
+            }
+            if let Some(Value::Array(required)) = schema.get_mut("required") {
+                required.insert(0, json!(tag));
+            }
+        }
+        one_of.push(json!({ "$ref": target }));
+        mapping.insert(variant.name.clone(), json!(target));
+    }
+
+    let mut schema = json!({
+        "title": name,
+        "oneOf": one_of,
+        "discriminator": { "propertyName": tag, "mapping": mapping },
+    });
+    if !union.description.is_empty() {
+        schema["description"] = json!(union.description);
+    }
+    defs.insert(name, schema);
+}
+
 /// The standard error payload: code, message, params.
 ///
 /// The wire protocol has no status codes, so this envelope *is* the error
@@ -131,8 +335,38 @@ pub fn error_code_list(schema: &EndpointSchema, error_codes: &[crate::definition
     Some(Value::Array(listed))
 }
 
-/// Document title. See [`Data::project_name`] for why this is not derived from
-/// the output path.
+/// The `x-` extensions for an endpoint's operational metadata, on an OpenAPI
+/// operation or an AsyncAPI request message. Only what is declared is emitted.
+pub fn operational_extensions(operational: &Operational) -> serde_json::Map<String, Value> {
+    let mut extensions = serde_json::Map::new();
+    if !operational.rate_limits.is_empty() {
+        let limits: Vec<Value> = operational
+            .rate_limits
+            .iter()
+            .map(|(role, limit)| json!({ "role": role, "requests": limit.requests, "perSeconds": limit.per_seconds }))
+            .collect();
+        extensions.insert("x-rate-limit".into(), json!(limits));
+    }
+    if let Some(timeout) = operational.timeout_ms {
+        extensions.insert("x-timeout-ms".into(), json!(timeout));
+    }
+    if operational.is_idempotent() {
+        extensions.insert("x-idempotent".into(), json!(true));
+    }
+    if operational.safe {
+        extensions.insert("x-safe".into(), json!(true));
+    }
+    if let Some(bytes) = operational.max_payload_bytes {
+        extensions.insert("x-max-payload-bytes".into(), json!(bytes));
+    }
+    if let Some(cost) = operational.cost {
+        extensions.insert("x-cost".into(), json!(cost));
+    }
+    extensions
+}
+
+/// Document title: `title` in `spec.ron`, else the project name. See
+/// [`Data::project_name`] for why this is not derived from the output path.
 pub fn document_title(data: &Data) -> String {
-    data.project_name.clone()
+    data.spec.title.clone().unwrap_or_else(|| data.project_name.clone())
 }
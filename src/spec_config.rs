@@ -0,0 +1,397 @@
+//! Project-level metadata for the specification documents.
+//!
+//! `spec.ron`, next to `version.toml`, fills the `info` block of every
+//! document, adds real `servers` to the AsyncAPI document and declares security
+//! schemes accepted alongside the session token. Without it the documents keep
+//! the project name as their title and `1.0.0` as their version.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use eyre::{Context, Result, bail, eyre};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use smart_default::SmartDefault;
+use smart_serde_default::smart_serde_default;
+
+/// The project-level spec config, next to `version.toml`.
+pub const SPEC_FILE: &str = "spec.ron";
+
+/// The version documents claim when [`SPEC_FILE`] sets none.
+pub const DEFAULT_API_VERSION: &str = "1.0.0";
+
+/// The security scheme every endpoint accepts: the token passed as the
+/// WebSocket subprotocol.
+pub const SESSION_TOKEN_SCHEME: &str = "sessionToken";
+
+/// The contents of [`SPEC_FILE`].
+#[smart_serde_default]
+#[derive(Debug, Clone, PartialEq, Deserialize, SmartDefault)]
+pub struct SpecConfig {
+    /// Document title. Defaults to the project name.
+    pub title: Option<String>,
+    /// The API version, e.g. `"2.3.0"`.
+    pub version: Option<String>,
+    /// Where to read the version from instead of [`SpecConfig::version`].
+    pub version_from: Option<VersionSource>,
+    pub contact: Option<Contact>,
+    pub license: Option<License>,
+    pub terms_of_service: Option<String>,
+    pub external_docs: Option<ExternalDocs>,
+    /// AsyncAPI servers by environment name.
+    #[serde(default)]
+    pub servers: BTreeMap<String, SpecServer>,
+    /// Schemes accepted as an alternative to the session token, by name.
+    #[serde(default)]
+    pub security_schemes: BTreeMap<String, SecurityScheme>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum VersionSource {
+    /// The latest tag reachable from `HEAD`, without a leading `v`.
+    GitTag,
+    /// `package.version` of the project's `Cargo.toml`, or
+    /// `workspace.package.version`.
+    Cargo,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct Contact {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct License {
+    pub name: String,
+    /// An SPDX expression. OpenAPI allows this or `url`, not both.
+    #[serde(default)]
+    pub identifier: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExternalDocs {
+    pub url: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SpecServer {
+    /// Host and optional port, without a scheme: `api.example.com:8443`.
+    pub host: String,
+    pub protocol: WsProtocol,
+    #[serde(default)]
+    pub pathname: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum WsProtocol {
+    Ws,
+    Wss,
+}
+
+impl WsProtocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WsProtocol::Ws => "ws",
+            WsProtocol::Wss => "wss",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum SecurityScheme {
+    /// A key in a header, query parameter or cookie.
+    ApiKey {
+        name: String,
+        location: KeyLocation,
+        #[serde(default)]
+        description: Option<String>,
+    },
+    /// An `Authorization: Bearer` token.
+    Bearer {
+        #[serde(default)]
+        format: Option<String>,
+        #[serde(default)]
+        description: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum KeyLocation {
+    Header,
+    Query,
+    Cookie,
+}
+
+impl KeyLocation {
+    fn as_str(self) -> &'static str {
+        match self {
+            KeyLocation::Header => "header",
+            KeyLocation::Query => "query",
+            KeyLocation::Cookie => "cookie",
+        }
+    }
+}
+
+impl SecurityScheme {
+    /// The scheme as an OpenAPI Security Scheme Object.
+    pub fn to_openapi(&self) -> Value {
+        let (mut scheme, description) = match self {
+            SecurityScheme::ApiKey {
+                name,
+                location,
+                description,
+            } => (
+                json!({ "type": "apiKey", "in": location.as_str(), "name": name }),
+                description,
+            ),
+            SecurityScheme::Bearer { format, description } => {
+                let mut scheme = json!({ "type": "http", "scheme": "bearer" });
+                if let Some(format) = format {
+                    scheme["bearerFormat"] = json!(format);
+                }
+                (scheme, description)
+            }
+        };
+        if let Some(description) = description {
+            scheme["description"] = json!(description);
+        }
+        scheme
+    }
+
+    /// The scheme as an AsyncAPI 3.0 Security Scheme Object, which names an
+    /// API key `httpApiKey` but is otherwise the same.
+    pub fn to_asyncapi(&self) -> Value {
+        let mut scheme = self.to_openapi();
+        if let SecurityScheme::ApiKey { .. } = self {
+            scheme["type"] = json!("httpApiKey");
+        }
+        scheme
+    }
+}
+
+impl SpecConfig {
+    /// The `info` block shared by every document: title, version, contact,
+    /// license and terms. `description` is each document's own.
+    pub fn info(&self, project_name: &str, description: &str) -> Value {
+        let mut info = json!({
+            "title": self.title.as_deref().unwrap_or(project_name),
+            "version": self.version.as_deref().unwrap_or(DEFAULT_API_VERSION),
+            "description": description,
+        });
+        if let Some(terms) = &self.terms_of_service {
+            info["termsOfService"] = json!(terms);
+        }
+        if let Some(contact) = &self.contact {
+            info["contact"] = without_nulls(json!({
+                "name": contact.name,
+                "url": contact.url,
+                "email": contact.email,
+            }));
+        }
+        if let Some(license) = &self.license {
+            info["license"] = without_nulls(json!({
+                "name": license.name,
+                "identifier": license.identifier,
+                "url": license.url,
+            }));
+        }
+        info
+    }
+
+    /// [`SpecConfig::info`] for formats whose license object has no
+    /// `identifier`, AsyncAPI and OpenRPC: an SPDX identifier becomes a link
+    /// to its SPDX page.
+    pub fn info_with_license_url(&self, project_name: &str, description: &str) -> Value {
+        let mut info = self.info(project_name, description);
+        if let Some(license) = info.get_mut("license").and_then(Value::as_object_mut)
+            && let Some(Value::String(identifier)) = license.remove("identifier")
+        {
+            license.insert(
+                "url".into(),
+                json!(format!("https://spdx.org/licenses/{identifier}.html")),
+            );
+        }
+        info
+    }
+
+    /// `externalDocs`. OpenAPI and OpenRPC place it at the top level,
+    /// AsyncAPI 3.0 in `info`.
+    pub fn external_docs(&self) -> Option<Value> {
+        self.external_docs.as_ref().map(|docs| {
+            without_nulls(json!({
+                "url": docs.url,
+                "description": docs.description,
+            }))
+        })
+    }
+
+    /// Replaces [`SpecConfig::version_from`] by the version it points at.
+    /// `project_root` is where `git` runs and `Cargo.toml` is read.
+    pub fn resolve(mut self, project_root: &Path) -> Result<Self> {
+        let Some(source) = self.version_from.take() else {
+            return Ok(self);
+        };
+        let version = match source {
+            VersionSource::GitTag => git_tag_version(project_root)?,
+            VersionSource::Cargo => cargo_version(project_root)?,
+        };
+        self.version = Some(version);
+        Ok(self)
+    }
+
+    /// Rejects contradictory settings and servers that could not be reached.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = vec![];
+        if self.version.is_some() && self.version_from.is_some() {
+            problems.push("set either version or version_from, not both".to_string());
+        }
+        if let Some(License {
+            identifier: Some(_),
+            url: Some(_),
+            ..
+        }) = &self.license
+        {
+            problems.push("license: set either identifier or url, not both".to_string());
+        }
+        for (name, server) in &self.servers {
+            if server.host.contains("://") {
+                problems.push(format!(
+                    "servers.{name}: host {:?} has a scheme; use protocol instead",
+                    server.host
+                ));
+            }
+            if server.pathname.as_ref().is_some_and(|path| !path.starts_with('/')) {
+                problems.push(format!("servers.{name}: pathname must start with '/'"));
+            }
+        }
+        if self.security_schemes.contains_key(SESSION_TOKEN_SCHEME) {
+            problems.push(format!("security_schemes: {SESSION_TOKEN_SCHEME} is built in"));
+        }
+        if !problems.is_empty() {
+            bail!("Invalid {SPEC_FILE}:\n  {}", problems.join("\n  "));
+        }
+        Ok(())
+    }
+}
+
+pub fn read_spec_file(path: &Path) -> Result<SpecConfig> {
+    if !path.exists() {
+        return Ok(SpecConfig::default());
+    }
+    let content = std::fs::read_to_string(path)?;
+    ron::from_str(&content).wrap_err_with(|| format!("Error parsing {}", path.display()))
+}
+
+fn git_tag_version(project_root: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .args(["describe", "--tags", "--abbrev=0"])
+        .current_dir(project_root)
+        .output()
+        .wrap_err("version_from: GitTag needs git")?;
+    if !output.status.success() {
+        bail!(
+            "version_from: GitTag found no tag in {}: {}",
+            project_root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let tag = String::from_utf8(output.stdout)?;
+    let tag = tag.trim();
+    Ok(tag.strip_prefix('v').unwrap_or(tag).to_string())
+}
+
+fn cargo_version(project_root: &Path) -> Result<String> {
+    let path = project_root.join("Cargo.toml");
+    let content =
+        std::fs::read_to_string(&path).wrap_err_with(|| format!("version_from: Cargo needs {}", path.display()))?;
+    let manifest: toml::Table =
+        toml::from_str(&content).wrap_err_with(|| format!("Error parsing {}", path.display()))?;
+    let version = |table: Option<&toml::Value>| table?.get("version")?.as_str().map(str::to_string);
+    let package = manifest.get("package");
+    version(package)
+        .or_else(|| version(manifest.get("workspace").and_then(|workspace| workspace.get("package"))))
+        .ok_or_else(|| eyre!("version_from: Cargo found no package version in {}", path.display()))
+}
+
+fn without_nulls(mut value: Value) -> Value {
+    if let Value::Object(map) = &mut value {
+        map.retain(|_, field| !field.is_null());
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_falls_back_to_the_project_name_and_default_version() {
+        let info = SpecConfig::default().info("trading", "Desc.");
+        assert_eq!(
+            info,
+            json!({ "title": "trading", "version": "1.0.0", "description": "Desc." })
+        );
+
+        let config: SpecConfig = ron::from_str(
+            r#"(
+                title: Some("Trading API"),
+                version: Some("2.3.0"),
+                contact: Some((email: Some("api@example.com"))),
+                license: Some((name: "Apache 2.0", identifier: Some("Apache-2.0"))),
+            )"#,
+        )
+        .unwrap();
+        let info = config.info("trading", "Desc.");
+        assert_eq!(info["title"], "Trading API");
+        assert_eq!(info["version"], "2.3.0");
+        assert_eq!(info["contact"], json!({ "email": "api@example.com" }));
+        assert_eq!(
+            info["license"],
+            json!({ "name": "Apache 2.0", "identifier": "Apache-2.0" })
+        );
+    }
+
+    #[test]
+    fn the_cargo_version_falls_back_to_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"api\"\nversion.workspace = true\n\n[workspace.package]\nversion = \"0.7.1\"\n",
+        )
+        .unwrap();
+        let config = SpecConfig {
+            version_from: Some(VersionSource::Cargo),
+            ..Default::default()
+        };
+        assert_eq!(config.resolve(dir.path()).unwrap().version.as_deref(), Some("0.7.1"));
+    }
+
+    #[test]
+    fn contradictions_and_schemed_hosts_are_rejected() {
+        let config: SpecConfig = ron::from_str(
+            r#"(
+                version: Some("1.2.0"),
+                version_from: Some(GitTag),
+                servers: {"production": (host: "wss://api.example.com", protocol: Wss)},
+                security_schemes: {"sessionToken": Bearer()},
+            )"#,
+        )
+        .unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("either version or version_from"), "{err}");
+        assert!(err.contains("servers.production: host"), "{err}");
+        assert!(err.contains("sessionToken is built in"), "{err}");
+    }
+}
@@ -0,0 +1,247 @@
+//! How the specification documents are written to disk.
+//!
+//! Each document is one file in `docs/`, in JSON or YAML. The split layout
+//! additionally moves `components.schemas` into one file per schema under
+//! `docs/components/schemas/`, shared by every document and referenced by
+//! relative `$ref`. The documents share their schemas, so any document can be
+//! bundled back into one file by a standard `$ref` resolver.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use endpoint_libs::model::COMPONENTS_SCHEMAS_PREFIX;
+use eyre::{Context, Result, bail};
+use serde_json::Value;
+
+/// Where the split layout puts schemas, relative to `docs/`.
+pub const SPLIT_SCHEMAS_DIR: &str = "components/schemas";
+
+/// The stems of the documents written through [`write_document`].
+pub const SPEC_DOCUMENTS: &[&str] = &["openapi", "openapi.gateway", "asyncapi", "openrpc"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpecFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+impl SpecFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SpecFormat::Json => "json",
+            SpecFormat::Yaml => "yaml",
+        }
+    }
+
+    pub fn render(self, value: &Value) -> Result<String> {
+        Ok(match self {
+            SpecFormat::Json => serde_json::to_string_pretty(value)?,
+            SpecFormat::Yaml => serde_norway::to_string(value)?,
+        })
+    }
+}
+
+impl FromStr for SpecFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(SpecFormat::Json),
+            "yaml" => Ok(SpecFormat::Yaml),
+            other => Err(format!("expected json or yaml, got {other:?}")),
+        }
+    }
+}
+
+impl fmt::Display for SpecFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// The format and layout of the specification documents.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpecOutput {
+    pub format: SpecFormat,
+    /// Write each schema to its own file under [`SPLIT_SCHEMAS_DIR`].
+    pub split: bool,
+}
+
+/// Removes the schema files of an earlier run, so that neither a schema
+/// dropped from the RON nor a split layout since abandoned lingers. Called
+/// once per generation, before any document is written.
+pub fn clear_split_schemas(docs_dir: &Path) -> Result<()> {
+    let schemas_dir = docs_dir.join(SPLIT_SCHEMAS_DIR);
+    if schemas_dir.exists() {
+        std::fs::remove_dir_all(&schemas_dir).with_context(|| format!("Failed to clear {}", schemas_dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Writes `docs_dir/{stem}.{json|yaml}`, first moving its schemas out if the
+/// layout is split. The same document in the other format is removed.
+pub fn write_document(docs_dir: &Path, stem: &str, mut document: Value, output: SpecOutput) -> Result<()> {
+    std::fs::create_dir_all(docs_dir)?;
+    if output.split {
+        write_split_schemas(docs_dir, &mut document, output.format)?;
+    }
+    let other = match output.format {
+        SpecFormat::Json => SpecFormat::Yaml,
+        SpecFormat::Yaml => SpecFormat::Json,
+    };
+    let replaced = docs_dir.join(format!("{stem}.{other}"));
+    if replaced.exists() {
+        std::fs::remove_file(&replaced).with_context(|| format!("Failed to remove {}", replaced.display()))?;
+    }
+    let filename = docs_dir.join(format!("{stem}.{}", output.format.extension()));
+    std::fs::write(&filename, output.format.render(&document)?)
+        .with_context(|| format!("Failed to write {}", filename.display()))?;
+    Ok(())
+}
+
+/// Moves `components.schemas` into [`SPLIT_SCHEMAS_DIR`] and points every
+/// reference at the files. Another document may already have written a schema
+/// file in this run; it must have written the same thing.
+fn write_split_schemas(docs_dir: &Path, document: &mut Value, format: SpecFormat) -> Result<()> {
+    let Some(components) = document.get_mut("components").and_then(Value::as_object_mut) else {
+        return Ok(());
+    };
+    let Some(Value::Object(schemas)) = components.remove("schemas") else {
+        return Ok(());
+    };
+    if components.is_empty() {
+        document
+            .as_object_mut()
+            .expect("documents are objects")
+            .remove("components");
+    }
+    let extension = format.extension();
+    relink(document, &format!("{SPLIT_SCHEMAS_DIR}/"), extension);
+
+    let schemas_dir = docs_dir.join(SPLIT_SCHEMAS_DIR);
+    std::fs::create_dir_all(&schemas_dir)?;
+    let schemas: BTreeMap<String, Value> = schemas.into_iter().collect();
+    for (name, mut schema) in schemas {
+        relink(&mut schema, "", extension);
+        let rendered = format.render(&schema)?;
+        let filename = schemas_dir.join(format!("{name}.{extension}"));
+        match std::fs::read_to_string(&filename) {
+            Ok(existing) if existing != rendered => {
+                bail!(
+                    "Two documents disagree on the schema {name}; cannot share {}",
+                    filename.display()
+                )
+            }
+            Ok(_) => {}
+            Err(_) => std::fs::write(&filename, rendered)
+                .with_context(|| format!("Failed to write {}", filename.display()))?,
+        }
+    }
+    Ok(())
+}
+
+/// Points `#/components/schemas/X` references, in `$ref` and in discriminator
+/// mappings, at `{dir}X.{extension}`.
+fn relink(value: &mut Value, dir: &str, extension: &str) {
+    let rewrite = |target: &mut String| {
+        if let Some(rest) = target.strip_prefix(COMPONENTS_SCHEMAS_PREFIX) {
+            *target = match rest.split_once('/') {
+                Some((name, pointer)) => format!("{dir}{name}.{extension}#/{pointer}"),
+                None => format!("{dir}{rest}.{extension}"),
+            };
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(target)) = map.get_mut("$ref") {
+                rewrite(target);
+            }
+            if let Some(Value::Object(mapping)) = map.get_mut("mapping") {
+                for target in mapping.values_mut() {
+                    if let Value::String(target) = target {
+                        rewrite(target);
+                    }
+                }
+            }
+            for child in map.values_mut() {
+                relink(child, dir, extension);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                relink(item, dir, extension);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "openapi": "3.1.0",
+            "paths": {
+                "/userApi/get_user": { "post": { "requestBody": { "$ref": "#/components/schemas/User" } } },
+            },
+            "components": {
+                "schemas": {
+                    "User": { "type": "object", "properties": { "role": { "$ref": "#/components/schemas/Role" } } },
+                    "Role": { "type": "integer" },
+                    "Event": {
+                        "oneOf": [{ "$ref": "#/components/schemas/EventA" }],
+                        "discriminator": { "propertyName": "kind", "mapping": { "A": "#/components/schemas/EventA" } },
+                    },
+                },
+                "securitySchemes": {},
+            },
+        })
+    }
+
+    #[test]
+    fn the_split_layout_links_documents_and_schemas_by_relative_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = SpecOutput {
+            format: SpecFormat::Yaml,
+            split: true,
+        };
+        write_document(dir.path(), "openapi", document(), output).unwrap();
+
+        let doc: Value =
+            serde_norway::from_str(&std::fs::read_to_string(dir.path().join("openapi.yaml")).unwrap()).unwrap();
+        assert_eq!(
+            doc["paths"]["/userApi/get_user"]["post"]["requestBody"]["$ref"],
+            "components/schemas/User.yaml"
+        );
+        assert!(doc["components"].get("schemas").is_none());
+
+        let schemas = dir.path().join(SPLIT_SCHEMAS_DIR);
+        let user: Value = serde_norway::from_str(&std::fs::read_to_string(schemas.join("User.yaml")).unwrap()).unwrap();
+        assert_eq!(user["properties"]["role"]["$ref"], "Role.yaml");
+        let event: Value =
+            serde_norway::from_str(&std::fs::read_to_string(schemas.join("Event.yaml")).unwrap()).unwrap();
+        assert_eq!(event["discriminator"]["mapping"]["A"], "EventA.yaml");
+
+        // A second document sharing the schemas reuses the files.
+        write_document(dir.path(), "asyncapi", document(), output).unwrap();
+        let mut other = document();
+        other["components"]["schemas"]["Role"] = json!({ "type": "string" });
+        let err = write_document(dir.path(), "openrpc", other, output).unwrap_err();
+        assert!(err.to_string().contains("disagree on the schema Role"), "{err}");
+    }
+
+    #[test]
+    fn the_single_layout_keeps_components_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        write_document(dir.path(), "openapi", document(), SpecOutput::default()).unwrap();
+        let doc: Value = serde_json::from_slice(&std::fs::read(dir.path().join("openapi.json")).unwrap()).unwrap();
+        assert_eq!(doc, document());
+        assert!(!dir.path().join(SPLIT_SCHEMAS_DIR).exists());
+    }
+}
@@ -0,0 +1,745 @@
+//! Postgres tables for shared structs that set `sql_table: true`.
+//!
+//! Each opted-in struct becomes one table named after it in snake case, with
+//! a column per field. Three artifacts come out of the same [`SqlSchema`]:
+//!
+//! - `docs/sql/schema.sql`, the `CREATE TYPE`/`CREATE TABLE` statements
+//!   (with `--sql`);
+//! - `docs/sql/schema.json`, the schema as data. The next generation diffs
+//!   against it and writes a forward migration to `docs/sql/migrations/`;
+//! - a `TryFrom<&tokio_postgres::Row>` impl per table in `model.rs`, plus a
+//!   `FromSql` impl per enum the tables use (always, once a struct opts in).
+//!
+//! Shared enums become Postgres enum types, or `text` columns with a `CHECK`
+//! constraint on tables that set `sql_enums_as_text`. A newtype is stored as
+//! its underlying type. Structs, unions, maps and anything else without a
+//! native column type are stored as `jsonb`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use convert_case::{Case, Casing};
+use endpoint_libs::model::{Field, Type};
+use eyre::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::definitions::StructElement;
+use crate::docs::Data;
+use crate::rust::ToRust;
+use crate::rust_item::enum_variant_name;
+
+/// Every table and enum type the opted-in structs need. Also the format of
+/// `docs/sql/schema.json`, the snapshot migrations are diffed against.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SqlSchema {
+    /// Postgres enum types by name, with their labels in declaration order.
+    pub enums: BTreeMap<String, Vec<String>>,
+    /// Tables by name, with their columns in field order.
+    pub tables: BTreeMap<String, Vec<SqlColumn>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SqlColumn {
+    pub name: String,
+    /// The column type as written in DDL, e.g. `bigint` or `"side"[]`.
+    pub ty: String,
+    pub nullable: bool,
+    /// Labels a `text` enum column is constrained to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed: Vec<String>,
+}
+
+/// How a column is read back into the field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Storage {
+    /// `FromSql` for the field type itself.
+    Native,
+    /// `u32` has no `FromSql`; it is stored as `bigint` and converted.
+    Unsigned,
+    /// `jsonb`, through `tokio_postgres::types::Json`.
+    Json,
+    /// A newtype, read as its underlying type and wrapped. `unsigned` if that
+    /// type is `u32`.
+    Newtype { unsigned: bool },
+}
+
+/// A field lowered to a column.
+struct Column {
+    column: SqlColumn,
+    storage: Storage,
+    /// The shared enum the column uses.
+    enum_type: Option<SqlEnum>,
+}
+
+struct SqlEnum {
+    rust_name: String,
+    pg_name: String,
+    labels: Vec<String>,
+}
+
+/// The table name for a struct.
+pub fn table_name(struct_name: &str) -> String {
+    struct_name.to_case(Case::Snake)
+}
+
+/// Quotes an identifier, so names like `user` or `order` need no special care.
+fn quote(name: &str) -> String {
+    format!("\"{name}\"")
+}
+
+fn literal(label: &str) -> String {
+    format!("'{}'", label.replace('\'', "''"))
+}
+
+/// The shared enum an `EnumRef` names.
+fn shared_enum(name: &str, data: &Data) -> Option<SqlEnum> {
+    data.enums.iter().find_map(|element| match &element.inner {
+        Type::Enum { name: n, variants } if n == name => Some(SqlEnum {
+            rust_name: element.to_rust_ref(false),
+            pg_name: n.to_case(Case::Snake),
+            labels: variants.iter().map(|v| enum_variant_name(&v.name)).collect(),
+        }),
+        _ => None,
+    })
+}
+
+/// The column type of a non-nullable, non-array value, with its storage, the
+/// labels it is constrained to and the enum type it uses.
+fn scalar(ty: &Type, enums_as_text: bool, data: &Data) -> Result<Column, String> {
+    let native = |ty: &str| Column {
+        column: SqlColumn {
+            name: String::new(),
+            ty: ty.to_string(),
+            nullable: false,
+            allowed: vec![],
+        },
+        storage: Storage::Native,
+        enum_type: None,
+    };
+    if let Type::StructRef(name) = ty
+        && let Some(newtype) = data.newtypes.iter().find(|n| &n.name == name)
+    {
+        let mut column = scalar(&newtype.ty, enums_as_text, data)?;
+        column.storage = match column.storage {
+            Storage::Native => Storage::Newtype { unsigned: false },
+            Storage::Unsigned => Storage::Newtype { unsigned: true },
+            // A newtype over a struct or another newtype reads fine as JSON.
+            Storage::Json | Storage::Newtype { .. } => return scalar(&Type::Object, enums_as_text, data),
+        };
+        return Ok(column);
+    }
+    Ok(match ty {
+        Type::Int32 => native("integer"),
+        Type::Int64 | Type::TimeStampMs => native("bigint"),
+        Type::UInt32 => Column {
+            storage: Storage::Unsigned,
+            ..native("bigint")
+        },
+        Type::Float64 => native("double precision"),
+        Type::Boolean => native("boolean"),
+        Type::String => native("text"),
+        Type::Bytea => native("bytea"),
+        Type::UUID => native("uuid"),
+        Type::IpAddr => native("inet"),
+        Type::BlockchainDecimal => native("numeric"),
+        Type::EnumRef { name, .. } => {
+            let Some(sql_enum) = shared_enum(name, data) else {
+                return Err(format!("enum '{name}' is not a shared enum"));
+            };
+            let mut column = if enums_as_text {
+                let mut column = native("text");
+                column.column.allowed = sql_enum.labels.clone();
+                column
+            } else {
+                native(&quote(&sql_enum.pg_name))
+            };
+            column.enum_type = Some(sql_enum);
+            column
+        }
+        Type::StructRef(_) | Type::StructTable { .. } | Type::Object => Column {
+            storage: Storage::Json,
+            ..native("jsonb")
+        },
+        Type::Enum { name, .. } => {
+            return Err(format!(
+                "inline enum '{name}' has no SQL mapping; declare it as a shared enum and use EnumRef"
+            ));
+        }
+        Type::Struct { name, .. } => {
+            return Err(format!(
+                "inline struct '{name}' has no SQL mapping; declare it as a shared struct and use StructRef"
+            ));
+        }
+        Type::Optional(_) => {
+            return Err("nested Optional has no SQL mapping: NULL cannot tell None from Some(None)".into());
+        }
+        other => return Err(format!("`{}` has no SQL mapping", other.to_rust_ref(false))),
+    })
+}
+
+/// The column for `field` of a table.
+fn column(field: &Field, enums_as_text: bool, data: &Data) -> Result<Column, String> {
+    let (ty, nullable) = match &field.ty {
+        Type::Optional(inner) => (inner.as_ref(), true),
+        ty => (ty, false),
+    };
+    let mut column = match ty {
+        Type::Vec(element) => {
+            // Arrays may hold NULLs, so `Vec<Option<T>>` maps like `Vec<T>`.
+            let element = match element.as_ref() {
+                Type::Optional(inner) => inner.as_ref(),
+                element => element,
+            };
+            let mut column = scalar(element, enums_as_text, data)?;
+            if column.storage == Storage::Native {
+                column.column.ty.push_str("[]");
+            } else {
+                column = scalar(&Type::Object, enums_as_text, data)?;
+            }
+            column
+        }
+        ty => scalar(ty, enums_as_text, data)?,
+    };
+    column.column.name = field.name.to_case(Case::Snake);
+    column.column.nullable = nullable;
+    Ok(column)
+}
+
+/// Why each field of an opted-in struct has no column, as `(field, reason)`.
+pub fn unmapped_fields(element: &StructElement, data: &Data) -> Vec<(String, String)> {
+    let Type::Struct { fields, .. } = &element.inner else {
+        return vec![];
+    };
+    fields
+        .iter()
+        .filter_map(|field| {
+            column(field, element.config.sql_enums_as_text, data)
+                .err()
+                .map(|reason| (field.name.clone(), reason))
+        })
+        .collect()
+}
+
+fn tables(data: &Data) -> impl Iterator<Item = (&str, &StructElement)> {
+    data.structs
+        .iter()
+        .filter(|s| s.config.sql_table)
+        .filter_map(|s| match &s.inner {
+            Type::Struct { name, .. } => Some((name.as_str(), s)),
+            _ => None,
+        })
+}
+
+fn columns(element: &StructElement, data: &Data) -> Result<Vec<Column>> {
+    let Type::Struct { name, fields } = &element.inner else {
+        unreachable!("StructElement is validated to wrap a struct");
+    };
+    fields
+        .iter()
+        .map(|field| column(field, element.config.sql_enums_as_text, data))
+        .collect::<Result<_, _>>()
+        .map_err(|reason| eyre::eyre!("struct '{name}': {reason}"))
+}
+
+/// The tables and enum types of every opted-in struct.
+pub fn build_schema(data: &Data) -> Result<SqlSchema> {
+    let mut schema = SqlSchema::default();
+    for (name, element) in tables(data) {
+        let mut table = vec![];
+        for column in columns(element, data)? {
+            if let Some(sql_enum) = column.enum_type
+                && column.column.allowed.is_empty()
+            {
+                schema.enums.insert(sql_enum.pg_name, sql_enum.labels);
+            }
+            table.push(column.column);
+        }
+        schema.tables.insert(table_name(name), table);
+    }
+    Ok(schema)
+}
+
+fn create_type(name: &str, labels: &[String]) -> String {
+    format!(
+        "CREATE TYPE {} AS ENUM ({});\n",
+        quote(name),
+        labels.iter().map(|l| literal(l)).collect::<Vec<_>>().join(", ")
+    )
+}
+
+fn check_constraint(table: &str, column: &SqlColumn) -> String {
+    let labels = column.allowed.iter().map(|l| literal(l)).collect::<Vec<_>>().join(", ");
+    let condition = if column.ty.ends_with("[]") {
+        format!("{} <@ ARRAY[{labels}]", quote(&column.name))
+    } else {
+        format!("{} IN ({labels})", quote(&column.name))
+    };
+    format!(
+        "CONSTRAINT {} CHECK ({condition})",
+        quote(&constraint_name(table, column))
+    )
+}
+
+fn constraint_name(table: &str, column: &SqlColumn) -> String {
+    format!("{table}_{}_check", column.name)
+}
+
+fn column_definition(table: &str, column: &SqlColumn) -> String {
+    let mut definition = format!("{} {}", quote(&column.name), column.ty);
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+    if !column.allowed.is_empty() {
+        definition.push(' ');
+        definition.push_str(&check_constraint(table, column));
+    }
+    definition
+}
+
+fn create_table(name: &str, columns: &[SqlColumn]) -> String {
+    let columns: Vec<String> = columns
+        .iter()
+        .map(|c| format!("    {}", column_definition(name, c)))
+        .collect();
+    format!("CREATE TABLE {} (\n{}\n);\n", quote(name), columns.join(",\n"))
+}
+
+const HEADER: &str = "-- Generated by endpoint-gen from the RON definitions. Do not edit.\n";
+
+/// The DDL creating `schema` from nothing.
+pub fn render_ddl(schema: &SqlSchema) -> String {
+    let mut out = HEADER.to_string();
+    for (name, labels) in &schema.enums {
+        out.push('\n');
+        out.push_str(&create_type(name, labels));
+    }
+    for (name, columns) in &schema.tables {
+        out.push('\n');
+        out.push_str(&create_table(name, columns));
+    }
+    out
+}
+
+/// The statements taking a database from `old` to `new`, or `None` if they
+/// are the same.
+///
+/// A renamed table or column shows up as a drop and an add, and Postgres
+/// cannot drop enum labels, so every script wants reading before it is run.
+pub fn migration(old: &SqlSchema, new: &SqlSchema) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let mut statements = vec![];
+
+    for (name, labels) in &new.enums {
+        match old.enums.get(name) {
+            None => statements.push(create_type(name, labels)),
+            Some(old_labels) => {
+                for label in labels.iter().filter(|l| !old_labels.contains(l)) {
+                    statements.push(format!("ALTER TYPE {} ADD VALUE {};\n", quote(name), literal(label)));
+                }
+                for label in old_labels.iter().filter(|l| !labels.contains(l)) {
+                    statements.push(format!(
+                        "-- Label {} was removed from {}. Postgres cannot drop enum labels; recreate the type by hand.\n",
+                        literal(label),
+                        quote(name)
+                    ));
+                }
+            }
+        }
+    }
+
+    for (table, columns) in &new.tables {
+        let Some(old_columns) = old.tables.get(table) else {
+            statements.push(create_table(table, columns));
+            continue;
+        };
+        let alter = |change: String| format!("ALTER TABLE {} {change};\n", quote(table));
+        for old_column in old_columns {
+            if !columns.iter().any(|c| c.name == old_column.name) {
+                statements.push(alter(format!("DROP COLUMN {}", quote(&old_column.name))));
+            }
+        }
+        for column in columns {
+            let Some(old_column) = old_columns.iter().find(|c| c.name == column.name) else {
+                statements.push(alter(format!("ADD COLUMN {}", column_definition(table, column))));
+                continue;
+            };
+            let name = quote(&column.name);
+            let constraint_changed = old_column.allowed != column.allowed || old_column.ty != column.ty;
+            if constraint_changed && !old_column.allowed.is_empty() {
+                statements.push(alter(format!(
+                    "DROP CONSTRAINT {}",
+                    quote(&constraint_name(table, old_column))
+                )));
+            }
+            if old_column.ty != column.ty {
+                statements.push(alter(format!(
+                    "ALTER COLUMN {name} TYPE {ty} USING {name}::{ty}",
+                    ty = column.ty
+                )));
+            }
+            if old_column.nullable != column.nullable {
+                let change = if column.nullable { "DROP" } else { "SET" };
+                statements.push(alter(format!("ALTER COLUMN {name} {change} NOT NULL")));
+            }
+            if constraint_changed && !column.allowed.is_empty() {
+                statements.push(alter(format!("ADD {}", check_constraint(table, column))));
+            }
+        }
+    }
+
+    for table in old.tables.keys().filter(|t| !new.tables.contains_key(*t)) {
+        statements.push(format!("DROP TABLE {};\n", quote(table)));
+    }
+    for name in old.enums.keys().filter(|e| !new.enums.contains_key(*e)) {
+        statements.push(format!("DROP TYPE {};\n", quote(name)));
+    }
+
+    let mut out = HEADER.to_string();
+    out.push_str("-- Forward migration from the previous docs/sql/schema.json. Review before applying.\n\n");
+    for statement in statements {
+        out.push_str(&statement);
+    }
+    Some(out)
+}
+
+/// Writes `docs/sql/schema.sql` and `docs/sql/schema.json`, and a migration
+/// from the `schema.json` being replaced if it differs.
+pub fn gen_sql(data: &Data) -> Result<()> {
+    let sql_dir = data.project_root.join("docs").join("sql");
+    std::fs::create_dir_all(&sql_dir)?;
+    let schema = build_schema(data)?;
+
+    let snapshot = sql_dir.join("schema.json");
+    if snapshot.exists() {
+        let previous: SqlSchema = serde_json::from_str(&std::fs::read_to_string(&snapshot)?)
+            .with_context(|| format!("Failed to parse previous SQL schema: {}", snapshot.display()))?;
+        if let Some(script) = migration(&previous, &schema) {
+            write_migration(&sql_dir.join("migrations"), &script)?;
+        }
+    }
+
+    std::fs::write(sql_dir.join("schema.sql"), render_ddl(&schema))?;
+    let file = std::fs::File::create(&snapshot)
+        .with_context(|| format!("Failed to create SQL schema file: {}", snapshot.display()))?;
+    serde_json::to_writer_pretty(file, &schema)?;
+    Ok(())
+}
+
+/// Writes `script` as the next numbered migration: `0001.sql`, `0002.sql`, ...
+fn write_migration(dir: &Path, script: &str) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut last = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let name = name.to_string_lossy();
+        if let Some(number) = name.strip_suffix(".sql").and_then(|n| n.parse::<u32>().ok()) {
+            last = last.max(number);
+        }
+    }
+    let path = dir.join(format!("{:04}.sql", last + 1));
+    if path.exists() {
+        bail!("Refusing to overwrite migration {}", path.display());
+    }
+    std::fs::write(&path, script)?;
+    println!("Wrote SQL migration {}", path.display());
+    Ok(())
+}
+
+/// The `model.rs` impls reading rows back: `TryFrom<&tokio_postgres::Row>` for
+/// each table's struct and `FromSql` for each enum its columns use. Empty if
+/// no struct opts in.
+pub fn row_impls(data: &Data) -> Result<String> {
+    let mut out = String::new();
+    let mut enums: BTreeMap<String, String> = BTreeMap::new();
+    for (_, element) in tables(data) {
+        let Type::Struct { fields, .. } = &element.inner else {
+            unreachable!("tables are structs");
+        };
+        let mut reads = vec![];
+        for (field, column) in fields.iter().zip(columns(element, data)?) {
+            if let Some(sql_enum) = &column.enum_type {
+                enums.insert(sql_enum.rust_name.clone(), sql_enum.pg_name.clone());
+            }
+            reads.push(format!(
+                "{}: {},",
+                field.name.to_case(Case::Snake),
+                read_expression(&field.ty, &column)
+            ));
+        }
+        writeln!(
+            out,
+            "impl TryFrom<&tokio_postgres::Row> for {name} {{
+    type Error = Box<dyn std::error::Error + Sync + Send>;
+    fn try_from(row: &tokio_postgres::Row) -> Result<Self, Self::Error> {{
+        Ok(Self {{
+            {reads}
+        }})
+    }}
+}}",
+            name = element.to_rust_ref(false),
+            reads = reads.join("\n            "),
+        )?;
+    }
+    // Labels are the Rust variant names, so `EnumString` parses them whether
+    // the column is a Postgres enum or constrained `text`.
+    for (rust_name, pg_name) in enums {
+        writeln!(
+            out,
+            "impl<'a> tokio_postgres::types::FromSql<'a> for {rust_name} {{
+    fn from_sql(
+        _: &tokio_postgres::types::Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {{
+        Ok(std::str::from_utf8(raw)?.parse()?)
+    }}
+    fn accepts(ty: &tokio_postgres::types::Type) -> bool {{
+        ty.name() == \"{pg_name}\" || *ty == tokio_postgres::types::Type::TEXT
+    }}
+}}"
+        )?;
+    }
+    Ok(out)
+}
+
+/// The expression reading `column` into a field of type `ty`.
+fn read_expression(ty: &Type, column: &Column) -> String {
+    let name = &column.column.name;
+    let wrapper = || match ty {
+        Type::Optional(inner) => inner.to_rust_ref(false),
+        ty => ty.to_rust_ref(false),
+    };
+    match (column.storage, column.column.nullable) {
+        (Storage::Native, _) => format!("row.try_get(\"{name}\")?"),
+        (Storage::Unsigned, false) => format!("u32::try_from(row.try_get::<_, i64>(\"{name}\")?)?"),
+        (Storage::Unsigned, true) => {
+            format!("row.try_get::<_, Option<i64>>(\"{name}\")?.map(u32::try_from).transpose()?")
+        }
+        (Storage::Json, false) => format!(
+            "row.try_get::<_, tokio_postgres::types::Json<{}>>(\"{name}\")?.0",
+            ty.to_rust_ref(false)
+        ),
+        (Storage::Json, true) => {
+            let Type::Optional(inner) = ty else {
+                unreachable!("nullable columns are Optional fields");
+            };
+            format!(
+                "row.try_get::<_, Option<tokio_postgres::types::Json<{}>>>(\"{name}\")?.map(|json| json.0)",
+                inner.to_rust_ref(false)
+            )
+        }
+        (Storage::Newtype { unsigned: false }, false) => format!("{}(row.try_get(\"{name}\")?)", wrapper()),
+        (Storage::Newtype { unsigned: false }, true) => {
+            format!("row.try_get::<_, Option<_>>(\"{name}\")?.map({})", wrapper())
+        }
+        (Storage::Newtype { unsigned: true }, false) => {
+            format!("{}(u32::try_from(row.try_get::<_, i64>(\"{name}\")?)?)", wrapper())
+        }
+        (Storage::Newtype { unsigned: true }, true) => format!(
+            "row.try_get::<_, Option<i64>>(\"{name}\")?.map(u32::try_from).transpose()?.map({})",
+            wrapper()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{EnumElement, NewtypeElement, RustGenConfig};
+    use endpoint_libs::model::EnumVariant;
+    use std::path::PathBuf;
+
+    fn data(enums_as_text: bool, fields: Vec<Field>) -> Data {
+        Data {
+            project_name: "test".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![],
+            enums: vec![EnumElement {
+                config: RustGenConfig {
+                    prefix_enum: true,
+                    ..Default::default()
+                },
+                inner: Type::enum_("Side", vec![EnumVariant::new("Buy", 1), EnumVariant::new("Sell", 2)]),
+            }],
+            structs: vec![StructElement {
+                config: RustGenConfig {
+                    sql_table: true,
+                    sql_enums_as_text: enums_as_text,
+                    ..Default::default()
+                },
+                type_params: vec![],
+                inner: Type::struct_("Order", fields),
+            }],
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    fn order_fields() -> Vec<Field> {
+        vec![
+            Field::new("id", Type::UUID),
+            Field::new("created_at", Type::TimeStampMs),
+            Field::new("price", Type::BlockchainDecimal),
+            Field::new("note", Type::optional(Type::String)),
+            Field::new("side", Type::enum_ref("Side", true)),
+            Field::new("quantity", Type::UInt32),
+            Field::new("fill", Type::optional(Type::struct_ref("Fill"))),
+        ]
+    }
+
+    #[test]
+    fn structs_become_tables_with_enum_types_or_checks() {
+        let ddl = render_ddl(&build_schema(&data(false, order_fields())).unwrap());
+        assert_eq!(
+            ddl,
+            "-- Generated by endpoint-gen from the RON definitions. Do not edit.\n\
+             \n\
+             CREATE TYPE \"side\" AS ENUM ('Buy', 'Sell');\n\
+             \n\
+             CREATE TABLE \"order\" (\n    \
+                 \"id\" uuid NOT NULL,\n    \
+                 \"created_at\" bigint NOT NULL,\n    \
+                 \"price\" numeric NOT NULL,\n    \
+                 \"note\" text,\n    \
+                 \"side\" \"side\" NOT NULL,\n    \
+                 \"quantity\" bigint NOT NULL,\n    \
+                 \"fill\" jsonb\n\
+             );\n"
+        );
+
+        let schema = build_schema(&data(
+            true,
+            vec![Field::new("sides", Type::vec(Type::enum_ref("Side", true)))],
+        ))
+        .unwrap();
+        assert!(schema.enums.is_empty());
+        assert!(
+            render_ddl(&schema).contains(
+                "\"sides\" text[] NOT NULL CONSTRAINT \"order_sides_check\" CHECK (\"sides\" <@ ARRAY['Buy', 'Sell'])"
+            ),
+            "{}",
+            render_ddl(&schema)
+        );
+    }
+
+    #[test]
+    fn row_impls_convert_what_postgres_cannot_decode_directly() {
+        let impls = row_impls(&data(false, order_fields())).unwrap();
+        assert!(
+            impls.contains("impl TryFrom<&tokio_postgres::Row> for Order {"),
+            "{impls}"
+        );
+        assert!(impls.contains("id: row.try_get(\"id\")?,"), "{impls}");
+        assert!(
+            impls.contains("quantity: u32::try_from(row.try_get::<_, i64>(\"quantity\")?)?,"),
+            "{impls}"
+        );
+        assert!(
+            impls.contains(
+                "fill: row.try_get::<_, Option<tokio_postgres::types::Json<Fill>>>(\"fill\")?.map(|json| json.0),"
+            ),
+            "{impls}"
+        );
+        assert!(
+            impls.contains("impl<'a> tokio_postgres::types::FromSql<'a> for EnumSide {"),
+            "{impls}"
+        );
+        assert!(impls.contains("ty.name() == \"side\""), "{impls}");
+    }
+
+    #[test]
+    fn newtypes_are_stored_as_their_underlying_type() {
+        let mut data = data(
+            false,
+            vec![
+                Field::new("user_id", Type::struct_ref("UserId")),
+                Field::new("referrer", Type::optional(Type::struct_ref("UserId"))),
+                Field::new("followers", Type::vec(Type::struct_ref("UserId"))),
+            ],
+        );
+        data.newtypes = vec![NewtypeElement {
+            config: RustGenConfig::default(),
+            name: "UserId".into(),
+            ty: Type::Int64,
+            description: String::new(),
+        }];
+
+        let ddl = render_ddl(&build_schema(&data).unwrap());
+        assert!(ddl.contains("\"user_id\" bigint NOT NULL,"), "{ddl}");
+        assert!(ddl.contains("\"referrer\" bigint,"), "{ddl}");
+        assert!(ddl.contains("\"followers\" jsonb NOT NULL"), "{ddl}");
+
+        let impls = row_impls(&data).unwrap();
+        assert!(impls.contains("user_id: UserId(row.try_get(\"user_id\")?),"), "{impls}");
+        assert!(
+            impls.contains("referrer: row.try_get::<_, Option<_>>(\"referrer\")?.map(UserId),"),
+            "{impls}"
+        );
+        assert!(!impls.contains("Json<UserId>"), "{impls}");
+    }
+
+    #[test]
+    fn unmappable_fields_are_reported() {
+        let data = data(
+            false,
+            vec![
+                Field::new("address", Type::BlockchainAddress),
+                Field::new("maybe", Type::optional(Type::optional(Type::Int64))),
+            ],
+        );
+        let unmapped = unmapped_fields(&data.structs[0], &data);
+        assert_eq!(unmapped.len(), 2, "{unmapped:?}");
+        assert_eq!(unmapped[0].0, "address");
+        assert!(unmapped[1].1.contains("nested Optional"), "{unmapped:?}");
+    }
+
+    #[test]
+    fn migrations_alter_only_what_changed() {
+        let old = build_schema(&data(false, order_fields())).unwrap();
+        assert_eq!(migration(&old, &old), None);
+
+        let mut fields = order_fields();
+        fields.retain(|f| f.name != "fill");
+        fields[3] = Field::new("note", Type::String);
+        fields.push(Field::new("venue", Type::optional(Type::String)));
+        let mut new_data = data(false, fields);
+        if let Type::Enum { variants, .. } = &mut new_data.enums[0].inner {
+            variants.push(EnumVariant::new("Short", 3));
+        }
+        let new = build_schema(&new_data).unwrap();
+
+        let script = migration(&old, &new).unwrap();
+        let statements: Vec<&str> = script
+            .lines()
+            .filter(|l| !l.starts_with("--") && !l.is_empty())
+            .collect();
+        assert_eq!(
+            statements,
+            [
+                "ALTER TYPE \"side\" ADD VALUE 'Short';",
+                "ALTER TABLE \"order\" DROP COLUMN \"fill\";",
+                "ALTER TABLE \"order\" ALTER COLUMN \"note\" SET NOT NULL;",
+                "ALTER TABLE \"order\" ADD COLUMN \"venue\" text;",
+            ]
+        );
+
+        let as_text = build_schema(&data(true, order_fields())).unwrap();
+        let script = migration(&old, &as_text).unwrap();
+        assert!(
+            script.contains("ALTER TABLE \"order\" ALTER COLUMN \"side\" TYPE text USING \"side\"::text;"),
+            "{script}"
+        );
+        assert!(
+            script.contains(
+                "ALTER TABLE \"order\" ADD CONSTRAINT \"order_side_check\" CHECK (\"side\" IN ('Buy', 'Sell'));"
+            ),
+            "{script}"
+        );
+        assert!(script.contains("DROP TYPE \"side\";"), "{script}");
+    }
+}
@@ -0,0 +1,1646 @@
+//! Cross-definition checks over the merged model.
+//!
+//! `Definition::validate_self` sees one RON file at a time, so it cannot notice
+//! that two files disagree about what `Position` is. These passes run once every
+//! file has been merged into [`Data`] and report problems against the files that
+//! introduced them, instead of leaving rustc to complain about `model.rs`.
+//!
+//! - [`check_type_consistency`]: one name, one shape.
+//! - [`check_references`]: every `StructRef`/`EnumRef` lands on something.
+//! - [`check_worktable_structs`]: worktable structs only hold what worktable can store.
+//! - [`check_sql_tables`]: every field of a SQL table has a column type.
+//! - [`check_stream_cancellation`]: `cancelled_by` pairs a stream with an endpoint of its service.
+//! - [`check_operational`]: rate limits name the endpoint's roles, and no limit is zero.
+//! - [`check_mcp_tools`]: exposed MCP tool names are valid and unique across services.
+//! - [`check_mcp_catalog`]: MCP resources read read-only endpoints, and prompts name exposed ones.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use convert_case::{Case, Casing};
+use endpoint_libs::model::{Type, TypeRegistry};
+use eyre::bail;
+use itertools::Itertools;
+
+use crate::definitions::Definition;
+use crate::docs::Data;
+use crate::generics;
+use crate::rust::ToRust;
+use crate::spec_common::build_registry;
+
+/// Which RON file each endpoint, event, MCP resource and prompt, struct and
+/// enum came from.
+///
+/// [`Data`] is deliberately location-free — `--check` clones it into a scratch
+/// directory — so provenance is recorded separately while the files are read.
+/// Lookups for something never recorded (e.g. a [`Data`] built in a test) fall
+/// back to `<unknown file>` rather than failing.
+#[derive(Debug, Default, Clone)]
+pub struct DefinitionSources {
+    endpoints: HashMap<(String, String), PathBuf>,
+    events: HashMap<(String, String), PathBuf>,
+    resources: HashMap<(String, String), PathBuf>,
+    prompts: HashMap<(String, String), PathBuf>,
+    structs: HashMap<String, Vec<PathBuf>>,
+    enums: HashMap<String, Vec<PathBuf>>,
+}
+
+impl DefinitionSources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records every endpoint, event, MCP resource and prompt, struct and enum declared by `definition` as
+    /// coming from `path`.
+    pub fn record(&mut self, definition: &Definition, path: &Path) {
+        match definition {
+            Definition::EndpointSchema(def) => {
+                self.endpoints.insert(
+                    (def.service_name.clone(), def.schema.schema.name.clone()),
+                    path.to_path_buf(),
+                );
+            }
+            Definition::EndpointSchemaList(def) => {
+                for endpoint in &def.endpoints {
+                    self.endpoints.insert(
+                        (def.service_name.clone(), endpoint.schema.name.clone()),
+                        path.to_path_buf(),
+                    );
+                }
+            }
+            Definition::Enum(element) => self.record_enum(&element.inner, path),
+            Definition::EnumList(list) => {
+                for element in &list.enum_elements {
+                    self.record_enum(&element.inner, path);
+                }
+            }
+            Definition::Struct(element) => self.record_struct(&element.inner, path),
+            Definition::StructList(list) => {
+                for element in &list.struct_elements {
+                    self.record_struct(&element.inner, path);
+                }
+            }
+            // Unions and newtypes are referenced with StructRef, so they share
+            // the struct namespace.
+            Definition::TaggedUnion(element) => self.record_struct(&element.placeholder(), path),
+            Definition::Newtype(element) => self.record_struct(&element.placeholder(), path),
+            Definition::ServerEvent(event) => {
+                self.events
+                    .insert((event.service_name.clone(), event.name.clone()), path.to_path_buf());
+            }
+            Definition::McpResource(resource) => {
+                self.resources.insert(
+                    (resource.service_name.clone(), resource.name.clone()),
+                    path.to_path_buf(),
+                );
+            }
+            Definition::McpPrompt(prompt) => {
+                self.prompts
+                    .insert((prompt.service_name.clone(), prompt.name.clone()), path.to_path_buf());
+            }
+            Definition::ErrorCodeList(_) => {}
+        }
+    }
+
+    fn record_struct(&mut self, ty: &Type, path: &Path) {
+        if let Type::Struct { name, .. } = ty {
+            self.structs.entry(name.clone()).or_default().push(path.to_path_buf());
+        }
+    }
+
+    fn record_enum(&mut self, ty: &Type, path: &Path) {
+        if let Type::Enum { name, .. } = ty {
+            self.enums.entry(name.clone()).or_default().push(path.to_path_buf());
+        }
+    }
+
+    pub fn endpoint_file(&self, service: &str, endpoint: &str) -> String {
+        self.endpoints
+            .get(&(service.to_string(), endpoint.to_string()))
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| UNKNOWN_FILE.to_string())
+    }
+
+    pub fn event_file(&self, service: &str, event: &str) -> String {
+        self.events
+            .get(&(service.to_string(), event.to_string()))
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| UNKNOWN_FILE.to_string())
+    }
+
+    pub fn resource_file(&self, service: &str, resource: &str) -> String {
+        self.resources
+            .get(&(service.to_string(), resource.to_string()))
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| UNKNOWN_FILE.to_string())
+    }
+
+    pub fn prompt_file(&self, service: &str, prompt: &str) -> String {
+        self.prompts
+            .get(&(service.to_string(), prompt.to_string()))
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| UNKNOWN_FILE.to_string())
+    }
+
+    pub fn struct_files(&self, name: &str) -> String {
+        Self::join_files(self.structs.get(name))
+    }
+
+    pub fn enum_files(&self, name: &str) -> String {
+        Self::join_files(self.enums.get(name))
+    }
+
+    fn join_files(paths: Option<&Vec<PathBuf>>) -> String {
+        match paths {
+            Some(paths) if !paths.is_empty() => paths.iter().map(|p| p.display().to_string()).unique().join(", "),
+            _ => UNKNOWN_FILE.to_string(),
+        }
+    }
+}
+
+const UNKNOWN_FILE: &str = "<unknown file>";
+
+/// A type as written in one place in the RON: a whole shared definition, or
+/// one endpoint field / stream / error field. Every pass walks these.
+struct TypeRoot<'a> {
+    ty: &'a Type,
+    /// `file: context`, e.g. `config/s1.ron: service 's1' endpoint 'Get' returns 'data'`.
+    site: String,
+    /// The shared struct or enum this root *is*, if any. Refs from inside a
+    /// shared struct back to itself do not count as uses of it.
+    shared: Option<&'a str>,
+}
+
+fn type_roots<'a>(data: &'a Data, sources: &DefinitionSources) -> Vec<TypeRoot<'a>> {
+    let mut roots = vec![];
+
+    for element in &data.structs {
+        if let Type::Struct { name, .. } = &element.inner {
+            roots.push(TypeRoot {
+                ty: &element.inner,
+                site: format!("{}: struct '{name}'", sources.struct_files(name)),
+                shared: Some(name),
+            });
+        }
+    }
+    for element in &data.enums {
+        if let Type::Enum { name, .. } = &element.inner {
+            roots.push(TypeRoot {
+                ty: &element.inner,
+                site: format!("{}: enum '{name}'", sources.enum_files(name)),
+                shared: Some(name),
+            });
+        }
+    }
+
+    for newtype in &data.newtypes {
+        roots.push(TypeRoot {
+            ty: &newtype.ty,
+            site: format!("{}: newtype '{}'", sources.struct_files(&newtype.name), newtype.name),
+            shared: None,
+        });
+    }
+    for union in &data.unions {
+        for variant in &union.variants {
+            for field in &variant.fields {
+                roots.push(TypeRoot {
+                    ty: &field.ty,
+                    site: format!(
+                        "{}: tagged union '{}' variant '{}' field '{}'",
+                        sources.struct_files(&union.name),
+                        union.name,
+                        variant.name,
+                        field.name
+                    ),
+                    shared: None,
+                });
+            }
+        }
+    }
+
+    for service in &data.services {
+        for endpoint in &service.endpoints {
+            let schema = &endpoint.schema;
+            let prefix = format!(
+                "{}: service '{}' endpoint '{}'",
+                sources.endpoint_file(&service.name, &schema.name),
+                service.name,
+                schema.name
+            );
+            let mut push = |ty, site| roots.push(TypeRoot { ty, site, shared: None });
+            for field in &schema.parameters {
+                push(&field.ty, format!("{prefix} parameter '{}'", field.name));
+            }
+            for field in &schema.returns {
+                push(&field.ty, format!("{prefix} returns '{}'", field.name));
+            }
+            if let Some(stream) = &schema.stream_response {
+                push(stream, format!("{prefix} stream_response"));
+            }
+            for error in &schema.errors {
+                for field in &error.fields {
+                    push(
+                        &field.ty,
+                        format!("{prefix} error '{}' field '{}'", error.name, field.name),
+                    );
+                }
+            }
+        }
+        for event in &service.events {
+            for field in &event.fields {
+                roots.push(TypeRoot {
+                    ty: &field.ty,
+                    site: format!(
+                        "{}: service '{}' event '{}' field '{}'",
+                        sources.event_file(&service.name, &event.name),
+                        service.name,
+                        event.name,
+                        field.name
+                    ),
+                    shared: None,
+                });
+            }
+        }
+    }
+
+    roots
+}
+
+/// One place a named struct or enum is declared.
+struct NamedTypeSite<'a> {
+    ty: &'a Type,
+    site: String,
+    /// Shared definitions (`Data.structs` / `Data.enums`) as opposed to inline ones.
+    shared: bool,
+}
+
+/// Records `ty` if it declares a named struct or enum, then recurses into
+/// anything it contains. Refs are not declarations and are skipped.
+fn collect_named_types<'a>(ty: &'a Type, site: &str, shared: bool, out: &mut Vec<NamedTypeSite<'a>>) {
+    match ty {
+        Type::Struct { fields, .. } => {
+            out.push(NamedTypeSite {
+                ty,
+                site: site.to_string(),
+                shared,
+            });
+            for field in fields {
+                // Anything nested inside a declaration is an inline declaration of its own.
+                collect_named_types(&field.ty, &format!("{site} field '{}'", field.name), false, out);
+            }
+        }
+        Type::Enum { .. } => out.push(NamedTypeSite {
+            ty,
+            site: site.to_string(),
+            shared,
+        }),
+        Type::Vec(inner) | Type::Optional(inner) => collect_named_types(inner, site, shared, out),
+        _ => {}
+    }
+}
+
+fn named_type_sites<'a>(data: &'a Data, sources: &DefinitionSources) -> Vec<NamedTypeSite<'a>> {
+    let mut sites = vec![];
+    for root in type_roots(data, sources) {
+        collect_named_types(root.ty, &root.site, root.shared.is_some(), &mut sites);
+    }
+    sites
+}
+
+fn type_name(ty: &Type) -> &str {
+    match ty {
+        Type::Struct { name, .. } | Type::Enum { name, .. } => name,
+        _ => unreachable!("only named declarations are collected"),
+    }
+}
+
+fn kind(ty: &Type) -> &'static str {
+    match ty {
+        Type::Struct { .. } => "struct",
+        _ => "enum",
+    }
+}
+
+/// A compact, one-line rendering of a declaration's shape for error messages.
+fn describe_shape(ty: &Type) -> String {
+    match ty {
+        Type::Struct { name, fields } => format!(
+            "struct {name} {{ {} }}",
+            fields
+                .iter()
+                .map(|f| format!("{}: {}", f.name, f.ty.to_rust_ref(false)))
+                .join(", ")
+        ),
+        Type::Enum { name, variants } => format!(
+            "enum {name} {{ {} }}",
+            variants.iter().map(|v| format!("{} = {}", v.name, v.value)).join(", ")
+        ),
+        _ => unreachable!("only named declarations are collected"),
+    }
+}
+
+/// `ty` with every field and variant description cleared, recursively.
+/// Descriptions only reach doc comments and schema prose, so two declarations
+/// differing in them alone still describe the same type.
+fn normalized_shape(ty: &Type) -> Type {
+    let mut ty = ty.clone();
+    clear_descriptions(&mut ty);
+    ty
+}
+
+fn clear_descriptions(ty: &mut Type) {
+    match ty {
+        Type::Struct { fields, .. } => {
+            for field in fields {
+                field.description.clear();
+                clear_descriptions(&mut field.ty);
+            }
+        }
+        Type::Enum { variants, .. } => {
+            for variant in variants {
+                variant.description.clear();
+            }
+        }
+        Type::Vec(inner) | Type::Optional(inner) => clear_descriptions(inner),
+        _ => {}
+    }
+}
+
+/// Fails if the same struct/enum name is declared with different shapes
+/// anywhere in the project — shared definitions, inline endpoint types, or
+/// types nested inside either.
+///
+/// Names are compared in PascalCase, which is what both the generated Rust and
+/// the schema component names use, so `position` and `Position` collide too.
+/// A struct and an enum sharing a name is also a conflict: they would land on
+/// the same component name in the specification documents. So is any of them
+/// reusing the name of a newtype, a tagged union or one of its variant structs.
+/// Descriptions are not part of a shape.
+///
+/// Returns one hoisting suggestion per name that is declared identically more
+/// than once inline. Those are not errors — the Rust emitter deduplicates
+/// them — but each copy is a future conflict waiting for someone to edit only
+/// one of them.
+pub fn check_type_consistency(data: &Data, sources: &DefinitionSources) -> eyre::Result<Vec<String>> {
+    let sites = named_type_sites(data, sources);
+
+    let mut by_name: BTreeMap<String, Vec<&NamedTypeSite>> = BTreeMap::new();
+    for site in &sites {
+        by_name
+            .entry(type_name(site.ty).to_case(Case::Pascal))
+            .or_default()
+            .push(site);
+    }
+
+    // Names the Rust and schema emitters already claim for other declarations.
+    let mut reserved: BTreeMap<String, String> = BTreeMap::new();
+    for newtype in &data.newtypes {
+        reserved.insert(
+            newtype.name.to_case(Case::Pascal),
+            format!("{}: newtype '{}'", sources.struct_files(&newtype.name), newtype.name),
+        );
+    }
+    for union in &data.unions {
+        let files = sources.struct_files(&union.name);
+        reserved.insert(
+            union.name.to_case(Case::Pascal),
+            format!("{files}: tagged union '{}'", union.name),
+        );
+        for variant in &union.variants {
+            reserved.insert(
+                union.variant_struct_name(variant),
+                format!("{files}: tagged union '{}' variant '{}'", union.name, variant.name),
+            );
+        }
+    }
+
+    let mut conflicts = vec![];
+    let mut suggestions = vec![];
+
+    for (name, group) in &by_name {
+        let first = group[0];
+        // Shared definitions clashing with these are reported by `check_references`.
+        if let Some(owner) = reserved.get(name) {
+            for site in group.iter().filter(|s| !s.shared) {
+                conflicts.push(format!(
+                    "{} '{name}' reuses a name that is already taken:\n    {owner}\n    {}",
+                    kind(site.ty),
+                    site.site,
+                ));
+            }
+            continue;
+        }
+        let shape = normalized_shape(first.ty);
+        let shapes: Vec<_> = group.iter().map(|s| normalized_shape(s.ty)).collect();
+        for (other, other_shape) in group[1..].iter().zip(&shapes[1..]) {
+            if *other_shape != shape {
+                conflicts.push(format!(
+                    "{} '{name}' is declared with different shapes:\n    {}\n      {}\n    {}\n      {}",
+                    kind(first.ty),
+                    first.site,
+                    describe_shape(first.ty),
+                    other.site,
+                    describe_shape(other.ty),
+                ));
+            }
+        }
+
+        let inline: Vec<_> = group.iter().filter(|s| !s.shared).collect();
+        let has_shared = group.iter().any(|s| s.shared);
+        if group.len() > 1 && !inline.is_empty() && shapes.iter().all(|s| *s == shape) {
+            let locations = inline.iter().map(|s| format!("    {}", s.site)).join("\n");
+            let reference = match first.ty {
+                Type::Struct { name, .. } => format!("StructRef(\"{name}\")"),
+                _ => format!("EnumRef(name: \"{}\")", type_name(first.ty)),
+            };
+            suggestions.push(if has_shared {
+                format!(
+                    "{} '{name}' is already a shared definition but is redeclared inline at:\n{locations}\n  \
+                     Replace the inline copies with {reference}.",
+                    kind(first.ty)
+                )
+            } else {
+                format!(
+                    "{} '{name}' is declared identically inline {} times:\n{locations}\n  \
+                     Consider hoisting it into a shared {} definition and referencing it with {reference}.",
+                    kind(first.ty),
+                    inline.len(),
+                    if matches!(first.ty, Type::Struct { .. }) {
+                        "StructList"
+                    } else {
+                        "EnumList"
+                    },
+                )
+            });
+        }
+    }
+
+    if !conflicts.is_empty() {
+        bail!(
+            "Type consistency check failed for {} definition(s). The same name must describe the \
+             same type everywhere it is declared:\n  {}",
+            conflicts.len(),
+            conflicts.join("\n  ")
+        );
+    }
+
+    Ok(suggestions)
+}
+
+/// A `StructRef`, `StructTable` or `EnumRef` and where it was written.
+struct TypeReference<'a> {
+    ty: &'a Type,
+    site: String,
+    /// The shared definition the reference sits inside, if any.
+    owner: Option<&'a str>,
+}
+
+fn collect_references<'a>(ty: &'a Type, site: &str, owner: Option<&'a str>, out: &mut Vec<TypeReference<'a>>) {
+    match ty {
+        Type::StructRef(_) | Type::StructTable { .. } | Type::EnumRef { .. } => out.push(TypeReference {
+            ty,
+            site: site.to_string(),
+            owner,
+        }),
+        Type::Struct { fields, .. } => {
+            for field in fields {
+                collect_references(&field.ty, &format!("{site} field '{}'", field.name), owner, out);
+            }
+        }
+        Type::Vec(inner) | Type::Optional(inner) => collect_references(inner, site, owner, out),
+        _ => {}
+    }
+}
+
+/// The Rust name an `EnumRef` to `name` must use, or `None` if `name` is not
+/// an enum the model knows about.
+///
+/// Shared enums follow their own `prefix_enum` config. Everything else the
+/// registry knows — inline enums and the generated `ErrorCode` — is always
+/// emitted with the `Enum` prefix.
+fn expected_enum_prefix(data: &Data, registry: &TypeRegistry, name: &str) -> Option<bool> {
+    registry.get_enum(name)?;
+    Some(
+        data.enums
+            .iter()
+            .find(|e| matches!(&e.inner, Type::Enum { name: n, .. } if n == name))
+            .map(|e| e.config.prefix_enum)
+            .unwrap_or(true),
+    )
+}
+
+/// Fails on any `StructRef`, `StructTable` or `EnumRef` that does not resolve
+/// against [`build_registry`], and on any `EnumRef` whose `prefixed_name`
+/// disagrees with how its target is actually emitted — both would otherwise
+/// surface as a rustc error in `model.rs`, or as an opaque failure while
+/// collecting schema components.
+///
+/// Returns one warning per shared struct, enum, tagged union or newtype that
+/// nothing references. Roles count as references to their enum; a struct referencing
+/// only itself does not count as used.
+pub fn check_references(data: &Data, sources: &DefinitionSources) -> eyre::Result<Vec<String>> {
+    let registry = build_registry(data);
+
+    let mut references = vec![];
+    for root in type_roots(data, sources) {
+        collect_references(root.ty, &root.site, root.shared, &mut references);
+    }
+
+    let mut errors = vec![];
+    // Unions and newtypes are registered as structs under their own names;
+    // sharing a name would make every StructRef to it ambiguous.
+    let mut struct_names: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for element in &data.structs {
+        if let Type::Struct { name, .. } = &element.inner {
+            struct_names.entry(name).or_default().push("a shared struct");
+        }
+    }
+    for union in &data.unions {
+        struct_names.entry(&union.name).or_default().push("a tagged union");
+    }
+    for newtype in &data.newtypes {
+        struct_names.entry(&newtype.name).or_default().push("a newtype");
+    }
+    for (name, kinds) in &struct_names {
+        if kinds.len() > 1 {
+            errors.push(format!(
+                "{}: '{name}' is declared as {}",
+                sources.struct_files(name),
+                kinds.join(" and as ")
+            ));
+        }
+    }
+    let mut used_structs = BTreeSet::<String>::new();
+    let mut used_enums = BTreeSet::<String>::new();
+
+    for reference in &references {
+        match reference.ty {
+            Type::StructRef(name) | Type::StructTable { struct_ref: name } => {
+                // Type parameters are in scope inside the generic struct declaring them.
+                let scope = reference
+                    .owner
+                    .and_then(|owner| generics::find_generic(&data.structs, owner))
+                    .map(|generic| generic.type_params.as_slice())
+                    .unwrap_or_default();
+                let is_generic = generics::parse_generic_ref(name).is_some()
+                    || scope.contains(name)
+                    || generics::find_generic(&data.structs, name).is_some();
+                if is_generic {
+                    if let Err(err) =
+                        generics::check_type_name(name, data, &registry, scope, &mut used_structs, &mut used_enums)
+                    {
+                        errors.push(format!("{}: {err}", reference.site));
+                    }
+                } else if registry.get_struct(name).is_none() {
+                    errors.push(format!(
+                        "{}: {} references struct '{name}', which is not defined",
+                        reference.site,
+                        reference_kind(reference.ty)
+                    ));
+                } else if reference.owner != Some(name.as_str()) {
+                    used_structs.insert(name.clone());
+                }
+            }
+            Type::EnumRef { name, prefixed_name } => match expected_enum_prefix(data, &registry, name) {
+                None => errors.push(format!(
+                    "{}: EnumRef references enum '{name}', which is not defined",
+                    reference.site
+                )),
+                Some(expected) => {
+                    if expected != *prefixed_name {
+                        let emitted = crate::rust_item::enum_name(name, expected);
+                        errors.push(format!(
+                            "{}: EnumRef to '{name}' has prefixed_name: {prefixed_name}, so it would \
+                             reference `{}`, but the enum is generated as `{emitted}`; set prefixed_name: {expected}",
+                            reference.site,
+                            crate::rust_item::enum_name(name, *prefixed_name),
+                        ));
+                    }
+                    used_enums.insert(name.clone());
+                }
+            },
+            _ => unreachable!("only refs are collected"),
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "Reference check failed for {} reference(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+
+    // Roles are `Enum::Variant` strings resolved by name at generation time;
+    // see resolve_roles_ids. Either the declared or the generated name works.
+    for service in &data.services {
+        let roles = service
+            .endpoints
+            .iter()
+            .flat_map(|endpoint| &endpoint.schema.roles)
+            .chain(service.events.iter().flat_map(|event| &event.roles));
+        for role in roles {
+            let Some((enum_name, _)) = role.split_once("::") else {
+                continue;
+            };
+            for element in &data.enums {
+                if let Type::Enum { name, .. } = &element.inner
+                    && (name == enum_name || element.to_rust_ref(false) == enum_name)
+                {
+                    used_enums.insert(name.clone());
+                }
+            }
+        }
+    }
+
+    let mut warnings = vec![];
+    for element in &data.structs {
+        if let Type::Struct { name, .. } = &element.inner
+            && !used_structs.contains(name)
+        {
+            warnings.push(format!(
+                "{}: shared struct '{name}' is never referenced",
+                sources.struct_files(name)
+            ));
+        }
+    }
+    for newtype in data.newtypes.iter().filter(|n| !used_structs.contains(&n.name)) {
+        warnings.push(format!(
+            "{}: newtype '{}' is never referenced",
+            sources.struct_files(&newtype.name),
+            newtype.name
+        ));
+    }
+    for union in data.unions.iter().filter(|u| !used_structs.contains(&u.name)) {
+        warnings.push(format!(
+            "{}: tagged union '{}' is never referenced",
+            sources.struct_files(&union.name),
+            union.name
+        ));
+    }
+    for element in &data.enums {
+        if let Type::Enum { name, .. } = &element.inner
+            && !used_enums.contains(name)
+        {
+            warnings.push(format!(
+                "{}: shared enum '{name}' is never referenced",
+                sources.enum_files(name)
+            ));
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Checks that every field of a `worktable_support` struct can itself be
+/// stored in a worktable, so the missing `rkyv`/`MemStat` impls are reported
+/// against the RON instead of as trait errors in `model.rs`.
+///
+/// Primitives, strings, bytes and UUIDs are stored as they are; `Option`/`Vec`
+/// of those are too. Enums and structs must be shared definitions that set
+/// `worktable_support` themselves. Runs after [`check_references`], so
+/// references are known to resolve.
+pub fn check_worktable_structs(data: &Data, sources: &DefinitionSources) -> eyre::Result<()> {
+    let mut errors = vec![];
+    for element in data.structs.iter().filter(|s| s.config.worktable_support) {
+        let Type::Struct { name, fields } = &element.inner else {
+            continue;
+        };
+        let file = sources.struct_files(name);
+        if !element.type_params.is_empty() {
+            errors.push(format!(
+                "{file}: struct '{name}' is generic, which worktable does not support"
+            ));
+        }
+        for field in fields {
+            if let Some(reason) = worktable_incompatibility(&field.ty, data) {
+                errors.push(format!("{file}: struct '{name}' field '{}': {reason}", field.name));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "Worktable check failed for {} field(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks that every field of a `sql_table` struct maps to a column, so an
+/// unmappable field fails generation with its file instead of partway through
+/// writing `model.rs`. See [`crate::sql`] for the mapping.
+pub fn check_sql_tables(data: &Data, sources: &DefinitionSources) -> eyre::Result<()> {
+    let mut errors = vec![];
+    for element in data.structs.iter().filter(|s| s.config.sql_table) {
+        let Type::Struct { name, .. } = &element.inner else {
+            continue;
+        };
+        let file = sources.struct_files(name);
+        if !element.type_params.is_empty() {
+            errors.push(format!(
+                "{file}: struct '{name}' is generic, so it cannot be a SQL table"
+            ));
+        }
+        for (field, reason) in crate::sql::unmapped_fields(element, data) {
+            errors.push(format!("{file}: struct '{name}' field '{field}': {reason}"));
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "SQL table check failed for {} field(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks that `cancelled_by` is only set on `stream_response` endpoints and
+/// names another endpoint of the same service.
+pub fn check_stream_cancellation(data: &Data, sources: &DefinitionSources) -> eyre::Result<()> {
+    let mut errors = vec![];
+    for service in &data.services {
+        for element in &service.endpoints {
+            let Some(cancel) = &element.cancelled_by else {
+                continue;
+            };
+            let name = &element.schema.name;
+            let file = sources.endpoint_file(&service.name, name);
+            if element.schema.stream_response.is_none() {
+                errors.push(format!(
+                    "{file}: service '{}' endpoint '{name}' sets cancelled_by but has no stream_response",
+                    service.name
+                ));
+            }
+            if cancel == name || !service.endpoints.iter().any(|e| &e.schema.name == cancel) {
+                errors.push(format!(
+                    "{file}: service '{}' endpoint '{name}' is cancelled_by '{cancel}', which is not another endpoint of the service",
+                    service.name
+                ));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "Stream cancellation check failed for {} endpoint(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks that every rate-limited role is one of the endpoint's `roles`, and
+/// that no rate limit, timeout or payload size is zero.
+pub fn check_operational(data: &Data, sources: &DefinitionSources) -> eyre::Result<()> {
+    let mut errors = vec![];
+    for service in &data.services {
+        for element in &service.endpoints {
+            let operational = &element.operational;
+            let name = &element.schema.name;
+            let site = format!(
+                "{}: service '{}' endpoint '{name}'",
+                sources.endpoint_file(&service.name, name),
+                service.name
+            );
+            for (role, limit) in &operational.rate_limits {
+                if !element.schema.roles.contains(role) {
+                    errors.push(format!(
+                        "{site}: rate limit for '{role}', which is not one of its roles"
+                    ));
+                }
+                if limit.requests == 0 || limit.per_seconds == 0 {
+                    errors.push(format!("{site}: rate limit for '{role}' must be non-zero"));
+                }
+            }
+            if operational.timeout_ms == Some(0) {
+                errors.push(format!("{site}: timeout_ms must be non-zero"));
+            }
+            if operational.max_payload_bytes == Some(0) {
+                errors.push(format!("{site}: max_payload_bytes must be non-zero"));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "Operational metadata check failed for {} problem(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks that every endpoint exposed over MCP has a tool name of 1-64
+/// characters from `[A-Za-z0-9_-]`, and that no two share one. Tools from every
+/// service are served on the same socket, so names must be unique across
+/// services, not just within one.
+pub fn check_mcp_tools(data: &Data, sources: &DefinitionSources) -> eyre::Result<()> {
+    let mut errors = vec![];
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for service in &data.services {
+        for element in service.endpoints.iter().filter(|element| element.mcp.expose) {
+            let name = &element.schema.name;
+            let site = format!(
+                "{}: service '{}' endpoint '{name}'",
+                sources.endpoint_file(&service.name, name),
+                service.name
+            );
+            let tool = element.tool_name();
+            let valid = (1..=64).contains(&tool.len())
+                && tool.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+            if !valid {
+                errors.push(format!(
+                    "{site}: MCP tool name '{tool}' must be 1-64 letters, digits, '_' or '-'"
+                ));
+            }
+            let owner = format!("{}.{name}", service.name);
+            if let Some(first) = seen.get(&tool) {
+                errors.push(format!("{site}: MCP tool name '{tool}' is already used by {first}"));
+            } else {
+                seen.insert(tool, owner);
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "MCP tool check failed for {} problem(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// Checks the MCP resources and prompts of every service:
+///
+/// - a resource reads a read-only, non-streaming endpoint of its service, and
+///   the variables of its URI template are parameters of that endpoint,
+///   covering every required one;
+/// - a prompt relies only on endpoints of its service that are exposed as
+///   tools, and its placeholders are declared arguments;
+/// - resource names, resource URIs and prompt names are unique across services.
+pub fn check_mcp_catalog(data: &Data, sources: &DefinitionSources) -> eyre::Result<()> {
+    let mut errors = vec![];
+    let mut resource_names: HashMap<&str, String> = HashMap::new();
+    let mut resource_uris: HashMap<String, String> = HashMap::new();
+    let mut prompt_names: HashMap<&str, String> = HashMap::new();
+    for service in &data.services {
+        for resource in &service.resources {
+            let site = format!(
+                "{}: service '{}' MCP resource '{}'",
+                sources.resource_file(&service.name, &resource.name),
+                service.name,
+                resource.name
+            );
+            let owner = format!("{}.{}", service.name, resource.name);
+            if let Some(first) = resource_names.insert(&resource.name, owner.clone()) {
+                errors.push(format!("{site}: name is already used by {first}"));
+            }
+            let Some(endpoint) = service.endpoint(&resource.endpoint) else {
+                errors.push(format!(
+                    "{site}: endpoint '{}' is not an endpoint of the service",
+                    resource.endpoint
+                ));
+                continue;
+            };
+            if !endpoint.is_read_only() {
+                errors.push(format!(
+                    "{site}: endpoint '{}' is not read-only; set operational.safe or mcp.read_only",
+                    resource.endpoint
+                ));
+            }
+            if endpoint.schema.stream_response.is_some() {
+                errors.push(format!(
+                    "{site}: endpoint '{}' streams, and a resource is read with one response",
+                    resource.endpoint
+                ));
+            }
+            let uri = resource.uri_template(&service.name, endpoint);
+            let variables = uri_template_variables(&uri);
+            let parameters: Vec<_> = endpoint
+                .schema
+                .parameters
+                .iter()
+                .map(|field| (field.name.to_case(Case::Camel), !matches!(field.ty, Type::Optional(_))))
+                .collect();
+            for variable in &variables {
+                if !parameters.iter().any(|(name, _)| name == variable) {
+                    errors.push(format!("{site}: URI variable '{variable}' is not a parameter"));
+                }
+            }
+            for (name, required) in &parameters {
+                if *required && !variables.contains(name) {
+                    errors.push(format!("{site}: required parameter '{name}' is not in the URI"));
+                }
+            }
+            if let Some(first) = resource_uris.insert(uri.clone(), owner) {
+                errors.push(format!("{site}: URI '{uri}' is already used by {first}"));
+            }
+        }
+
+        for prompt in &service.prompts {
+            let site = format!(
+                "{}: service '{}' MCP prompt '{}'",
+                sources.prompt_file(&service.name, &prompt.name),
+                service.name,
+                prompt.name
+            );
+            if let Some(first) = prompt_names.insert(&prompt.name, format!("{}.{}", service.name, prompt.name)) {
+                errors.push(format!("{site}: name is already used by {first}"));
+            }
+            for name in &prompt.endpoints {
+                match service.endpoint(name) {
+                    None => errors.push(format!("{site}: '{name}' is not an endpoint of the service")),
+                    Some(endpoint) if !endpoint.mcp.expose => {
+                        errors.push(format!("{site}: endpoint '{name}' is not exposed as an MCP tool"))
+                    }
+                    Some(_) => {}
+                }
+            }
+            for placeholder in prompt.placeholders().into_iter().unique() {
+                if !prompt.arguments.iter().any(|argument| argument.name == placeholder) {
+                    errors.push(format!("{site}: placeholder '{{{placeholder}}}' is not an argument"));
+                }
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "MCP resource and prompt check failed for {} problem(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// The variable names of an RFC 6570 URI template, without operators
+/// (`{?a,b}`) or modifiers (`{a*}`, `{a:3}`).
+fn uri_template_variables(template: &str) -> Vec<String> {
+    let mut variables = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('}') else { break };
+        let expression = rest[..end].trim_start_matches(['+', '#', '.', '/', ';', '?', '&']);
+        for spec in expression.split(',') {
+            let name = spec.split(':').next().unwrap_or_default().trim_end_matches('*');
+            variables.push(name.to_string());
+        }
+        rest = &rest[end + 1..];
+    }
+    variables
+}
+
+/// Why `ty` cannot be a field of a worktable struct, or `None` if it can.
+fn worktable_incompatibility(ty: &Type, data: &Data) -> Option<String> {
+    match ty {
+        Type::UInt32
+        | Type::Int32
+        | Type::Int64
+        | Type::Float64
+        | Type::Boolean
+        | Type::String
+        | Type::Bytea
+        | Type::UUID
+        | Type::TimeStampMs => None,
+        Type::Optional(inner) | Type::Vec(inner) => worktable_incompatibility(inner, data),
+        Type::EnumRef { name, .. } => {
+            let element = data
+                .enums
+                .iter()
+                .find(|e| matches!(&e.inner, Type::Enum { name: n, .. } if n == name))?;
+            (!element.config.worktable_support).then(|| format!("enum '{name}' does not set worktable_support"))
+        }
+        Type::StructRef(name) | Type::StructTable { struct_ref: name } => {
+            if generics::parse_generic_ref(name).is_some() {
+                return Some(format!(
+                    "`{name}` is a map or generic type, which worktable does not support"
+                ));
+            }
+            if data.unions.iter().any(|u| &u.name == name) {
+                return Some(format!("'{name}' is a tagged union, which worktable does not support"));
+            }
+            if data.newtypes.iter().any(|n| &n.name == name) {
+                return Some(format!("'{name}' is a newtype, which worktable does not support"));
+            }
+            let element = data
+                .structs
+                .iter()
+                .find(|s| matches!(&s.inner, Type::Struct { name: n, .. } if n == name))?;
+            (!element.config.worktable_support).then(|| format!("struct '{name}' does not set worktable_support"))
+        }
+        Type::Struct { name, .. } => Some(format!(
+            "inline struct '{name}' cannot derive the worktable traits; declare it as a shared struct with \
+             worktable_support and use StructRef"
+        )),
+        Type::Enum { name, .. } => Some(format!(
+            "inline enum '{name}' cannot derive the worktable traits; declare it as a shared enum with \
+             worktable_support and use EnumRef"
+        )),
+        other => Some(format!(
+            "`{}` cannot be stored in a worktable",
+            other.to_rust_ref(false)
+        )),
+    }
+}
+
+fn reference_kind(ty: &Type) -> &'static str {
+    match ty {
+        Type::StructTable { .. } => "StructTable",
+        _ => "StructRef",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::definitions::{
+        EndpointSchemaElement, GenService, McpPromptArgument, McpPromptElement, McpResourceElement, RustGenConfig,
+        StructElement,
+    };
+    use endpoint_libs::model::{EndpointSchema, Field};
+
+    fn position(fields: Vec<Field>) -> Type {
+        Type::struct_("Position", fields)
+    }
+
+    fn endpoint(name: &str, code: u32, returns: Vec<Field>) -> EndpointSchemaElement {
+        EndpointSchemaElement {
+            frontend_facing: true,
+            config: RustGenConfig::default(),
+            schema: EndpointSchema::new(name, code, vec![], returns).with_description("Test endpoint."),
+            ..Default::default()
+        }
+    }
+
+    fn data_with(endpoints: Vec<EndpointSchemaElement>, structs: Vec<StructElement>) -> Data {
+        Data {
+            project_name: "test".into(),
+            spec: Default::default(),
+            project_root: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            services: vec![GenService::new("s1".into(), 1, endpoints)],
+            enums: vec![],
+            structs,
+            unions: vec![],
+            newtypes: vec![],
+            error_codes: vec![],
+        }
+    }
+
+    #[test]
+    fn conflicting_inline_structs_fail_with_both_locations() {
+        let data = data_with(
+            vec![
+                endpoint(
+                    "GetPosition",
+                    1,
+                    vec![Field::new(
+                        "position",
+                        position(vec![Field::new("symbol", Type::String)]),
+                    )],
+                ),
+                endpoint(
+                    "ListPositions",
+                    2,
+                    vec![Field::new(
+                        "positions",
+                        Type::vec(position(vec![Field::new("size", Type::Float64)])),
+                    )],
+                ),
+            ],
+            vec![],
+        );
+        let mut sources = DefinitionSources::new();
+        sources
+            .endpoints
+            .insert(("s1".into(), "GetPosition".into()), PathBuf::from("config/a.ron"));
+        sources
+            .endpoints
+            .insert(("s1".into(), "ListPositions".into()), PathBuf::from("config/b.ron"));
+
+        let err = check_type_consistency(&data, &sources).unwrap_err().to_string();
+        assert!(err.contains("struct 'Position'"), "{err}");
+        assert!(
+            err.contains("config/a.ron: service 's1' endpoint 'GetPosition' returns 'position'"),
+            "{err}"
+        );
+        assert!(
+            err.contains("config/b.ron: service 's1' endpoint 'ListPositions' returns 'positions'"),
+            "{err}"
+        );
+        assert!(err.contains("struct Position { symbol: String }"), "{err}");
+        assert!(err.contains("struct Position { size: f64 }"), "{err}");
+    }
+
+    #[test]
+    fn identical_inline_structs_pass_with_a_hoisting_suggestion() {
+        let shape = || position(vec![Field::new("symbol", Type::String)]);
+        let data = data_with(
+            vec![
+                endpoint("GetPosition", 1, vec![Field::new("position", shape())]),
+                endpoint("ListPositions", 2, vec![Field::new("positions", Type::vec(shape()))]),
+            ],
+            vec![],
+        );
+
+        let suggestions = check_type_consistency(&data, &DefinitionSources::new()).unwrap();
+        assert_eq!(suggestions.len(), 1, "{suggestions:?}");
+        assert!(suggestions[0].contains("declared identically inline 2 times"));
+        assert!(suggestions[0].contains("StructRef(\"Position\")"));
+    }
+
+    #[test]
+    fn inline_struct_disagreeing_with_shared_definition_fails() {
+        let data = data_with(
+            vec![endpoint(
+                "GetPosition",
+                1,
+                vec![Field::new(
+                    "position",
+                    position(vec![Field::new("symbol", Type::String)]),
+                )],
+            )],
+            vec![StructElement {
+                config: RustGenConfig::default(),
+                type_params: vec![],
+                inner: position(vec![Field::new("symbol", Type::Int64)]),
+            }],
+        );
+
+        let err = check_type_consistency(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("<unknown file>: struct 'Position'"), "{err}");
+        assert!(err.contains("endpoint 'GetPosition'"), "{err}");
+    }
+
+    #[test]
+    fn struct_and_enum_sharing_a_name_conflict() {
+        use endpoint_libs::model::EnumVariant;
+        let data = data_with(
+            vec![
+                endpoint("A", 1, vec![Field::new("side", Type::struct_("side", vec![]))]),
+                endpoint(
+                    "B",
+                    2,
+                    vec![Field::new(
+                        "side",
+                        Type::enum_("Side", vec![EnumVariant::new("Buy", 1)]),
+                    )],
+                ),
+            ],
+            vec![],
+        );
+        assert!(check_type_consistency(&data, &DefinitionSources::new()).is_err());
+    }
+
+    #[test]
+    fn inline_enums_differing_only_in_descriptions_agree() {
+        use endpoint_libs::model::EnumVariant;
+        let side = |description: &str| {
+            Field::new(
+                "side",
+                Type::enum_("Side", vec![EnumVariant::new_with_description("Buy", description, 1)]),
+            )
+        };
+        let data = data_with(
+            vec![
+                endpoint("A", 1, vec![side("Buying.")]),
+                endpoint("B", 2, vec![side("")]),
+            ],
+            vec![],
+        );
+
+        let suggestions = check_type_consistency(&data, &DefinitionSources::new()).unwrap();
+        assert!(suggestions[0].contains("enum 'Side' is declared identically inline 2 times"));
+    }
+
+    #[test]
+    fn inline_struct_reusing_a_newtype_or_union_name_fails() {
+        use crate::definitions::{NewtypeElement, TaggedUnionElement, TaggedVariant};
+        let mut data = data_with(
+            vec![
+                endpoint("A", 1, vec![Field::new("id", Type::struct_("UserId", vec![]))]),
+                endpoint(
+                    "B",
+                    2,
+                    vec![Field::new("fill", Type::struct_("OrderEventFilled", vec![]))],
+                ),
+            ],
+            vec![],
+        );
+        data.newtypes = vec![NewtypeElement {
+            config: RustGenConfig::default(),
+            name: "UserId".into(),
+            ty: Type::Int64,
+            description: String::new(),
+        }];
+        data.unions = vec![TaggedUnionElement {
+            config: RustGenConfig::default(),
+            name: "OrderEvent".into(),
+            tag: "type".into(),
+            description: String::new(),
+            variants: vec![TaggedVariant {
+                name: "Filled".into(),
+                description: String::new(),
+                fields: vec![],
+            }],
+        }];
+
+        let err = check_type_consistency(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("struct 'UserId' reuses a name"), "{err}");
+        assert!(err.contains("<unknown file>: newtype 'UserId'"), "{err}");
+        assert!(
+            err.contains("<unknown file>: tagged union 'OrderEvent' variant 'Filled'"),
+            "{err}"
+        );
+    }
+
+    fn role_enum(prefix_enum: bool) -> crate::definitions::EnumElement {
+        use endpoint_libs::model::EnumVariant;
+        crate::definitions::EnumElement {
+            config: RustGenConfig {
+                prefix_enum,
+                ..Default::default()
+            },
+            inner: Type::enum_("UserRole", vec![EnumVariant::new("Admin", 1)]),
+        }
+    }
+
+    #[test]
+    fn dangling_struct_ref_names_the_endpoint_and_file() {
+        let data = data_with(
+            vec![endpoint(
+                "GetUser",
+                1,
+                vec![Field::new("user", Type::struct_ref("UserInfo"))],
+            )],
+            vec![],
+        );
+        let mut sources = DefinitionSources::new();
+        sources
+            .endpoints
+            .insert(("s1".into(), "GetUser".into()), PathBuf::from("config/users.ron"));
+
+        let err = check_references(&data, &sources).unwrap_err().to_string();
+        assert!(
+            err.contains(
+                "config/users.ron: service 's1' endpoint 'GetUser' returns 'user': StructRef references \
+                 struct 'UserInfo', which is not defined"
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn dangling_enum_ref_inside_a_shared_struct_fails() {
+        let data = data_with(
+            vec![],
+            vec![StructElement {
+                config: RustGenConfig::default(),
+                type_params: vec![],
+                inner: Type::struct_("Order", vec![Field::new("side", Type::enum_ref("Side", false))]),
+            }],
+        );
+        let err = check_references(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("struct 'Order' field 'side': EnumRef references enum 'Side'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn prefixed_name_must_match_the_targets_prefix_enum() {
+        let mut data = data_with(
+            vec![endpoint(
+                "GetRole",
+                1,
+                vec![Field::new("role", Type::enum_ref("UserRole", true))],
+            )],
+            vec![],
+        );
+        data.enums = vec![role_enum(false)];
+        let err = check_references(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("reference `EnumUserRole`"), "{err}");
+        assert!(
+            err.contains("generated as `UserRole`; set prefixed_name: false"),
+            "{err}"
+        );
+
+        data.enums = vec![role_enum(true)];
+        assert!(check_references(&data, &DefinitionSources::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unused_shared_definitions_are_warned_about() {
+        let mut data = data_with(
+            vec![endpoint("Ping", 1, vec![])],
+            vec![StructElement {
+                config: RustGenConfig::default(),
+                type_params: vec![],
+                inner: Type::struct_(
+                    "Node",
+                    vec![Field::new("next", Type::optional(Type::struct_ref("Node")))],
+                ),
+            }],
+        );
+        data.enums = vec![role_enum(false)];
+
+        let warnings = check_references(&data, &DefinitionSources::new()).unwrap();
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("shared struct 'Node' is never referenced"));
+        assert!(warnings[1].contains("shared enum 'UserRole' is never referenced"));
+
+        // A role is a use of its enum.
+        data.services[0].endpoints[0].schema.roles = vec!["UserRole::Admin".into()];
+        let warnings = check_references(&data, &DefinitionSources::new()).unwrap();
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+    }
+
+    fn position_element() -> StructElement {
+        StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec![],
+            inner: position(vec![Field::new("symbol", Type::String)]),
+        }
+    }
+
+    #[test]
+    fn generic_references_resolve_parameters_and_instantiations() {
+        let paged = StructElement {
+            config: RustGenConfig::default(),
+            type_params: vec!["T".into()],
+            inner: Type::struct_("Paged", vec![Field::new("items", Type::vec(Type::struct_ref("T")))]),
+        };
+        let mut data = data_with(
+            vec![endpoint(
+                "ListPositions",
+                1,
+                vec![Field::new("page", Type::struct_ref("Paged<Position>"))],
+            )],
+            vec![paged, position_element()],
+        );
+        let warnings = check_references(&data, &DefinitionSources::new()).unwrap();
+        assert!(warnings.is_empty(), "both Paged and Position are used: {warnings:?}");
+
+        data.services[0].endpoints[0].schema.returns = vec![Field::new("page", Type::struct_ref("Paged"))];
+        let err = check_references(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("returns 'page': struct 'Paged' is generic and must be instantiated"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn newtype_sharing_a_struct_name_fails() {
+        let mut data = data_with(
+            vec![endpoint(
+                "GetPosition",
+                1,
+                vec![Field::new("position", Type::struct_ref("Position"))],
+            )],
+            vec![position_element()],
+        );
+        data.newtypes = vec![crate::definitions::NewtypeElement {
+            config: RustGenConfig::default(),
+            name: "Position".into(),
+            ty: Type::Int64,
+            description: String::new(),
+        }];
+
+        let err = check_references(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("'Position' is declared as a shared struct and as a newtype"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn worktable_struct_lists_every_incompatible_field() {
+        use endpoint_libs::model::EnumVariant;
+        let worktable = RustGenConfig {
+            worktable_support: true,
+            ..Default::default()
+        };
+        let mut data = data_with(
+            vec![],
+            vec![
+                StructElement {
+                    config: worktable.clone(),
+                    type_params: vec![],
+                    inner: Type::struct_(
+                        "Order",
+                        vec![
+                            Field::new("id", Type::Int64),
+                            Field::new("tags", Type::optional(Type::vec(Type::String))),
+                            Field::new("side", Type::enum_ref("Side", false)),
+                            Field::new("fill", Type::struct_ref("Fill")),
+                            Field::new("position", Type::struct_ref("Position")),
+                            Field::new("ip", Type::IpAddr),
+                        ],
+                    ),
+                },
+                StructElement {
+                    config: worktable,
+                    type_params: vec![],
+                    inner: Type::struct_("Fill", vec![Field::new("price", Type::Float64)]),
+                },
+                position_element(),
+            ],
+        );
+        data.enums = vec![crate::definitions::EnumElement {
+            config: RustGenConfig::default(),
+            inner: Type::enum_("Side", vec![EnumVariant::new("Buy", 1)]),
+        }];
+        let mut sources = DefinitionSources::new();
+        sources
+            .structs
+            .insert("Order".into(), vec![PathBuf::from("config/orders.ron")]);
+
+        let err = check_worktable_structs(&data, &sources).unwrap_err().to_string();
+        assert!(err.starts_with("Worktable check failed for 3 field(s)"), "{err}");
+        assert!(
+            err.contains("config/orders.ron: struct 'Order' field 'side': enum 'Side' does not set worktable_support"),
+            "{err}"
+        );
+        assert!(
+            err.contains("field 'position': struct 'Position' does not set worktable_support"),
+            "{err}"
+        );
+        assert!(err.contains("field 'ip': `IpAddr` cannot be stored"), "{err}");
+        assert!(!err.contains("'tags'") && !err.contains("'fill'"), "{err}");
+    }
+
+    #[test]
+    fn cancelled_by_needs_a_stream_and_another_endpoint_of_the_service() {
+        let mut subscribe = endpoint("SubscribePositions", 10, vec![]);
+        subscribe.schema = subscribe.schema.with_stream_response_type(Type::struct_ref("Position"));
+        subscribe.cancelled_by = Some("UnsubscribePositions".into());
+        let mut lookup = endpoint("GetPosition", 11, vec![]);
+        lookup.cancelled_by = Some("Missing".into());
+        let unsubscribe = endpoint("UnsubscribePositions", 12, vec![]);
+
+        let mut data = data_with(vec![subscribe, unsubscribe.clone()], vec![]);
+        check_stream_cancellation(&data, &DefinitionSources::new()).unwrap();
+
+        data.services[0].endpoints = vec![lookup, unsubscribe];
+        let err = check_stream_cancellation(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("Stream cancellation check failed for 2 endpoint(s)"),
+            "{err}"
+        );
+        assert!(
+            err.contains("'GetPosition' sets cancelled_by but has no stream_response"),
+            "{err}"
+        );
+        assert!(
+            err.contains("cancelled_by 'Missing', which is not another endpoint"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn rate_limits_must_name_the_endpoints_roles() {
+        let mut get = endpoint("GetPosition", 10, vec![]);
+        get.schema.roles = vec!["UserRole::Trader".into()];
+        get.operational.rate_limits.insert(
+            "UserRole::Trader".into(),
+            crate::definitions::RateLimit {
+                requests: 10,
+                per_seconds: 1,
+            },
+        );
+        let mut data = data_with(vec![get], vec![]);
+        check_operational(&data, &DefinitionSources::new()).unwrap();
+
+        let operational = &mut data.services[0].endpoints[0].operational;
+        operational.rate_limits.insert(
+            "UserRole::Admin".into(),
+            crate::definitions::RateLimit {
+                requests: 0,
+                per_seconds: 1,
+            },
+        );
+        operational.timeout_ms = Some(0);
+        let err = check_operational(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("Operational metadata check failed for 3 problem(s)"),
+            "{err}"
+        );
+        assert!(
+            err.contains("rate limit for 'UserRole::Admin', which is not one of its roles"),
+            "{err}"
+        );
+        assert!(err.contains("timeout_ms must be non-zero"), "{err}");
+    }
+
+    #[test]
+    fn mcp_tool_names_are_valid_and_unique_across_services() {
+        let mut data = data_with(vec![endpoint("GetPosition", 10, vec![])], vec![]);
+        let mut hidden = endpoint("GetPosition", 20, vec![]);
+        hidden.mcp.expose = false;
+        data.services.push(GenService::new(
+            "s2".into(),
+            2,
+            vec![hidden, endpoint("ListOrders", 21, vec![])],
+        ));
+        check_mcp_tools(&data, &DefinitionSources::new()).unwrap();
+
+        data.services[1].endpoints[1].mcp.name = Some("get_position".into());
+        data.services[0].endpoints.push(endpoint("Cancel", 11, vec![]));
+        data.services[0].endpoints[1].mcp.name = Some("cancel order".into());
+        let err = check_mcp_tools(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("MCP tool check failed for 2 problem(s)"), "{err}");
+        assert!(
+            err.contains("endpoint 'ListOrders': MCP tool name 'get_position' is already used by s1.GetPosition"),
+            "{err}"
+        );
+        assert!(err.contains("MCP tool name 'cancel order' must be"), "{err}");
+    }
+
+    #[test]
+    fn mcp_resources_read_read_only_endpoints_and_prompts_fill_their_arguments() {
+        let mut get = endpoint("GetOrder", 10, vec![]);
+        get.schema.parameters = vec![Field::new("order_id", Type::Int64)];
+        get.operational.safe = true;
+        let mut cancel = endpoint("CancelOrder", 11, vec![]);
+        cancel.mcp.expose = false;
+        let mut data = data_with(vec![get, cancel], vec![]);
+        let resource = |name: &str, endpoint: &str, uri_template: Option<&str>| McpResourceElement {
+            service_name: "s1".into(),
+            service_id: 1,
+            name: name.into(),
+            endpoint: endpoint.into(),
+            uri_template: uri_template.map(String::from),
+            title: None,
+            description: String::new(),
+            mime_type: None,
+        };
+        let prompt = |endpoints: &[&str], template: &str| McpPromptElement {
+            service_name: "s1".into(),
+            service_id: 1,
+            name: "review".into(),
+            title: None,
+            description: "Reviews an order.".into(),
+            arguments: vec![McpPromptArgument {
+                name: "order_id".into(),
+                description: String::new(),
+                required: true,
+            }],
+            endpoints: endpoints.iter().map(|e| e.to_string()).collect(),
+            template: template.into(),
+        };
+        data.services[0].resources = vec![resource("order", "GetOrder", Some("orders://{orderId}"))];
+        data.services[0].prompts = vec![prompt(&["GetOrder"], "Review order {order_id}.")];
+        check_mcp_catalog(&data, &DefinitionSources::new()).unwrap();
+
+        data.services[0].resources = vec![
+            resource("order", "GetOrder", Some("orders://{id}")),
+            resource("cancel", "CancelOrder", None),
+        ];
+        data.services[0].prompts = vec![prompt(&["CancelOrder", "Missing"], "Review {order} {order_id}.")];
+        let err = check_mcp_catalog(&data, &DefinitionSources::new())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("MCP resource and prompt check failed for 6 problem(s)"),
+            "{err}"
+        );
+        assert!(err.contains("URI variable 'id' is not a parameter"), "{err}");
+        assert!(err.contains("required parameter 'orderId' is not in the URI"), "{err}");
+        assert!(err.contains("endpoint 'CancelOrder' is not read-only"), "{err}");
+        assert!(
+            err.contains("endpoint 'CancelOrder' is not exposed as an MCP tool"),
+            "{err}"
+        );
+        assert!(err.contains("'Missing' is not an endpoint of the service"), "{err}");
+        assert!(err.contains("placeholder '{order}' is not an argument"), "{err}");
+    }
+}